@@ -9,3 +9,4 @@ pub mod fs;
 pub mod net;
 pub mod os;
 pub mod proc;
+pub mod user;
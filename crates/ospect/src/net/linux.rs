@@ -163,21 +163,33 @@ pub fn interfaces() -> std::io::Result<impl Iterator<Item = Interface>> {
 }
 
 /// Returns an iterator over IPv4 TCP connections for the specified process.
+///
+/// Listing connections requires access to file descriptors of the process, so
+/// it fails for processes of other users unless run with elevated privileges.
 pub fn tcp_v4_connections(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<TcpConnectionV4>>> {
     self::conn::tcp_v4(pid)
 }
 
 /// Returns an iterator over IPv6 TCP connections for the specified process.
+///
+/// Listing connections requires access to file descriptors of the process, so
+/// it fails for processes of other users unless run with elevated privileges.
 pub fn tcp_v6_connections(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<TcpConnectionV6>>> {
     self::conn::tcp_v6(pid)
 }
 
 /// Returns an iterator over IPv4 UDP connections for the specified process.
+///
+/// Listing connections requires access to file descriptors of the process, so
+/// it fails for processes of other users unless run with elevated privileges.
 pub fn udp_v4_connections(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<UdpConnectionV4>>> {
     self::conn::udp_v4(pid)
 }
 
 /// Returns an iterator over IPv6 UDP connections for the specified process.
+///
+/// Listing connections requires access to file descriptors of the process, so
+/// it fails for processes of other users unless run with elevated privileges.
 pub fn udp_v6_connections(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<UdpConnectionV6>>> {
     self::conn::udp_v6(pid)
 }
@@ -10,7 +10,7 @@ pub fn tcp_v4(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<
     let path = format!("/proc/{pid}/net/tcp");
     Ok(TcpConnections {
         pid,
-        iter: Connections::new(path, parse_tcp_v4_connection)?,
+        iter: Connections::new(pid, path, parse_tcp_v4_connection)?,
     }.map(|conn| Ok(TcpConnectionV4::from_inner(conn?))))
 }
 
@@ -19,7 +19,7 @@ pub fn tcp_v6(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<
     let path = format!("/proc/{pid}/net/tcp6");
     Ok(TcpConnections {
         pid,
-        iter: Connections::new(path, parse_tcp_v6_connection)?,
+        iter: Connections::new(pid, path, parse_tcp_v6_connection)?,
     }.map(|conn| Ok(TcpConnectionV6::from_inner(conn?))))
 }
 
@@ -28,7 +28,7 @@ pub fn udp_v4(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<
     let path = format!("/proc/{pid}/net/udp");
     Ok(UdpConnections {
         pid,
-        iter: Connections::new(path, parse_udp_v4_connection)?,
+        iter: Connections::new(pid, path, parse_udp_v4_connection)?,
     }.map(|conn| Ok(UdpConnectionV4::from_inner(conn?))))
 }
 
@@ -37,7 +37,7 @@ pub fn udp_v6(pid: u32) -> std::io::Result<impl Iterator<Item = std::io::Result<
     let path = format!("/proc/{pid}/net/udp6");
     Ok(UdpConnections {
         pid,
-        iter: Connections::new(path, parse_udp_v6_connection)?,
+        iter: Connections::new(pid, path, parse_udp_v6_connection)?,
     }.map(|conn| Ok(UdpConnectionV6::from_inner(conn?))))
 }
 
@@ -112,6 +112,8 @@ struct Connections<C> {
     lines: std::io::Lines<std::io::BufReader<std::fs::File>>,
     /// Function to use for parsing connection information.
     parse_connection: fn(&str) -> Result<C, ParseConnectionError>,
+    /// Inodes of sockets owned by the process.
+    inodes: std::collections::HashSet<u64>,
 }
 
 impl<C> Connections<C> {
@@ -122,8 +124,20 @@ impl<C> Connections<C> {
     /// `parse_connection` should be a function that can parse the lines of that
     /// file.
     ///
+    /// Note that the procfs file lists connections of all processes within the
+    /// network namespace of the process identified by `pid`. Thus, only those
+    /// connections that belong to sockets owned by that process are yielded.
+    ///
+    /// Determining which sockets belong to the process requires listing its
+    /// file descriptors in `/proc/{pid}/fd`. Unlike the connection files, this
+    /// directory is accessible only to the owner of the process (or to callers
+    /// with `CAP_SYS_PTRACE`), so for processes of other users this function
+    /// fails with a permission error instead of yielding connections of the
+    /// whole network namespace.
+    ///
     /// [1]: https://docs.kernel.org/filesystems/proc.html#networking-info-in-proc-net
     fn new<P>(
+        pid: u32,
         path: P,
         parse_connection: fn(&str) -> Result<C, ParseConnectionError>,
     ) -> std::io::Result<Connections<C>>
@@ -132,6 +146,8 @@ impl<C> Connections<C> {
     {
         use std::io::BufRead as _;
 
+        let inodes = socket_inodes(pid)?;
+
         let file = std::fs::File::open(path)?;
         let mut lines = std::io::BufReader::new(file).lines();
         if lines.next().is_none() {
@@ -142,6 +158,7 @@ impl<C> Connections<C> {
         Ok(Connections {
             lines,
             parse_connection,
+            inodes,
         })
     }
 }
@@ -150,19 +167,72 @@ impl<C> Iterator for Connections<C> {
     type Item = std::io::Result<C>;
 
     fn next(&mut self) -> Option<std::io::Result<C>> {
-        let line = match self.lines.next() {
-            None => return None,
-            Some(Ok(line)) => line,
-            Some(Err(error)) => return Some(Err(error)),
+        loop {
+            let line = match self.lines.next() {
+                None => return None,
+                Some(Ok(line)) => line,
+                Some(Err(error)) => return Some(Err(error)),
+            };
+
+            match parse_inode(&line) {
+                Ok(inode) if self.inodes.contains(&inode) => (),
+                Ok(_) => continue,
+                Err(error) => return Some(Err({
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+                })),
+            }
+
+            return match (self.parse_connection)(&line) {
+                Ok(conn) => Some(Ok(conn)),
+                Err(error) => Some(Err({
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+                })),
+            };
+        }
+    }
+}
+
+/// Returns inodes of all sockets owned by the specified process.
+fn socket_inodes(pid: u32) -> std::io::Result<std::collections::HashSet<u64>> {
+    let mut inodes = std::collections::HashSet::new();
+
+    for entry in std::fs::read_dir(format!("/proc/{pid}/fd"))? {
+        // The file descriptor might have been closed in the meantime (or the
+        // process might have exited), in which case we simply skip it.
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
         };
 
-        match (self.parse_connection)(&line) {
-            Ok(conn) => Some(Ok(conn)),
-            Err(error) => Some(Err({
-                std::io::Error::new(std::io::ErrorKind::InvalidData, error)
-            })),
+        let target = match std::fs::read_link(entry.path()) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        // Links of socket file descriptors are pseudo-paths of the form
+        // `socket:[<inode>]`. All other links can be ignored.
+        let inode = target.to_str()
+            .and_then(|target| target.strip_prefix("socket:["))
+            .and_then(|target| target.strip_suffix(']'))
+            .and_then(|inode| inode.parse().ok());
+
+        if let Some(inode) = inode {
+            inodes.insert(inode);
         }
     }
+
+    Ok(inodes)
+}
+
+/// Parses the socket inode of a connection information in the procfs format.
+fn parse_inode(string: &str) -> Result<u64, ParseConnectionError> {
+    // The inode is the 10th column of the connection line (after the line
+    // number, addresses, state, queues, timer information, retransmit count,
+    // user identifier and timeout).
+    string.split_whitespace().nth(9)
+        .ok_or(ParseConnectionError::InvalidFormat)?
+        .parse()
+        .map_err(|_| ParseConnectionError::InvalidFormat)
 }
 
 /// Parses a TCP IPv4 connection information in the procfs format.
@@ -476,6 +546,54 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn tcp_v4_other_process_socket() {
+        use std::net::Ipv4Addr;
+
+        let server = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap();
+        let server_addr = server.local_addr()
+            .unwrap();
+
+        // The child process shares the network namespace with us, so its procfs
+        // connection file lists our listener as well. Sockets are created with
+        // the close-on-exec flag though, so the child does not own it.
+        let mut child = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
+
+        let conns = tcp_v4(child.id())
+            .unwrap()
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert! {
+            !conns.iter().any(|conn| server_addr == conn.local_addr().into())
+        };
+    }
+
+    #[test]
+    fn parse_inode_ok() {
+        let inode = parse_inode(
+            "0: 0400007F:1A29 00000000:0000 0A 00000000:00000000 00:00000000 00000000 0 0 666333 1 0000000000000000 100 0 0 10 0"
+        ).unwrap();
+
+        assert_eq!(inode, 666333);
+    }
+
+    #[test]
+    fn parse_inode_missing() {
+        let error = parse_inode(
+            "0: 0400007F:1A29 00000000:0000 0A 00000000:00000000"
+        ).unwrap_err();
+
+        assert_eq!(error, ParseConnectionError::InvalidFormat);
+    }
+
     #[test]
     fn parse_tcp_v4_connection_ok() {
         let conn = parse_tcp_v4_connection(
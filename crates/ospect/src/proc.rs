@@ -9,6 +9,9 @@ mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
 
+#[cfg(target_family = "unix")]
+mod unix;
+
 #[cfg(target_os = "windows")]
 mod windows;
 
@@ -45,3 +48,239 @@ mod sys {
 pub fn ids() -> std::io::Result<impl Iterator<Item = std::io::Result<u32>>> {
     self::sys::ids()
 }
+
+/// Returns information about the process with the specified identifier.
+///
+/// # Errors
+///
+/// The function will return an error if the process does not exist (e.g. it
+/// has exited in the meantime) or if the operating system does not allow to
+/// get the required information.
+///
+/// # Examples
+///
+/// ```no_run
+/// let process = ospect::proc::info(std::process::id())
+///     .unwrap();
+///
+/// assert_eq!(process.pid(), std::process::id());
+/// ```
+pub fn info(pid: u32) -> std::io::Result<Process> {
+    self::sys::info(pid)
+}
+
+/// Returns an iterator yielding information about all processes on the system.
+///
+/// The order in which the processes are yield is not defined. Failures to get
+/// information about particular processes (e.g. because they have exited in
+/// the meantime) are yielded as errors and do not stop the iteration.
+///
+/// # Errors
+///
+/// The function will return an error if the operating system does not allow
+/// get the required information (e.g. in case of insufficient permissions).
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut processes = ospect::proc::processes()
+///     .unwrap()
+///     .filter_map(Result::ok);
+///
+/// assert! {
+///     processes.find(|proc| proc.pid() == std::process::id()).is_some()
+/// };
+/// ```
+pub fn processes() -> std::io::Result<impl Iterator<Item = std::io::Result<Process>>> {
+    self::sys::processes()
+}
+
+/// Returns the total CPU time (both user and kernel) consumed by the current
+/// process.
+///
+/// # Errors
+///
+/// The function will return an error if the operating system does not allow
+/// to get the required information.
+///
+/// # Examples
+///
+/// ```
+/// let cpu_time = ospect::proc::cpu_time()
+///     .unwrap();
+///
+/// assert!(cpu_time > std::time::Duration::ZERO);
+/// ```
+pub fn cpu_time() -> std::io::Result<std::time::Duration> {
+    self::sys::cpu_time()
+}
+
+/// Returns the total CPU time (both user and kernel) consumed by the current
+/// thread.
+///
+/// # Errors
+///
+/// The function will return an error if the operating system does not allow
+/// to get the required information.
+///
+/// # Examples
+///
+/// ```
+/// let cpu_time = ospect::proc::thread_cpu_time()
+///     .unwrap();
+///
+/// assert!(cpu_time > std::time::Duration::ZERO);
+/// ```
+pub fn thread_cpu_time() -> std::io::Result<std::time::Duration> {
+    self::sys::thread_cpu_time()
+}
+
+/// Information about a process running on the system.
+#[derive(Clone, Debug)]
+pub struct Process {
+    /// Identifier of the process.
+    pid: u32,
+    /// Identifier of the parent process.
+    ppid: u32,
+    /// Name of the process (usually the name of the executable).
+    name: std::ffi::OsString,
+    /// Path to the executable of the process (if available).
+    exe: Option<std::path::PathBuf>,
+    /// Command-line arguments of the process (including the program name).
+    cmdline: Vec<std::ffi::OsString>,
+    /// Current working directory of the process (if available).
+    cwd: Option<std::path::PathBuf>,
+    /// Real identifier of the user owning the process.
+    #[cfg(target_family = "unix")]
+    uid: u32,
+    /// Real identifier of the group owning the process.
+    #[cfg(target_family = "unix")]
+    gid: u32,
+    /// Time at which the process was started.
+    start_time: std::time::SystemTime,
+    /// State the process is in.
+    state: State,
+    /// Resident set size of the process (in bytes).
+    rss: u64,
+    /// Virtual memory size of the process (in bytes).
+    vsz: u64,
+    /// Time the process has been scheduled in user mode.
+    user_time: std::time::Duration,
+    /// Time the process has been scheduled in kernel mode.
+    system_time: std::time::Duration,
+    /// Number of threads in the process.
+    thread_count: u32,
+}
+
+impl Process {
+
+    /// Returns the identifier of the process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns the identifier of the parent process.
+    pub fn ppid(&self) -> u32 {
+        self.ppid
+    }
+
+    /// Returns the name of the process.
+    ///
+    /// Note that on some systems this name can be truncated (e.g. on Linux it
+    /// is limited to 15 bytes). For the full path to the executable, refer to
+    /// [`Process::exe`].
+    pub fn name(&self) -> &std::ffi::OsStr {
+        self.name.as_os_str()
+    }
+
+    /// Returns the path to the executable of the process (if available).
+    ///
+    /// The path might not be available in case of insufficient permissions or
+    /// for special processes (like kernel threads).
+    pub fn exe(&self) -> Option<&std::path::Path> {
+        self.exe.as_deref()
+    }
+
+    /// Returns the command-line arguments of the process.
+    ///
+    /// The first argument is usually (but not necessarily) the program name.
+    pub fn cmdline(&self) -> &[std::ffi::OsString] {
+        self.cmdline.as_slice()
+    }
+
+    /// Returns the current working directory of the process (if available).
+    ///
+    /// The directory might not be available in case of insufficient
+    /// permissions or for special processes (like kernel threads).
+    pub fn cwd(&self) -> Option<&std::path::Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Returns the real identifier of the user owning the process.
+    #[cfg(target_family = "unix")]
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the real identifier of the group owning the process.
+    #[cfg(target_family = "unix")]
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Returns the time at which the process was started.
+    pub fn start_time(&self) -> std::time::SystemTime {
+        self.start_time
+    }
+
+    /// Returns the state the process is in.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns the resident set size of the process (in bytes).
+    pub fn rss(&self) -> u64 {
+        self.rss
+    }
+
+    /// Returns the virtual memory size of the process (in bytes).
+    pub fn vsz(&self) -> u64 {
+        self.vsz
+    }
+
+    /// Returns the time the process has been scheduled in user mode.
+    pub fn user_time(&self) -> std::time::Duration {
+        self.user_time
+    }
+
+    /// Returns the time the process has been scheduled in kernel mode.
+    pub fn system_time(&self) -> std::time::Duration {
+        self.system_time
+    }
+
+    /// Returns the number of threads in the process.
+    pub fn thread_count(&self) -> u32 {
+        self.thread_count
+    }
+}
+
+/// A list of possible states of a process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// The process is running (or is runnable).
+    Running,
+    /// The process is in an interruptible sleep (e.g. waiting for an event).
+    Sleeping,
+    /// The process is in an uninterruptible sleep (usually waiting for I/O).
+    DiskSleep,
+    /// The process is stopped (e.g. by a signal or a debugger).
+    Stopped,
+    /// The process has terminated but has not been reaped by its parent yet.
+    Zombie,
+    /// The process is dead.
+    Dead,
+    /// The process is an idle kernel thread.
+    Idle,
+    /// The process is in a state not covered by other variants.
+    Other,
+}
@@ -8,6 +8,28 @@ pub fn ids() -> std::io::Result<impl Iterator<Item = std::io::Result<u32>>> {
     Ids::new()
 }
 
+/// Returns information about the process with the specified identifier.
+pub fn info(pid: u32) -> std::io::Result<crate::proc::Process> {
+    Context::new()?.info(pid)
+}
+
+/// Returns an iterator yielding information about all processes on the system.
+pub fn processes() -> std::io::Result<impl Iterator<Item = std::io::Result<crate::proc::Process>>> {
+    let context = Context::new()?;
+
+    Ok(ids()?.map(move |pid| context.info(pid?)))
+}
+
+/// Returns the total CPU time consumed by the current process.
+pub fn cpu_time() -> std::io::Result<std::time::Duration> {
+    crate::proc::unix::cpu_time()
+}
+
+/// Returns the total CPU time consumed by the current thread.
+pub fn thread_cpu_time() -> std::io::Result<std::time::Duration> {
+    crate::proc::unix::thread_cpu_time()
+}
+
 /// A Linux-specific implementation of the iterator over process identifiers.
 struct Ids {
     /// An iterator over contents of the `/proc` directory.
@@ -67,6 +89,408 @@ impl Iterator for Ids {
         }
 
         None
+    }
+}
+
+/// System-wide information needed to interpret per-process data.
+struct Context {
+    /// Time at which the system was booted.
+    boot_time: std::time::SystemTime,
+    /// Number of clock ticks per second (as used by the procfs).
+    clock_ticks: u64,
+    /// Size of a memory page (in bytes).
+    page_size: u64,
+}
+
+impl Context {
+
+    /// Collects the system-wide information as of now.
+    fn new() -> std::io::Result<Context> {
+        Ok(Context {
+            boot_time: boot_time()?,
+            clock_ticks: sysconf(libc::_SC_CLK_TCK)?,
+            page_size: sysconf(libc::_SC_PAGESIZE)?,
+        })
+    }
+
+    /// Returns information about the process with the specified identifier.
+    fn info(&self, pid: u32) -> std::io::Result<crate::proc::Process> {
+        let path = std::path::PathBuf::from(format!("/proc/{pid}"));
+
+        let stat = parse_stat(&std::fs::read(path.join("stat"))?)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        let status = parse_status(&std::fs::read(path.join("status"))?)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        let cmdline = parse_cmdline(&std::fs::read(path.join("cmdline"))?);
+
+        // Links to the executable and the working directory are not available
+        // for kernel threads and are not readable for processes owned by other
+        // users (unless we have the required privileges). In such cases we just
+        // report them as missing instead of failing entirely.
+        let exe = std::fs::read_link(path.join("exe")).ok();
+        let cwd = std::fs::read_link(path.join("cwd")).ok();
+
+        Ok(crate::proc::Process {
+            pid,
+            ppid: stat.ppid,
+            name: stat.name,
+            exe,
+            cmdline,
+            cwd,
+            uid: status.uid,
+            gid: status.gid,
+            start_time: self.boot_time + self.ticks_to_duration(stat.start_time),
+            state: stat.state,
+            rss: stat.rss * self.page_size,
+            vsz: stat.vsize,
+            user_time: self.ticks_to_duration(stat.utime),
+            system_time: self.ticks_to_duration(stat.stime),
+            thread_count: stat.num_threads,
+        })
+    }
+
+    /// Converts the number of clock ticks to a duration.
+    fn ticks_to_duration(&self, ticks: u64) -> std::time::Duration {
+        let secs = ticks / self.clock_ticks;
+        let nanos = (ticks % self.clock_ticks) * 1_000_000_000 / self.clock_ticks;
+
+        std::time::Duration::from_secs(secs) + std::time::Duration::from_nanos(nanos)
+    }
+}
+
+/// Returns the time at which the system was booted.
+fn boot_time() -> std::io::Result<std::time::SystemTime> {
+    use std::io::BufRead as _;
+
+    let file = std::fs::File::open("/proc/stat")?;
+
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+
+        let secs = match line.strip_prefix("btime ") {
+            Some(secs) => secs,
+            None => continue,
+        };
+
+        let secs = secs.trim().parse::<u64>()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        return Ok(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+    }
+
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "no boot time"))
+}
+
+/// Returns the value of the specified configurable system variable.
+fn sysconf(name: libc::c_int) -> std::io::Result<u64> {
+    // SAFETY: `sysconf` is always safe to call. In case the variable is not
+    // supported, it returns -1 (and this is checked below).
+    let value = unsafe {
+        libc::sysconf(name)
+    };
+
+    if value <= 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(value as u64)
+}
+
+/// Process information parsed from the `/proc/<pid>/stat` file.
+#[derive(Debug)]
+struct Stat {
+    /// Name of the process (the `comm` field).
+    name: std::ffi::OsString,
+    /// State the process is in.
+    state: crate::proc::State,
+    /// Identifier of the parent process.
+    ppid: u32,
+    /// Time the process has been scheduled in user mode (in clock ticks).
+    utime: u64,
+    /// Time the process has been scheduled in kernel mode (in clock ticks).
+    stime: u64,
+    /// Number of threads in the process.
+    num_threads: u32,
+    /// Time the process started after system boot (in clock ticks).
+    start_time: u64,
+    /// Virtual memory size of the process (in bytes).
+    vsize: u64,
+    /// Resident set size of the process (in pages).
+    rss: u64,
+}
+
+/// Parses the contents of the `/proc/<pid>/stat` file.
+///
+/// See the `proc(5)` manual page [1] for the description of the format.
+///
+/// [1]: https://man7.org/linux/man-pages/man5/proc.5.html
+fn parse_stat(bytes: &[u8]) -> Result<Stat, ParseError> {
+    use std::os::unix::ffi::OsStringExt as _;
+
+    // The name of the process is enclosed in parentheses, but the name itself
+    // can contain parentheses (and spaces), so we have to look for the *last*
+    // closing parenthesis to find where the name ends.
+    let name_start = bytes.iter().position(|byte| *byte == b'(')
+        .ok_or(ParseError::InvalidFormat)?;
+    let name_end = bytes.iter().rposition(|byte| *byte == b')')
+        .ok_or(ParseError::InvalidFormat)?;
+    if name_end < name_start {
+        return Err(ParseError::InvalidFormat);
+    }
+
+    let name = bytes[name_start + 1..name_end].to_vec();
+
+    let rest = std::str::from_utf8(&bytes[name_end + 1..])
+        .map_err(|_| ParseError::InvalidFormat)?;
+    let fields = rest.split_ascii_whitespace().collect::<Vec<_>>();
+
+    // Fields are numbered starting from 1 and we skip first two of them (the
+    // process identifier and its name), so the indices are shifted by 3.
+    fn field<T>(fields: &[&str], num: usize, name: &'static str) -> Result<T, ParseError>
+    where
+        T: std::str::FromStr,
+    {
+        fields.get(num - 3)
+            .ok_or(ParseError::MissingField(name))?
+            .parse()
+            .map_err(|_| ParseError::InvalidField(name))
+    }
+
+    let state = match fields.first() {
+        Some(state) => parse_state(state),
+        None => return Err(ParseError::MissingField("state")),
+    };
+
+    Ok(Stat {
+        name: std::ffi::OsString::from_vec(name),
+        state,
+        ppid: field(&fields, 4, "ppid")?,
+        utime: field(&fields, 14, "utime")?,
+        stime: field(&fields, 15, "stime")?,
+        num_threads: field(&fields, 20, "num_threads")?,
+        start_time: field(&fields, 22, "starttime")?,
+        vsize: field(&fields, 23, "vsize")?,
+        rss: field(&fields, 24, "rss")?,
+    })
+}
+
+/// Parses the process state in the procfs format.
+fn parse_state(string: &str) -> crate::proc::State {
+    use crate::proc::State::*;
+
+    match string {
+        "R" => Running,
+        "S" => Sleeping,
+        "D" => DiskSleep,
+        "T" | "t" => Stopped,
+        "Z" => Zombie,
+        "X" | "x" => Dead,
+        "I" => Idle,
+        _ => Other,
+    }
+}
+
+/// Process information parsed from the `/proc/<pid>/status` file.
+#[derive(Debug)]
+struct Status {
+    /// Real identifier of the user owning the process.
+    uid: u32,
+    /// Real identifier of the group owning the process.
+    gid: u32,
+}
+
+/// Parses the contents of the `/proc/<pid>/status` file.
+///
+/// See the `proc(5)` manual page [1] for the description of the format.
+///
+/// [1]: https://man7.org/linux/man-pages/man5/proc.5.html
+fn parse_status(bytes: &[u8]) -> Result<Status, ParseError> {
+    let mut uid = None;
+    let mut gid = None;
+
+    // Note that the status file can contain non-Unicode data (e.g. in the name
+    // of the process) but since the fields that we care about are ASCII, it is
+    // fine to parse it lossily.
+    for line in String::from_utf8_lossy(bytes).lines() {
+        // Both `Uid` and `Gid` lines contain four identifiers: real, effective,
+        // saved set and filesystem one. We are interested only in the first.
+        if let Some(ids) = line.strip_prefix("Uid:") {
+            let id = ids.split_ascii_whitespace().next()
+                .ok_or(ParseError::MissingField("Uid"))?;
+            uid = Some(id.parse().map_err(|_| ParseError::InvalidField("Uid"))?);
+        }
+        if let Some(ids) = line.strip_prefix("Gid:") {
+            let id = ids.split_ascii_whitespace().next()
+                .ok_or(ParseError::MissingField("Gid"))?;
+            gid = Some(id.parse().map_err(|_| ParseError::InvalidField("Gid"))?);
+        }
+    }
+
+    Ok(Status {
+        uid: uid.ok_or(ParseError::MissingField("Uid"))?,
+        gid: gid.ok_or(ParseError::MissingField("Gid"))?,
+    })
+}
+
+/// Parses the contents of the `/proc/<pid>/cmdline` file.
+///
+/// Arguments in this file are separated (and terminated) by null bytes. Note
+/// that for zombie processes and kernel threads the file is empty.
+fn parse_cmdline(bytes: &[u8]) -> Vec<std::ffi::OsString> {
+    use std::os::unix::ffi::OsStringExt as _;
+
+    let bytes = bytes.strip_suffix(b"\0").unwrap_or(bytes);
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+
+    bytes.split(|byte| *byte == 0)
+        .map(|arg| std::ffi::OsString::from_vec(arg.to_vec()))
+        .collect()
+}
+
+/// An error that might be returned when parsing procfs process files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ParseError {
+    /// The format of the file is not as it should be.
+    InvalidFormat,
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A field could not be parsed.
+    InvalidField(&'static str),
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ParseError::*;
+        match *self {
+            InvalidFormat => write!(fmt, "invalid process file format"),
+            MissingField(name) => write!(fmt, "missing field '{name}'"),
+            InvalidField(name) => write!(fmt, "invalid field '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_stat_ok() {
+        let stat = parse_stat(b"\
+            1337 (foo) S 1 1337 1337 0 -1 4194560 1000 0 0 0 42 17 0 0 20 0 3 0 \
+            12345 1048576 256 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 \
+            17 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n\
+        ").unwrap();
+
+        assert_eq!(stat.name, "foo");
+        assert_eq!(stat.state, crate::proc::State::Sleeping);
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.utime, 42);
+        assert_eq!(stat.stime, 17);
+        assert_eq!(stat.num_threads, 3);
+        assert_eq!(stat.start_time, 12345);
+        assert_eq!(stat.vsize, 1048576);
+        assert_eq!(stat.rss, 256);
+    }
+
+    #[test]
+    fn parse_stat_name_with_parens_and_spaces() {
+        let stat = parse_stat(b"\
+            1337 (foo) (bar baz)) R 42 1337 1337 0 -1 4194560 1000 0 0 0 0 0 0 \
+            0 20 0 1 0 0 0 0 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 \
+            17 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n\
+        ").unwrap();
+
+        assert_eq!(stat.name, "foo) (bar baz)");
+        assert_eq!(stat.state, crate::proc::State::Running);
+        assert_eq!(stat.ppid, 42);
+    }
+
+    #[test]
+    fn parse_stat_empty() {
+        assert_eq!(parse_stat(b"").unwrap_err(), ParseError::InvalidFormat);
+    }
+
+    #[test]
+    fn parse_stat_truncated() {
+        assert_eq! {
+            parse_stat(b"1337 (foo) S 1 1337 1337").unwrap_err(),
+            ParseError::MissingField("utime")
+        };
+    }
+
+    #[test]
+    fn parse_stat_invalid_field() {
+        assert_eq! {
+            parse_stat(b"1337 (foo) S bar").unwrap_err(),
+            ParseError::InvalidField("ppid")
+        };
+    }
+
+    #[test]
+    fn parse_status_ok() {
+        let status = parse_status(b"\
+            Name:\tfoo\n\
+            State:\tS (sleeping)\n\
+            Uid:\t1000\t1001\t1002\t1003\n\
+            Gid:\t2000\t2001\t2002\t2003\n\
+            Threads:\t1\n\
+        ").unwrap();
+
+        assert_eq!(status.uid, 1000);
+        assert_eq!(status.gid, 2000);
+    }
+
+    #[test]
+    fn parse_status_missing_gid() {
+        assert_eq! {
+            parse_status(b"Uid:\t0\t0\t0\t0\n").unwrap_err(),
+            ParseError::MissingField("Gid")
+        };
+    }
+
+    #[test]
+    fn parse_cmdline_empty() {
+        assert!(parse_cmdline(b"").is_empty());
+    }
+
+    #[test]
+    fn parse_cmdline_many_args() {
+        assert_eq! {
+            parse_cmdline(b"/bin/foo\0--bar\0\0baz\0"),
+            vec!["/bin/foo", "--bar", "", "baz"]
+        };
+    }
+
+    #[test]
+    fn info_self() {
+        let process = info(std::process::id())
+            .unwrap();
+
+        assert_eq!(process.pid(), std::process::id());
+        assert_eq!(process.ppid(), std::os::unix::process::parent_id());
+        assert_eq!(process.exe(), Some(std::env::current_exe().unwrap().as_path()));
+        assert_eq!(process.cwd(), Some(std::env::current_dir().unwrap().as_path()));
+        assert!(process.thread_count() >= 1);
+        assert!(process.rss() > 0);
+        assert!(process.vsz() > 0);
+        assert!(process.start_time() <= std::time::SystemTime::now());
+
+        // SAFETY: These functions are always successful.
+        assert_eq!(process.uid(), unsafe { libc::getuid() });
+        assert_eq!(process.gid(), unsafe { libc::getgid() });
+    }
 
+    #[test]
+    fn info_non_existent() {
+        // Process identifiers on Linux are limited to 2^22, so there cannot be
+        // a process with this identifier.
+        assert!(info(u32::MAX).is_err());
     }
 }
@@ -8,6 +8,28 @@ pub fn ids() -> std::io::Result<impl Iterator<Item = std::io::Result<u32>>> {
     Ids::new()
 }
 
+/// Returns information about the process with the specified identifier.
+pub fn info(_pid: u32) -> std::io::Result<crate::proc::Process> {
+    // TODO: Add support for collecting process information on macOS.
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Returns an iterator yielding information about all processes on the system.
+pub fn processes() -> std::io::Result<impl Iterator<Item = std::io::Result<crate::proc::Process>>> {
+    // TODO: Add support for collecting process information on macOS.
+    Err::<std::iter::Empty<_>, _>(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Returns the total CPU time consumed by the current process.
+pub fn cpu_time() -> std::io::Result<std::time::Duration> {
+    crate::proc::unix::cpu_time()
+}
+
+/// Returns the total CPU time consumed by the current thread.
+pub fn thread_cpu_time() -> std::io::Result<std::time::Duration> {
+    crate::proc::unix::thread_cpu_time()
+}
+
 /// A macOS-specific implementation of the iterator over process identifiers.
 struct Ids {
     /// An iterator over the process metadata returned by a `sysctl` call.
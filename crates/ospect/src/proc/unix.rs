@@ -0,0 +1,63 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+/// Returns the total CPU time consumed by the current process.
+pub fn cpu_time() -> std::io::Result<std::time::Duration> {
+    let mut usage = std::mem::MaybeUninit::uninit();
+
+    // SAFETY: We just pass the buffer we allocated. The buffer is valid for the
+    // entire scope of this function.
+    let code = unsafe {
+        libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr())
+    };
+    if code != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: We verified that the call succeeded. It means that the call has
+    // initialized the buffer and we can read from it.
+    let usage = unsafe {
+        usage.assume_init()
+    };
+
+    Ok(timeval_to_duration(usage.ru_utime) + timeval_to_duration(usage.ru_stime))
+}
+
+/// Returns the total CPU time consumed by the current thread.
+pub fn thread_cpu_time() -> std::io::Result<std::time::Duration> {
+    let mut timespec = std::mem::MaybeUninit::uninit();
+
+    // SAFETY: We just pass the buffer we allocated. The buffer is valid for the
+    // entire scope of this function.
+    let code = unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, timespec.as_mut_ptr())
+    };
+    if code != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: We verified that the call succeeded. It means that the call has
+    // initialized the buffer and we can read from it.
+    let timespec = unsafe {
+        timespec.assume_init()
+    };
+
+    // Values returned by `clock_gettime` for CPU-time clocks are never negative,
+    // so the conversions below can only fail in case of kernel bugs.
+    let secs = u64::try_from(timespec.tv_sec).unwrap_or(0);
+    let nanos = u64::try_from(timespec.tv_nsec).unwrap_or(0);
+
+    Ok(std::time::Duration::from_secs(secs) + std::time::Duration::from_nanos(nanos))
+}
+
+/// Converts the given `timeval` structure to a duration.
+fn timeval_to_duration(timeval: libc::timeval) -> std::time::Duration {
+    // Values returned by `getrusage` are never negative, so the conversions
+    // below can only fail in case of kernel bugs.
+    let secs = u64::try_from(timeval.tv_sec).unwrap_or(0);
+    let micros = u64::try_from(timeval.tv_usec).unwrap_or(0);
+
+    std::time::Duration::from_secs(secs) + std::time::Duration::from_micros(micros)
+}
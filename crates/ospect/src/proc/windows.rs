@@ -8,6 +8,95 @@ pub fn ids() -> std::io::Result<impl Iterator<Item = std::io::Result<u32>>> {
     Ids::new()
 }
 
+/// Returns information about the process with the specified identifier.
+pub fn info(_pid: u32) -> std::io::Result<crate::proc::Process> {
+    // TODO: Add support for collecting process information on Windows.
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Returns an iterator yielding information about all processes on the system.
+pub fn processes() -> std::io::Result<impl Iterator<Item = std::io::Result<crate::proc::Process>>> {
+    // TODO: Add support for collecting process information on Windows.
+    Err::<std::iter::Empty<_>, _>(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Returns the total CPU time consumed by the current process.
+pub fn cpu_time() -> std::io::Result<std::time::Duration> {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+    let mut creation_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut exit_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut kernel_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut user_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+
+    // SAFETY: `GetCurrentProcess` returns a pseudo-handle that does not need to
+    // be closed and we pass buffers that are valid for the entire scope of this
+    // function. We verify whether the call succeeded below.
+    let status = unsafe {
+        GetProcessTimes(
+            GetCurrentProcess(),
+            creation_time.as_mut_ptr(),
+            exit_time.as_mut_ptr(),
+            kernel_time.as_mut_ptr(),
+            user_time.as_mut_ptr(),
+        )
+    };
+    if status == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: We verified that the call succeeded, so the buffers are filled.
+    let (kernel_time, user_time) = unsafe {
+        (kernel_time.assume_init(), user_time.assume_init())
+    };
+
+    Ok(filetime_to_duration(kernel_time) + filetime_to_duration(user_time))
+}
+
+/// Returns the total CPU time consumed by the current thread.
+pub fn thread_cpu_time() -> std::io::Result<std::time::Duration> {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, GetThreadTimes};
+
+    let mut creation_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut exit_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut kernel_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+    let mut user_time = std::mem::MaybeUninit::<FILETIME>::uninit();
+
+    // SAFETY: `GetCurrentThread` returns a pseudo-handle that does not need to
+    // be closed and we pass buffers that are valid for the entire scope of this
+    // function. We verify whether the call succeeded below.
+    let status = unsafe {
+        GetThreadTimes(
+            GetCurrentThread(),
+            creation_time.as_mut_ptr(),
+            exit_time.as_mut_ptr(),
+            kernel_time.as_mut_ptr(),
+            user_time.as_mut_ptr(),
+        )
+    };
+    if status == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: We verified that the call succeeded, so the buffers are filled.
+    let (kernel_time, user_time) = unsafe {
+        (kernel_time.assume_init(), user_time.assume_init())
+    };
+
+    Ok(filetime_to_duration(kernel_time) + filetime_to_duration(user_time))
+}
+
+/// Converts the given `FILETIME` structure (interpreted as a time span) to a
+/// duration.
+fn filetime_to_duration(filetime: windows_sys::Win32::Foundation::FILETIME) -> std::time::Duration {
+    // `FILETIME` expresses time in 100-nanosecond intervals.
+    let ticks = u64::from(filetime.dwHighDateTime) << 32 | u64::from(filetime.dwLowDateTime);
+
+    std::time::Duration::from_nanos(ticks * 100)
+}
+
 /// A Windows-specific implementation of the iterator over process identifiers.
 struct Ids {
     /// An iterator over the process identifiers returned by `EnumProcesses`.
@@ -0,0 +1,153 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+mod sys {
+    #[cfg(target_os = "linux")]
+    pub use crate::user::linux::*;
+
+    #[cfg(target_os = "macos")]
+    pub use crate::user::macos::*;
+
+    #[cfg(target_os = "windows")]
+    pub use crate::user::windows::*;
+}
+
+/// Returns an iterator yielding information about all local user accounts.
+///
+/// The order in which the users are yield is not defined. Failures to parse
+/// information about particular users are yielded as errors and do not stop
+/// the iteration.
+///
+/// Note that some information (e.g. the time of the last password change) is
+/// available only to privileged users. If the operating system does not allow
+/// us to get it, it is reported as missing.
+///
+/// # Errors
+///
+/// The function will return an error if the operating system does not allow
+/// get the required information (e.g. in case of insufficient permissions).
+///
+/// # Examples
+///
+/// ```no_run
+/// let users = ospect::user::users()
+///     .unwrap()
+///     .filter_map(Result::ok);
+///
+/// for user in users {
+///     println!("{}", user.name().to_string_lossy());
+/// }
+/// ```
+pub fn users() -> std::io::Result<impl Iterator<Item = std::io::Result<User>>> {
+    self::sys::users()
+}
+
+/// Information about a local user account.
+#[derive(Clone, Debug)]
+pub struct User {
+    /// Name of the user (the login name).
+    name: std::ffi::OsString,
+    /// Identifier of the user.
+    #[cfg(target_family = "unix")]
+    uid: u32,
+    /// Identifier of the primary group of the user.
+    #[cfg(target_family = "unix")]
+    gid: u32,
+    /// Full name of the user (if available).
+    full_name: Option<std::ffi::OsString>,
+    /// Path to the home directory of the user (if available).
+    home: Option<std::path::PathBuf>,
+    /// Path to the login shell of the user (if available).
+    #[cfg(target_family = "unix")]
+    shell: Option<std::path::PathBuf>,
+    /// Groups the user is a member of.
+    groups: Vec<Group>,
+    /// Time at which the password of the user was last changed (if known).
+    last_password_change: Option<std::time::SystemTime>,
+}
+
+impl User {
+
+    /// Returns the name of the user (the login name).
+    pub fn name(&self) -> &std::ffi::OsStr {
+        &self.name
+    }
+
+    /// Returns the identifier of the user.
+    #[cfg(target_family = "unix")]
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the identifier of the primary group of the user.
+    #[cfg(target_family = "unix")]
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Returns the full name of the user (if available).
+    pub fn full_name(&self) -> Option<&std::ffi::OsStr> {
+        self.full_name.as_deref()
+    }
+
+    /// Returns the path to the home directory of the user (if available).
+    pub fn home(&self) -> Option<&std::path::Path> {
+        self.home.as_deref()
+    }
+
+    /// Returns the path to the login shell of the user (if available).
+    #[cfg(target_family = "unix")]
+    pub fn shell(&self) -> Option<&std::path::Path> {
+        self.shell.as_deref()
+    }
+
+    /// Returns the groups the user is a member of.
+    ///
+    /// On Unix, this includes the primary group of the user.
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Returns the time at which the password of the user was last changed.
+    ///
+    /// The time might not be available if the user has no password or if we
+    /// do not have the privileges required to get it.
+    pub fn last_password_change(&self) -> Option<std::time::SystemTime> {
+        self.last_password_change
+    }
+}
+
+/// Information about a local group.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Group {
+    /// Name of the group.
+    name: std::ffi::OsString,
+    /// Identifier of the group.
+    #[cfg(target_family = "unix")]
+    gid: u32,
+}
+
+impl Group {
+
+    /// Returns the name of the group.
+    pub fn name(&self) -> &std::ffi::OsStr {
+        &self.name
+    }
+
+    /// Returns the identifier of the group.
+    #[cfg(target_family = "unix")]
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+use std::os::unix::ffi::OsStringExt as _;
+
+/// Returns an iterator yielding information about all local user accounts.
+pub fn users() -> std::io::Result<impl Iterator<Item = std::io::Result<crate::user::User>>> {
+    let passwd = std::fs::read("/etc/passwd")?;
+
+    let groups = entries(&std::fs::read("/etc/group")?)
+        // Group information is only auxiliary, so we do not fail the whole
+        // listing if the file contains some malformed entries (we just skip
+        // them).
+        .filter_map(|entry| parse_group_entry(entry).ok())
+        .collect::<Vec<_>>();
+
+    // The shadow file is readable only by privileged users and does not need
+    // to exist at all (e.g. on systems that do not use shadow passwords). In
+    // such cases we simply do not report information stored there.
+    let shadow = match std::fs::read("/etc/shadow") {
+        Ok(shadow) => entries(&shadow)
+            .filter_map(|entry| parse_shadow_entry(entry).ok())
+            .collect::<Vec<_>>(),
+        Err(error) if is_unavailable(&error) => Vec::new(),
+        Err(error) => return Err(error),
+    };
+
+    let users = entries(&passwd)
+        .map(|entry| {
+            let entry = parse_passwd_entry(entry)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+            Ok(user(entry, &groups, &shadow))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(users.into_iter())
+}
+
+/// Checks whether the error means that a file is not available to us.
+fn is_unavailable(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+
+    matches!(error.kind(), NotFound | PermissionDenied)
+}
+
+/// Combines information from all the account files into a single user.
+fn user(
+    passwd: PasswdEntry,
+    groups: &[GroupEntry],
+    shadow: &[ShadowEntry],
+) -> crate::user::User {
+    // The primary group of the user goes first and then all the supplementary
+    // groups that list the user as their member. We make sure not to report
+    // the primary group twice in case it lists the user explicitly as well.
+    let primary_group = groups.iter()
+        .find(|group| group.gid == passwd.gid);
+    let supplementary_groups = groups.iter()
+        .filter(|group| group.gid != passwd.gid)
+        .filter(|group| group.members.contains(&passwd.name));
+
+    let groups = primary_group.into_iter()
+        .chain(supplementary_groups)
+        .map(|group| crate::user::Group {
+            name: std::ffi::OsString::from_vec(group.name.clone()),
+            gid: group.gid,
+        })
+        .collect();
+
+    let last_password_change = shadow.iter()
+        .find(|entry| entry.name == passwd.name)
+        .and_then(|entry| entry.last_change)
+        // The date is not validated when parsing, so absurdly big values could
+        // overflow (in which case we just do not report it).
+        .and_then(|days| days.checked_mul(86400))
+        .and_then(|secs| std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs)));
+
+    crate::user::User {
+        name: std::ffi::OsString::from_vec(passwd.name),
+        uid: passwd.uid,
+        gid: passwd.gid,
+        full_name: non_empty(passwd.full_name).map(std::ffi::OsString::from_vec),
+        home: non_empty(passwd.home).map(|home| {
+            std::path::PathBuf::from(std::ffi::OsString::from_vec(home))
+        }),
+        shell: non_empty(passwd.shell).map(|shell| {
+            std::path::PathBuf::from(std::ffi::OsString::from_vec(shell))
+        }),
+        groups,
+        last_password_change,
+    }
+}
+
+/// Returns the given bytes unless they are empty.
+fn non_empty(bytes: Vec<u8>) -> Option<Vec<u8>> {
+    if bytes.is_empty() {
+        None
+    } else {
+        Some(bytes)
+    }
+}
+
+/// Returns an iterator over meaningful entries of an account file.
+///
+/// Account files (like `/etc/passwd` or `/etc/group`) have one entry per line.
+/// Empty lines, comments and NIS compatibility entries (starting with `+` or
+/// `-`) are skipped.
+fn entries(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    bytes.split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .filter(|line| !matches!(line[0], b'#' | b'+' | b'-'))
+}
+
+/// An entry of the `/etc/passwd` file.
+#[derive(Debug)]
+struct PasswdEntry {
+    /// Login name of the user.
+    name: Vec<u8>,
+    /// Identifier of the user.
+    uid: u32,
+    /// Identifier of the primary group of the user.
+    gid: u32,
+    /// Full name of the user (the first part of the GECOS field).
+    full_name: Vec<u8>,
+    /// Path to the home directory of the user.
+    home: Vec<u8>,
+    /// Path to the login shell of the user.
+    shell: Vec<u8>,
+}
+
+/// Parses a single entry of the `/etc/passwd` file.
+///
+/// See the `passwd(5)` manual page [1] for the description of the format.
+///
+/// [1]: https://man7.org/linux/man-pages/man5/passwd.5.html
+fn parse_passwd_entry(bytes: &[u8]) -> Result<PasswdEntry, ParseError> {
+    let fields = bytes.split(|byte| *byte == b':').collect::<Vec<_>>();
+    let (name, uid, gid, gecos, home, shell) = match fields[..] {
+        [name, _, uid, gid, gecos, home, shell] => (name, uid, gid, gecos, home, shell),
+        _ => return Err(ParseError::InvalidFormat),
+    };
+
+    // The GECOS field can contain more comma-separated information like office
+    // location or phone number but the first one is always the full name.
+    let full_name = gecos.split(|byte| *byte == b',').next().unwrap_or(gecos);
+
+    Ok(PasswdEntry {
+        name: name.to_vec(),
+        uid: parse_id(uid).ok_or(ParseError::InvalidField("uid"))?,
+        gid: parse_id(gid).ok_or(ParseError::InvalidField("gid"))?,
+        full_name: full_name.to_vec(),
+        home: home.to_vec(),
+        shell: shell.to_vec(),
+    })
+}
+
+/// An entry of the `/etc/group` file.
+#[derive(Debug)]
+struct GroupEntry {
+    /// Name of the group.
+    name: Vec<u8>,
+    /// Identifier of the group.
+    gid: u32,
+    /// Login names of the group members.
+    members: Vec<Vec<u8>>,
+}
+
+/// Parses a single entry of the `/etc/group` file.
+///
+/// See the `group(5)` manual page [1] for the description of the format.
+///
+/// [1]: https://man7.org/linux/man-pages/man5/group.5.html
+fn parse_group_entry(bytes: &[u8]) -> Result<GroupEntry, ParseError> {
+    let fields = bytes.split(|byte| *byte == b':').collect::<Vec<_>>();
+    let (name, gid, members) = match fields[..] {
+        [name, _, gid, members] => (name, gid, members),
+        _ => return Err(ParseError::InvalidFormat),
+    };
+
+    let members = members.split(|byte| *byte == b',')
+        .filter(|member| !member.is_empty())
+        .map(|member| member.to_vec())
+        .collect();
+
+    Ok(GroupEntry {
+        name: name.to_vec(),
+        gid: parse_id(gid).ok_or(ParseError::InvalidField("gid"))?,
+        members,
+    })
+}
+
+/// An entry of the `/etc/shadow` file.
+#[derive(Debug)]
+struct ShadowEntry {
+    /// Login name of the user.
+    name: Vec<u8>,
+    /// Date of the last password change (in days since the Unix epoch).
+    last_change: Option<u64>,
+}
+
+/// Parses a single entry of the `/etc/shadow` file.
+///
+/// See the `shadow(5)` manual page [1] for the description of the format.
+///
+/// [1]: https://man7.org/linux/man-pages/man5/shadow.5.html
+fn parse_shadow_entry(bytes: &[u8]) -> Result<ShadowEntry, ParseError> {
+    let mut fields = bytes.split(|byte| *byte == b':');
+
+    let name = fields.next()
+        .ok_or(ParseError::InvalidFormat)?;
+    let _ = fields.next()
+        .ok_or(ParseError::InvalidFormat)?;
+    let last_change = fields.next()
+        .ok_or(ParseError::InvalidFormat)?;
+
+    // An empty field means that password aging is disabled and 0 means that
+    // the user has to change the password on the next login. In both cases
+    // there is no meaningful date to report.
+    let last_change = match last_change {
+        b"" | b"0" => None,
+        last_change => Some({
+            std::str::from_utf8(last_change).ok()
+                .and_then(|last_change| last_change.parse().ok())
+                .ok_or(ParseError::InvalidField("last_change"))?
+        }),
+    };
+
+    Ok(ShadowEntry {
+        name: name.to_vec(),
+        last_change,
+    })
+}
+
+/// Parses a numeric user or group identifier.
+fn parse_id(bytes: &[u8]) -> Option<u32> {
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// An error that might be returned when parsing account files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ParseError {
+    /// The format of the entry is not as it should be.
+    InvalidFormat,
+    /// A field could not be parsed.
+    InvalidField(&'static str),
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ParseError::*;
+        match *self {
+            InvalidFormat => write!(fmt, "invalid account entry format"),
+            InvalidField(name) => write!(fmt, "invalid field '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn users_root() {
+        let root = users().unwrap()
+            .filter_map(Result::ok)
+            .find(|user| user.uid() == 0)
+            .unwrap();
+
+        assert_eq!(root.name(), "root");
+        assert_eq!(root.gid(), 0);
+    }
+
+    #[test]
+    fn entries_skips_comments_and_empty_lines() {
+        let entries = entries(b"\
+            # comment\n\
+            foo:x:1000:1000::/home/foo:/bin/sh\n\
+            \n\
+            +@netgroup\n\
+            bar:x:1001:1001::/home/bar:/bin/sh\n\
+        ").collect::<Vec<_>>();
+
+        assert_eq!(entries, vec! {
+            &b"foo:x:1000:1000::/home/foo:/bin/sh"[..],
+            &b"bar:x:1001:1001::/home/bar:/bin/sh"[..],
+        });
+    }
+
+    #[test]
+    fn parse_passwd_entry_ok() {
+        let entry = parse_passwd_entry(
+            b"foo:x:1000:100:Foo Bar,Room 42,555-1337:/home/foo:/bin/bash"
+        ).unwrap();
+
+        assert_eq!(entry.name, b"foo");
+        assert_eq!(entry.uid, 1000);
+        assert_eq!(entry.gid, 100);
+        assert_eq!(entry.full_name, b"Foo Bar");
+        assert_eq!(entry.home, b"/home/foo");
+        assert_eq!(entry.shell, b"/bin/bash");
+    }
+
+    #[test]
+    fn parse_passwd_entry_empty_fields() {
+        let entry = parse_passwd_entry(b"foo:x:1000:100:::")
+            .unwrap();
+
+        assert!(entry.full_name.is_empty());
+        assert!(entry.home.is_empty());
+        assert!(entry.shell.is_empty());
+    }
+
+    #[test]
+    fn parse_passwd_entry_invalid_uid() {
+        let error = parse_passwd_entry(b"foo:x:bar:100::/home/foo:/bin/sh")
+            .unwrap_err();
+
+        assert_eq!(error, ParseError::InvalidField("uid"));
+    }
+
+    #[test]
+    fn parse_passwd_entry_missing_fields() {
+        let error = parse_passwd_entry(b"foo:x:1000:100")
+            .unwrap_err();
+
+        assert_eq!(error, ParseError::InvalidFormat);
+    }
+
+    #[test]
+    fn parse_group_entry_ok() {
+        let entry = parse_group_entry(b"wheel:x:10:foo,bar")
+            .unwrap();
+
+        assert_eq!(entry.name, b"wheel");
+        assert_eq!(entry.gid, 10);
+        assert_eq!(entry.members, vec![b"foo".to_vec(), b"bar".to_vec()]);
+    }
+
+    #[test]
+    fn parse_group_entry_no_members() {
+        let entry = parse_group_entry(b"users:x:100:")
+            .unwrap();
+
+        assert!(entry.members.is_empty());
+    }
+
+    #[test]
+    fn parse_shadow_entry_ok() {
+        let entry = parse_shadow_entry(b"foo:$6$salt$hash:19000:0:99999:7:::")
+            .unwrap();
+
+        assert_eq!(entry.name, b"foo");
+        assert_eq!(entry.last_change, Some(19000));
+    }
+
+    #[test]
+    fn parse_shadow_entry_no_last_change() {
+        let entry = parse_shadow_entry(b"foo:*:::::::")
+            .unwrap();
+        assert_eq!(entry.last_change, None);
+
+        let entry = parse_shadow_entry(b"foo:!:0:0:99999:7:::")
+            .unwrap();
+        assert_eq!(entry.last_change, None);
+    }
+
+    #[test]
+    fn user_groups() {
+        let passwd = parse_passwd_entry(b"foo:x:1000:1000::/home/foo:/bin/sh")
+            .unwrap();
+        let groups = vec! {
+            parse_group_entry(b"foo:x:1000:foo").unwrap(),
+            parse_group_entry(b"wheel:x:10:bar,foo").unwrap(),
+            parse_group_entry(b"audio:x:20:bar").unwrap(),
+        };
+        let shadow = vec! {
+            parse_shadow_entry(b"foo:!:19000::::::").unwrap(),
+        };
+
+        let user = user(passwd, &groups, &shadow);
+        assert_eq!(user.name(), "foo");
+        assert_eq!(user.home(), Some(std::path::Path::new("/home/foo")));
+        assert_eq!(user.full_name(), None);
+
+        let group_names = user.groups().iter()
+            .map(|group| group.name())
+            .collect::<Vec<_>>();
+        assert_eq!(group_names, vec!["foo", "wheel"]);
+
+        assert_eq! {
+            user.last_password_change(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(19000 * 86400))
+        };
+    }
+
+    #[test]
+    fn user_last_password_change_out_of_range() {
+        // The first one overflows the number of seconds and the second one the
+        // system time.
+        for entry in [&b"foo:!:18446744073709551615::::::"[..], b"foo:!:213503982334601::::::"] {
+            let passwd = parse_passwd_entry(b"foo:x:1000:1000::/home/foo:/bin/sh")
+                .unwrap();
+            let shadow = vec![parse_shadow_entry(entry).unwrap()];
+
+            let user = user(passwd, &[], &shadow);
+            assert_eq!(user.last_password_change(), None);
+        }
+    }
+}
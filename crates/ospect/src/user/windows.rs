@@ -0,0 +1,10 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+/// Returns an iterator yielding information about all local user accounts.
+pub fn users() -> std::io::Result<impl Iterator<Item = std::io::Result<crate::user::User>>> {
+    // TODO: Add support for collecting user information on Windows.
+    Err::<std::iter::Empty<_>, _>(std::io::ErrorKind::Unsupported.into())
+}
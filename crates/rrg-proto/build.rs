@@ -21,11 +21,25 @@ const PROTOS: &'static [&'static str] = &[
 
 const PROTOS_V2: &'static [&'static str] = &[
     "../../proto/rrg.proto",
+    "../../proto/rrg/blob.proto",
     "../../proto/rrg/fs.proto",
     "../../proto/rrg/os.proto",
+    "../../proto/rrg/net.proto",
+    "../../proto/rrg/process.proto",
+    "../../proto/rrg/user.proto",
     "../../proto/rrg/startup.proto",
+    "../../proto/rrg/action/get_agent_metadata.proto",
+    "../../proto/rrg/action/get_file_hash.proto",
     "../../proto/rrg/action/get_file_metadata.proto",
     "../../proto/rrg/action/get_system_metadata.proto",
+    "../../proto/rrg/action/list_directory.proto",
+    "../../proto/rrg/action/list_processes.proto",
+    "../../proto/rrg/action/list_connections.proto",
+    "../../proto/rrg/action/list_users.proto",
+    "../../proto/rrg/action/get_filesystem_timeline.proto",
+    "../../proto/rrg/action/cancel_request.proto",
+    "../../proto/rrg/action/find_files.proto",
+    "../../proto/rrg/action/scan_files.proto",
 ];
 
 fn main() {
@@ -5,10 +5,37 @@
 
 pub mod convert;
 pub mod path;
+pub mod text;
 
 pub mod v2 {
     include!(concat!(env!("OUT_DIR"), "/proto-v2/mod.rs"));
 
+    /// Returns descriptors of all the files that define the protocol.
+    pub fn file_descriptor_protos() -> Vec<&'static protobuf::descriptor::FileDescriptorProto> {
+        vec![
+            rrg::file_descriptor_proto(),
+            blob::file_descriptor_proto(),
+            fs::file_descriptor_proto(),
+            os::file_descriptor_proto(),
+            net::file_descriptor_proto(),
+            process::file_descriptor_proto(),
+            user::file_descriptor_proto(),
+            startup::file_descriptor_proto(),
+            get_agent_metadata::file_descriptor_proto(),
+            get_file_hash::file_descriptor_proto(),
+            get_file_metadata::file_descriptor_proto(),
+            get_system_metadata::file_descriptor_proto(),
+            list_directory::file_descriptor_proto(),
+            list_processes::file_descriptor_proto(),
+            list_connections::file_descriptor_proto(),
+            list_users::file_descriptor_proto(),
+            get_filesystem_timeline::file_descriptor_proto(),
+            cancel_request::file_descriptor_proto(),
+            find_files::file_descriptor_proto(),
+            scan_files::file_descriptor_proto(),
+        ]
+    }
+
     impl From<ospect::os::Kind> for os::Type {
 
         fn from(kind: ospect::os::Kind) -> os::Type {
@@ -105,6 +132,177 @@ pub mod v2 {
         }
     }
 
+    impl From<ospect::proc::State> for process::Process_State {
+
+        fn from(state: ospect::proc::State) -> process::Process_State {
+            use ospect::proc::State::*;
+
+            match state {
+                Running => process::Process_State::RUNNING,
+                Sleeping => process::Process_State::SLEEPING,
+                DiskSleep => process::Process_State::DISK_SLEEP,
+                Stopped => process::Process_State::STOPPED,
+                Zombie => process::Process_State::ZOMBIE,
+                Dead => process::Process_State::DEAD,
+                Idle => process::Process_State::IDLE,
+                Other => process::Process_State::UNKNOWN,
+            }
+        }
+    }
+
+    impl From<ospect::proc::Process> for process::Process {
+
+        fn from(process: ospect::proc::Process) -> process::Process {
+            use crate::{into_duration, into_timestamp};
+
+            let mut proto = process::Process::default();
+            proto.set_pid(process.pid());
+            proto.set_ppid(process.ppid());
+            // Names and arguments do not have to be valid Unicode, so we pass
+            // them as bytes in the same way as paths to avoid losing data.
+            proto.set_name(crate::path::into_bytes(process.name().into()));
+            if let Some(exe) = process.exe() {
+                proto.set_exe(exe.to_path_buf().into());
+            }
+            for arg in process.cmdline() {
+                proto.mut_cmdline().push(crate::path::into_bytes(arg.into()));
+            }
+            if let Some(cwd) = process.cwd() {
+                proto.set_cwd(cwd.to_path_buf().into());
+            }
+            #[cfg(target_family = "unix")]
+            {
+                proto.set_uid(process.uid());
+                proto.set_gid(process.gid());
+            }
+            proto.set_start_time(into_timestamp(process.start_time()));
+            proto.set_state(process.state().into());
+            proto.set_rss(process.rss());
+            proto.set_vsz(process.vsz());
+            proto.set_user_cpu_time(into_duration(process.user_time()));
+            proto.set_system_cpu_time(into_duration(process.system_time()));
+            proto.set_thread_count(process.thread_count());
+
+            proto
+        }
+    }
+
+    impl From<std::net::IpAddr> for net::IpAddress {
+
+        fn from(addr: std::net::IpAddr) -> net::IpAddress {
+            let octets = match addr {
+                std::net::IpAddr::V4(addr) => addr.octets().to_vec(),
+                std::net::IpAddr::V6(addr) => addr.octets().to_vec(),
+            };
+
+            let mut proto = net::IpAddress::default();
+            proto.set_octets(octets);
+
+            proto
+        }
+    }
+
+    impl From<std::net::SocketAddr> for net::SocketAddress {
+
+        fn from(addr: std::net::SocketAddr) -> net::SocketAddress {
+            let mut proto = net::SocketAddress::default();
+            proto.set_ip_address(addr.ip().into());
+            proto.set_port(u32::from(addr.port()));
+
+            proto
+        }
+    }
+
+    impl From<ospect::net::TcpState> for net::TcpState {
+
+        fn from(state: ospect::net::TcpState) -> net::TcpState {
+            use ospect::net::TcpState::*;
+
+            match state {
+                Listen => net::TcpState::LISTEN,
+                SynSent => net::TcpState::SYN_SENT,
+                SynReceived => net::TcpState::SYN_RECEIVED,
+                Established => net::TcpState::ESTABLISHED,
+                FinWait1 => net::TcpState::FIN_WAIT_1,
+                FinWait2 => net::TcpState::FIN_WAIT_2,
+                CloseWait => net::TcpState::CLOSE_WAIT,
+                Closing => net::TcpState::CLOSING,
+                LastAck => net::TcpState::LAST_ACK,
+                TimeWait => net::TcpState::TIME_WAIT,
+                Closed => net::TcpState::CLOSED,
+            }
+        }
+    }
+
+    impl From<ospect::net::Connection> for net::Connection {
+
+        fn from(conn: ospect::net::Connection) -> net::Connection {
+            use ospect::net::Connection::*;
+
+            let mut proto = net::Connection::default();
+            match conn {
+                Tcp(conn) => {
+                    proto.set_protocol(net::Protocol::TCP);
+                    proto.set_local_address(conn.local_addr().into());
+                    proto.set_remote_address(conn.remote_addr().into());
+                    proto.set_state(conn.state().into());
+                }
+                Udp(conn) => {
+                    proto.set_protocol(net::Protocol::UDP);
+                    proto.set_local_address(conn.local_addr().into());
+                }
+            }
+            proto.set_pid(conn.pid());
+
+            proto
+        }
+    }
+
+    impl From<ospect::user::Group> for user::Group {
+
+        fn from(group: ospect::user::Group) -> user::Group {
+            let mut proto = user::Group::default();
+            proto.set_name(group.name().to_string_lossy().into_owned());
+            #[cfg(target_family = "unix")]
+            proto.set_gid(group.gid());
+
+            proto
+        }
+    }
+
+    impl From<ospect::user::User> for user::User {
+
+        fn from(user: ospect::user::User) -> user::User {
+            let mut proto = user::User::default();
+            proto.set_name(user.name().to_string_lossy().into_owned());
+            #[cfg(target_family = "unix")]
+            {
+                proto.set_uid(user.uid());
+                proto.set_gid(user.gid());
+            }
+            if let Some(full_name) = user.full_name() {
+                proto.set_full_name(full_name.to_string_lossy().into_owned());
+            }
+            if let Some(home) = user.home() {
+                proto.set_home(home.to_path_buf().into());
+            }
+            #[cfg(target_family = "unix")]
+            {
+                if let Some(shell) = user.shell() {
+                    proto.set_shell(shell.to_path_buf().into());
+                }
+            }
+            for group in user.groups() {
+                proto.mut_groups().push(group.clone().into());
+            }
+            if let Some(time) = user.last_password_change() {
+                proto.set_last_password_change(crate::into_timestamp(time));
+            }
+
+            proto
+        }
+    }
+
     /// A type representing errors that can occur when parsing paths.
     #[derive(Debug, PartialEq, Eq)]
     pub struct ParsePathError(crate::path::ParseError);
@@ -544,3 +742,66 @@ pub fn into_timestamp(time: std::time::SystemTime) -> protobuf::well_known_types
 
     proto
 }
+
+/// Converts [`Duration`] to a Protocol Buffers `Duration` message.
+///
+/// # Examples
+///
+/// ```
+/// let duration = rrg_proto::into_duration(std::time::Duration::from_millis(1500));
+/// assert_eq!(duration.seconds, 1);
+/// assert_eq!(duration.nanos, 500_000_000);
+/// ```
+///
+/// [`Duration`]: std::time::Duration
+pub fn into_duration(duration: std::time::Duration) -> protobuf::well_known_types::Duration {
+    let mut proto = protobuf::well_known_types::Duration::default();
+    proto.set_nanos(duration.subsec_nanos() as i32);
+    proto.set_seconds(duration.as_secs() as i64);
+
+    proto
+}
+
+/// Converts a Protocol Buffers `Duration` message to [`Duration`].
+///
+/// # Errors
+///
+/// This function will return an error if the given duration is negative (which
+/// is not representable by the standard [`Duration`] type).
+///
+/// # Examples
+///
+/// ```
+/// let mut proto = protobuf::well_known_types::Duration::default();
+/// proto.set_seconds(1);
+/// proto.set_nanos(500_000_000);
+///
+/// let duration = rrg_proto::try_from_duration(proto).unwrap();
+/// assert_eq!(duration, std::time::Duration::from_millis(1500));
+/// ```
+///
+/// [`Duration`]: std::time::Duration
+pub fn try_from_duration(
+    proto: protobuf::well_known_types::Duration,
+) -> Result<std::time::Duration, NegativeDurationError> {
+    let secs = u64::try_from(proto.get_seconds())
+        .map_err(|_| NegativeDurationError)?;
+    let nanos = u32::try_from(proto.get_nanos())
+        .map_err(|_| NegativeDurationError)?;
+
+    Ok(std::time::Duration::new(secs, nanos))
+}
+
+/// An error type for attempts to convert negative durations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegativeDurationError;
+
+impl std::fmt::Display for NegativeDurationError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "negative duration")
+    }
+}
+
+impl std::error::Error for NegativeDurationError {
+}
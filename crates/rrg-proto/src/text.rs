@@ -0,0 +1,1237 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+//! Human-readable representations of the protocol messages.
+//!
+//! The version of the `protobuf` crate that we use can print messages in the
+//! text format but it can parse neither the text format nor JSON. This module
+//! fills the gap for messages defined in the protocol (and the well-known types
+//! they depend on).
+//!
+//! Parsing is schema-driven: the input is first parsed into a generic tree of
+//! values which is then encoded into the wire format according to the message
+//! descriptors. Note that well-known types (like `google.protobuf.Timestamp`)
+//! do not have any special representation and use their regular form.
+
+use std::collections::HashMap;
+
+use protobuf::descriptor::{
+    DescriptorProto,
+    EnumDescriptorProto,
+    FieldDescriptorProto,
+    FieldDescriptorProto_Label,
+    FieldDescriptorProto_Type,
+};
+
+/// Parses a message from its text format representation.
+///
+/// # Examples
+///
+/// ```
+/// use rrg_proto::v2::fs::Path;
+///
+/// let path = rrg_proto::text::parse_text::<Path>(r#"raw_bytes: "/foo""#)
+///     .unwrap();
+///
+/// assert_eq!(path.get_raw_bytes(), b"/foo");
+/// ```
+pub fn parse_text<M>(input: &str) -> Result<M, ParseError>
+where
+    M: protobuf::Message,
+{
+    let bytes = text_to_bytes(M::descriptor_static().full_name(), input)?;
+    M::parse_from_bytes(&bytes)
+        .map_err(|error| ParseError::new(error.to_string()))
+}
+
+/// Parses a message from its JSON representation.
+///
+/// # Examples
+///
+/// ```
+/// use rrg_proto::v2::fs::Path;
+///
+/// let path = rrg_proto::text::parse_json::<Path>(r#"{"rawBytes": "L2Zvbw=="}"#)
+///     .unwrap();
+///
+/// assert_eq!(path.get_raw_bytes(), b"/foo");
+/// ```
+pub fn parse_json<M>(input: &str) -> Result<M, ParseError>
+where
+    M: protobuf::Message,
+{
+    let bytes = json_to_bytes(M::descriptor_static().full_name(), input)?;
+    M::parse_from_bytes(&bytes)
+        .map_err(|error| ParseError::new(error.to_string()))
+}
+
+/// Encodes a message given in the text format into the wire format.
+///
+/// The type of the message is specified by its fully qualified name (e.g.
+/// `rrg.fs.Path`).
+pub fn text_to_bytes(type_name: &str, input: &str) -> Result<Vec<u8>, ParseError> {
+    let fields = TextParser::new(input).parse()?;
+    encode(type_name, fields, Format::Text)
+}
+
+/// Encodes a message given in the JSON format into the wire format.
+///
+/// The type of the message is specified by its fully qualified name (e.g.
+/// `rrg.fs.Path`).
+pub fn json_to_bytes(type_name: &str, input: &str) -> Result<Vec<u8>, ParseError> {
+    let fields = match JsonParser::new(input).parse()? {
+        Value::Message(fields) => fields,
+        _ => return Err(ParseError::new("expected a JSON object")),
+    };
+    encode(type_name, fields, Format::Json)
+}
+
+/// Prints the given message in the JSON format.
+///
+/// Fields with default values are omitted and the output is not indented, so
+/// the resulting string always fits in a single line.
+///
+/// # Examples
+///
+/// ```
+/// let mut path = rrg_proto::v2::fs::Path::new();
+/// path.set_raw_bytes(b"/foo".to_vec());
+///
+/// let json = rrg_proto::text::print_json(&path);
+/// assert_eq!(json, r#"{"rawBytes":"L2Zvbw=="}"#);
+/// ```
+pub fn print_json(message: &dyn protobuf::Message) -> String {
+    let mut output = String::new();
+    print_json_message(message, &mut output);
+
+    output
+}
+
+/// The error type for cases when parsing a human-readable message fails.
+#[derive(Debug)]
+pub struct ParseError {
+    /// A description of the problem.
+    message: String,
+}
+
+impl ParseError {
+
+    /// Creates a new error with the given description.
+    fn new<S: Into<String>>(message: S) -> ParseError {
+        ParseError {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {
+}
+
+/// A generic value of a message parsed from a human-readable representation.
+#[derive(Debug, PartialEq)]
+enum Value {
+    /// A string literal (with all escape sequences resolved).
+    String(Vec<u8>),
+    /// A numeric literal or an identifier (e.g. `true` or an enum name).
+    Literal(String),
+    /// A nested message as a list of its fields.
+    Message(Vec<(String, Value)>),
+    /// A list of values (of a repeated field).
+    List(Vec<Value>),
+    /// An explicit null value (of an unset field).
+    Null,
+}
+
+/// Human-readable formats that we can parse messages from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// Parser of messages in the text format.
+struct TextParser<'i> {
+    /// Input that has not been consumed yet.
+    input: &'i str,
+}
+
+impl<'i> TextParser<'i> {
+
+    /// Creates a new parser for the given input.
+    fn new(input: &'i str) -> TextParser<'i> {
+        TextParser {
+            input,
+        }
+    }
+
+    /// Parses the entire input as a list of message fields.
+    fn parse(mut self) -> Result<Vec<(String, Value)>, ParseError> {
+        let fields = self.parse_fields()?;
+        if !self.input.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        Ok(fields)
+    }
+
+    /// Parses message fields until the end of the message or input.
+    fn parse_fields(&mut self) -> Result<Vec<(String, Value)>, ParseError> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.input.is_empty() || self.input.starts_with('}') {
+                return Ok(fields);
+            }
+
+            let name = self.parse_ident()?;
+
+            self.skip_whitespace();
+            let value = if self.eat(':') {
+                self.skip_whitespace();
+                if self.input.starts_with('[') {
+                    self.parse_list()?
+                } else {
+                    self.parse_value()?
+                }
+            } else if self.input.starts_with('{') {
+                self.parse_value()?
+            } else {
+                return Err(self.unexpected());
+            };
+            fields.push((name, value));
+
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.eat(';');
+            }
+        }
+    }
+
+    /// Parses a list of values (e.g. `[1, 2, 3]`).
+    fn parse_list(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Value::List(values));
+            }
+            if !values.is_empty() {
+                self.expect(',')?;
+                self.skip_whitespace();
+            }
+
+            values.push(self.parse_value()?);
+        }
+    }
+
+    /// Parses a single (non-list) value.
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        if self.eat('{') {
+            let fields = self.parse_fields()?;
+            self.expect('}')?;
+            return Ok(Value::Message(fields));
+        }
+
+        if self.input.starts_with('"') || self.input.starts_with('\'') {
+            // Adjacent string literals are concatenated.
+            let mut bytes = Vec::new();
+            while self.input.starts_with('"') || self.input.starts_with('\'') {
+                bytes.extend(self.parse_string()?);
+                self.skip_whitespace();
+            }
+            return Ok(Value::String(bytes));
+        }
+
+        let len = self.input
+            .find(|char: char| {
+                !(char.is_ascii_alphanumeric() || "_-+.".contains(char))
+            })
+            .unwrap_or(self.input.len());
+        if len == 0 {
+            return Err(self.unexpected());
+        }
+
+        let (literal, rest) = self.input.split_at(len);
+        self.input = rest;
+
+        Ok(Value::Literal(String::from(literal)))
+    }
+
+    /// Parses a quoted string literal.
+    fn parse_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let quote = match self.input.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.unexpected()),
+        };
+        self.input = &self.input[1..];
+
+        let mut bytes = Vec::new();
+        let mut chars = self.input.char_indices();
+        loop {
+            let (_, char) = chars.next()
+                .ok_or_else(|| ParseError::new("unterminated string"))?;
+
+            match char {
+                '\\' => {
+                    let (_, escape) = chars.next()
+                        .ok_or_else(|| ParseError::new("unterminated string"))?;
+
+                    match escape {
+                        'a' => bytes.push(b'\x07'),
+                        'b' => bytes.push(b'\x08'),
+                        'f' => bytes.push(b'\x0C'),
+                        'n' => bytes.push(b'\n'),
+                        'r' => bytes.push(b'\r'),
+                        't' => bytes.push(b'\t'),
+                        'v' => bytes.push(b'\x0B'),
+                        '\\' | '\'' | '"' | '?' => bytes.push(escape as u8),
+                        'x' => {
+                            let digits = chars.as_str();
+                            let len = digits
+                                .find(|char: char| !char.is_ascii_hexdigit())
+                                .unwrap_or(digits.len())
+                                .min(2);
+                            let byte = u8::from_str_radix(&digits[..len], 16)
+                                .map_err(|_| ParseError::new("invalid hex escape"))?;
+
+                            bytes.push(byte);
+                            for _ in 0..len {
+                                chars.next();
+                            }
+                        }
+                        '0'..='7' => {
+                            let digits = chars.as_str();
+                            let len = digits
+                                .find(|char: char| !('0'..='7').contains(&char))
+                                .unwrap_or(digits.len())
+                                .min(2);
+                            let octal = format!("{escape}{}", &digits[..len]);
+                            let byte = u8::from_str_radix(&octal, 8)
+                                .map_err(|_| ParseError::new("invalid octal escape"))?;
+
+                            bytes.push(byte);
+                            for _ in 0..len {
+                                chars.next();
+                            }
+                        }
+                        _ => {
+                            let message = format!("invalid escape '\\{escape}'");
+                            return Err(ParseError::new(message));
+                        }
+                    }
+                }
+                _ if char == quote => break,
+                _ => {
+                    let mut buf = [0; 4];
+                    bytes.extend(char.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+
+        self.input = chars.as_str();
+        Ok(bytes)
+    }
+
+    /// Parses a field name.
+    fn parse_ident(&mut self) -> Result<String, ParseError> {
+        let len = self.input
+            .find(|char: char| !(char.is_ascii_alphanumeric() || char == '_'))
+            .unwrap_or(self.input.len());
+        if len == 0 {
+            return Err(self.unexpected());
+        }
+
+        let (ident, rest) = self.input.split_at(len);
+        self.input = rest;
+
+        Ok(String::from(ident))
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.input = self.input.trim_start();
+            if !self.input.starts_with('#') {
+                return;
+            }
+
+            let len = self.input.find('\n').unwrap_or(self.input.len());
+            self.input = &self.input[len..];
+        }
+    }
+
+    /// Consumes the given character if it is the next one in the input.
+    fn eat(&mut self, char: char) -> bool {
+        match self.input.strip_prefix(char) {
+            Some(rest) => {
+                self.input = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consumes the given character or fails if it is not the next one.
+    fn expect(&mut self, char: char) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.eat(char) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Creates an error for an unexpected input at the current position.
+    fn unexpected(&self) -> ParseError {
+        match self.input.chars().next() {
+            Some(char) => ParseError::new(format!("unexpected character '{char}'")),
+            None => ParseError::new("unexpected end of input"),
+        }
+    }
+}
+
+/// Parser of messages in the JSON format.
+struct JsonParser<'i> {
+    /// Input that has not been consumed yet.
+    input: &'i str,
+}
+
+impl<'i> JsonParser<'i> {
+
+    /// Creates a new parser for the given input.
+    fn new(input: &'i str) -> JsonParser<'i> {
+        JsonParser {
+            input,
+        }
+    }
+
+    /// Parses the entire input as a single value.
+    fn parse(mut self) -> Result<Value, ParseError> {
+        let value = self.parse_value()?;
+
+        self.input = self.input.trim_start();
+        if !self.input.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        Ok(value)
+    }
+
+    /// Parses a single JSON value.
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.input = self.input.trim_start();
+
+        if self.eat('{') {
+            let mut fields = Vec::new();
+            loop {
+                self.input = self.input.trim_start();
+                if self.eat('}') {
+                    return Ok(Value::Message(fields));
+                }
+                if !fields.is_empty() {
+                    self.expect(',')?;
+                    self.input = self.input.trim_start();
+                }
+
+                let name = String::from_utf8(self.parse_string()?)
+                    .map_err(|_| ParseError::new("invalid field name"))?;
+                self.expect(':')?;
+                fields.push((name, self.parse_value()?));
+            }
+        }
+
+        if self.eat('[') {
+            let mut values = Vec::new();
+            loop {
+                self.input = self.input.trim_start();
+                if self.eat(']') {
+                    return Ok(Value::List(values));
+                }
+                if !values.is_empty() {
+                    self.expect(',')?;
+                }
+
+                values.push(self.parse_value()?);
+            }
+        }
+
+        if self.input.starts_with('"') {
+            return Ok(Value::String(self.parse_string()?));
+        }
+
+        let len = self.input
+            .find(|char: char| {
+                !(char.is_ascii_alphanumeric() || "-+.".contains(char))
+            })
+            .unwrap_or(self.input.len());
+        if len == 0 {
+            return Err(self.unexpected());
+        }
+
+        let (literal, rest) = self.input.split_at(len);
+        self.input = rest;
+
+        match literal {
+            "null" => Ok(Value::Null),
+            _ => Ok(Value::Literal(String::from(literal))),
+        }
+    }
+
+    /// Parses a quoted string literal.
+    fn parse_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        if !self.eat('"') {
+            return Err(self.unexpected());
+        }
+
+        let mut string = String::new();
+        let mut chars = self.input.chars();
+        loop {
+            let char = chars.next()
+                .ok_or_else(|| ParseError::new("unterminated string"))?;
+
+            match char {
+                '"' => break,
+                '\\' => {
+                    let escape = chars.next()
+                        .ok_or_else(|| ParseError::new("unterminated string"))?;
+
+                    match escape {
+                        '"' | '\\' | '/' => string.push(escape),
+                        'b' => string.push('\x08'),
+                        'f' => string.push('\x0C'),
+                        'n' => string.push('\n'),
+                        'r' => string.push('\r'),
+                        't' => string.push('\t'),
+                        'u' => {
+                            let mut code = parse_json_code_unit(&mut chars)?;
+                            // Characters outside of the basic multilingual plane
+                            // are encoded as surrogate pairs.
+                            if (0xD800..0xDC00).contains(&code) {
+                                if chars.next() != Some('\\') || chars.next() != Some('u') {
+                                    return Err(ParseError::new("unpaired surrogate"));
+                                }
+
+                                let low = parse_json_code_unit(&mut chars)?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+
+                            string.push(char::from_u32(code)
+                                .ok_or_else(|| ParseError::new("invalid unicode escape"))?);
+                        }
+                        _ => {
+                            let message = format!("invalid escape '\\{escape}'");
+                            return Err(ParseError::new(message));
+                        }
+                    }
+                }
+                _ => string.push(char),
+            }
+        }
+
+        self.input = chars.as_str();
+        Ok(string.into_bytes())
+    }
+
+    /// Consumes the given character if it is the next one in the input.
+    fn eat(&mut self, char: char) -> bool {
+        match self.input.strip_prefix(char) {
+            Some(rest) => {
+                self.input = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consumes the given character or fails if it is not the next one.
+    fn expect(&mut self, char: char) -> Result<(), ParseError> {
+        self.input = self.input.trim_start();
+        if self.eat(char) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Creates an error for an unexpected input at the current position.
+    fn unexpected(&self) -> ParseError {
+        match self.input.chars().next() {
+            Some(char) => ParseError::new(format!("unexpected character '{char}'")),
+            None => ParseError::new("unexpected end of input"),
+        }
+    }
+}
+
+/// Parses 4 hex digits of a JSON unicode escape sequence.
+fn parse_json_code_unit(chars: &mut std::str::Chars) -> Result<u32, ParseError> {
+    let digits = chars.as_str().get(..4)
+        .ok_or_else(|| ParseError::new("invalid unicode escape"))?;
+    let code = u32::from_str_radix(digits, 16)
+        .map_err(|_| ParseError::new("invalid unicode escape"))?;
+
+    for _ in 0..4 {
+        chars.next();
+    }
+
+    Ok(code)
+}
+
+/// Index of all message and enum types that can be parsed.
+struct Schema {
+    /// Message descriptors by their fully qualified name (with a leading dot).
+    messages: HashMap<String, &'static DescriptorProto>,
+    /// Enum descriptors by their fully qualified name (with a leading dot).
+    enums: HashMap<String, &'static EnumDescriptorProto>,
+}
+
+impl Schema {
+
+    /// Creates an index of all the types defined in the protocol.
+    fn new() -> Schema {
+        let mut schema = Schema {
+            messages: HashMap::new(),
+            enums: HashMap::new(),
+        };
+
+        for file in crate::v2::file_descriptor_protos() {
+            let scope = format!(".{}", file.get_package());
+            for message in file.get_message_type() {
+                schema.add_message(&scope, message);
+            }
+            for enum_type in file.get_enum_type() {
+                schema.add_enum(&scope, enum_type);
+            }
+        }
+
+        use protobuf::Message as _;
+        use protobuf::well_known_types::{Any, Duration, Empty, Timestamp};
+
+        for descriptor in [
+            Any::descriptor_static(),
+            Duration::descriptor_static(),
+            Empty::descriptor_static(),
+            Timestamp::descriptor_static(),
+        ] {
+            schema.add_message(".google.protobuf", descriptor.get_proto());
+        }
+
+        schema
+    }
+
+    /// Adds the given message and all its nested types to the index.
+    fn add_message(&mut self, scope: &str, message: &'static DescriptorProto) {
+        let name = format!("{scope}.{}", message.get_name());
+
+        for nested in message.get_nested_type() {
+            self.add_message(&name, nested);
+        }
+        for enum_type in message.get_enum_type() {
+            self.add_enum(&name, enum_type);
+        }
+
+        self.messages.insert(name, message);
+    }
+
+    /// Adds the given enum to the index.
+    fn add_enum(&mut self, scope: &str, enum_type: &'static EnumDescriptorProto) {
+        let name = format!("{scope}.{}", enum_type.get_name());
+        self.enums.insert(name, enum_type);
+    }
+
+    /// Looks up a message descriptor by its fully qualified name.
+    fn message(&self, name: &str) -> Result<&'static DescriptorProto, ParseError> {
+        self.messages.get(name).copied()
+            .ok_or_else(|| ParseError::new(format!("unknown message type '{name}'")))
+    }
+
+    /// Looks up an enum descriptor by its fully qualified name.
+    fn enum_type(&self, name: &str) -> Result<&'static EnumDescriptorProto, ParseError> {
+        self.enums.get(name).copied()
+            .ok_or_else(|| ParseError::new(format!("unknown enum type '{name}'")))
+    }
+}
+
+/// Encodes a parsed message of the given type into the wire format.
+fn encode(
+    type_name: &str,
+    fields: Vec<(String, Value)>,
+    format: Format,
+) -> Result<Vec<u8>, ParseError> {
+    let schema = Schema::new();
+    let encoder = Encoder {
+        schema: &schema,
+        format,
+    };
+
+    let type_name = format!(".{}", type_name.trim_start_matches('.'));
+
+    let mut output = Vec::new();
+    encoder.encode_message(schema.message(&type_name)?, fields, &mut output)?;
+
+    Ok(output)
+}
+
+/// Encoder of parsed messages into the wire format.
+struct Encoder<'s> {
+    /// Index of types used to interpret the messages.
+    schema: &'s Schema,
+    /// Format from which the messages were parsed.
+    format: Format,
+}
+
+impl<'s> Encoder<'s> {
+
+    /// Encodes message fields according to the given message descriptor.
+    fn encode_message(
+        &self,
+        descriptor: &DescriptorProto,
+        fields: Vec<(String, Value)>,
+        output: &mut Vec<u8>,
+    ) -> Result<(), ParseError> {
+        for (name, value) in fields {
+            let field = descriptor.get_field().iter()
+                .find(|field| {
+                    field.get_name() == name ||
+                    self.format == Format::Json && json_name(field.get_name()) == name
+                })
+                .ok_or_else(|| {
+                    let message = format! {
+                        "unknown field '{name}' of '{}'", descriptor.get_name()
+                    };
+                    ParseError::new(message)
+                })?;
+
+            let repeated = field.get_label() == FieldDescriptorProto_Label::LABEL_REPEATED;
+            match value {
+                Value::Null => (),
+                Value::List(values) if repeated => {
+                    for value in values {
+                        self.encode_field(field, value, output)?;
+                    }
+                }
+                Value::List(_) => {
+                    let message = format!("field '{name}' is not repeated");
+                    return Err(ParseError::new(message));
+                }
+                value => self.encode_field(field, value, output)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encodes a single value of the given field.
+    fn encode_field(
+        &self,
+        field: &FieldDescriptorProto,
+        value: Value,
+        output: &mut Vec<u8>,
+    ) -> Result<(), ParseError> {
+        use FieldDescriptorProto_Type::*;
+
+        let number = field.get_number() as u32;
+        let mut stream = protobuf::CodedOutputStream::vec(output);
+
+        let result = match field.get_field_type() {
+            TYPE_DOUBLE => stream.write_double(number, self.float(field, value)?),
+            TYPE_FLOAT => stream.write_float(number, self.float(field, value)? as f32),
+            TYPE_INT64 => stream.write_int64(number, self.int(field, value)?),
+            TYPE_UINT64 => stream.write_uint64(number, self.int(field, value)?),
+            TYPE_INT32 => stream.write_int32(number, self.int(field, value)?),
+            TYPE_FIXED64 => stream.write_fixed64(number, self.int(field, value)?),
+            TYPE_FIXED32 => stream.write_fixed32(number, self.int(field, value)?),
+            TYPE_BOOL => stream.write_bool(number, self.bool(field, value)?),
+            TYPE_STRING => {
+                let string = String::from_utf8(self.bytes(field, value)?)
+                    .map_err(|_| invalid_value(field))?;
+                stream.write_string(number, &string)
+            }
+            TYPE_BYTES => stream.write_bytes(number, &self.bytes(field, value)?),
+            TYPE_UINT32 => stream.write_uint32(number, self.int(field, value)?),
+            TYPE_ENUM => stream.write_enum(number, self.enum_value(field, value)?),
+            TYPE_SFIXED32 => stream.write_sfixed32(number, self.int(field, value)?),
+            TYPE_SFIXED64 => stream.write_sfixed64(number, self.int(field, value)?),
+            TYPE_SINT32 => stream.write_sint32(number, self.int(field, value)?),
+            TYPE_SINT64 => stream.write_sint64(number, self.int(field, value)?),
+            TYPE_MESSAGE => {
+                let fields = match value {
+                    Value::Message(fields) => fields,
+                    _ => return Err(invalid_value(field)),
+                };
+
+                let descriptor = self.schema.message(field.get_type_name())?;
+
+                let mut bytes = Vec::new();
+                self.encode_message(descriptor, fields, &mut bytes)?;
+                stream.write_bytes(number, &bytes)
+            }
+            TYPE_GROUP => {
+                let message = format!("field '{}' is a group", field.get_name());
+                return Err(ParseError::new(message));
+            }
+        };
+
+        result.and_then(|()| stream.flush())
+            // Writing to a vector can fail only if we are out of memory.
+            .expect("failed to write to a vector");
+
+        Ok(())
+    }
+
+    /// Interprets the given value as a floating point number.
+    fn float(&self, field: &FieldDescriptorProto, value: Value) -> Result<f64, ParseError> {
+        let literal = self.literal(field, value)?;
+        match literal.as_str() {
+            "inf" | "Infinity" => Ok(f64::INFINITY),
+            "-inf" | "-Infinity" => Ok(f64::NEG_INFINITY),
+            "nan" | "NaN" => Ok(f64::NAN),
+            _ => literal.trim_end_matches(['f', 'F']).parse()
+                .map_err(|_| invalid_value(field)),
+        }
+    }
+
+    /// Interprets the given value as an integer of the specified type.
+    fn int<T>(&self, field: &FieldDescriptorProto, value: Value) -> Result<T, ParseError>
+    where
+        T: TryFrom<i128>,
+    {
+        let literal = self.literal(field, value)?;
+
+        let (negative, digits) = match literal.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, literal.as_str()),
+        };
+
+        let int = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => digits.parse::<i128>(),
+        }.map_err(|_| invalid_value(field))?;
+
+        T::try_from(if negative { -int } else { int })
+            .map_err(|_| invalid_value(field))
+    }
+
+    /// Interprets the given value as a boolean.
+    fn bool(&self, field: &FieldDescriptorProto, value: Value) -> Result<bool, ParseError> {
+        match value {
+            Value::Literal(literal) => match literal.as_str() {
+                "true" | "True" | "t" | "1" => Ok(true),
+                "false" | "False" | "f" | "0" => Ok(false),
+                _ => Err(invalid_value(field)),
+            },
+            _ => Err(invalid_value(field)),
+        }
+    }
+
+    /// Interprets the given value as a sequence of bytes.
+    ///
+    /// Note that in JSON, values of `bytes` fields are encoded using base64.
+    fn bytes(&self, field: &FieldDescriptorProto, value: Value) -> Result<Vec<u8>, ParseError> {
+        let bytes = match value {
+            Value::String(bytes) => bytes,
+            _ => return Err(invalid_value(field)),
+        };
+
+        if self.format == Format::Json && field.get_field_type() == FieldDescriptorProto_Type::TYPE_BYTES {
+            base64_decode(&bytes)
+                .ok_or_else(|| invalid_value(field))
+        } else {
+            Ok(bytes)
+        }
+    }
+
+    /// Interprets the given value as an enum value (by name or number).
+    fn enum_value(&self, field: &FieldDescriptorProto, value: Value) -> Result<i32, ParseError> {
+        let name = match value {
+            Value::Literal(literal) => literal,
+            Value::String(bytes) if self.format == Format::Json => {
+                String::from_utf8(bytes)
+                    .map_err(|_| invalid_value(field))?
+            }
+            _ => return Err(invalid_value(field)),
+        };
+
+        if let Ok(number) = name.parse::<i32>() {
+            return Ok(number);
+        }
+
+        self.schema.enum_type(field.get_type_name())?
+            .get_value().iter()
+            .find(|value| value.get_name() == name)
+            .map(|value| value.get_number())
+            .ok_or_else(|| invalid_value(field))
+    }
+
+    /// Interprets the given value as a numeric literal.
+    ///
+    /// Note that in JSON, numeric values can be also specified as strings.
+    fn literal(&self, field: &FieldDescriptorProto, value: Value) -> Result<String, ParseError> {
+        match value {
+            Value::Literal(literal) => Ok(literal),
+            Value::String(bytes) if self.format == Format::Json => {
+                String::from_utf8(bytes)
+                    .map_err(|_| invalid_value(field))
+            }
+            _ => Err(invalid_value(field)),
+        }
+    }
+}
+
+/// Creates an error for an invalid value of the given field.
+fn invalid_value(field: &FieldDescriptorProto) -> ParseError {
+    ParseError::new(format!("invalid value of field '{}'", field.get_name()))
+}
+
+/// Prints the given message in the JSON format to the given output.
+fn print_json_message(message: &dyn protobuf::Message, output: &mut String) {
+    use protobuf::reflect::ReflectFieldRef;
+
+    output.push('{');
+
+    let mut first = true;
+    for field in message.descriptor().fields() {
+        let mut separator = |output: &mut String| {
+            if !first {
+                output.push(',');
+            }
+            first = false;
+
+            print_json_string(field.json_name(), output);
+            output.push(':');
+        };
+
+        match field.get_reflect(message) {
+            ReflectFieldRef::Optional(None) => (),
+            ReflectFieldRef::Optional(Some(value)) => {
+                separator(output);
+                print_json_value(value, output);
+            }
+            ReflectFieldRef::Repeated(values) => {
+                if values.len() == 0 {
+                    continue;
+                }
+                separator(output);
+
+                output.push('[');
+                for (i, value) in values.reflect_iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    print_json_value(value.as_ref(), output);
+                }
+                output.push(']');
+            }
+            ReflectFieldRef::Map(map) => {
+                if map.len() == 0 {
+                    continue;
+                }
+                separator(output);
+
+                output.push('{');
+                for (i, (key, value)) in map.reflect_iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+
+                    // Map keys are always strings in JSON.
+                    let mut key_output = String::new();
+                    print_json_value(key.as_ref(), &mut key_output);
+                    print_json_string(key_output.trim_matches('"'), output);
+
+                    output.push(':');
+                    print_json_value(value.as_ref(), output);
+                }
+                output.push('}');
+            }
+        }
+    }
+
+    output.push('}');
+}
+
+/// Prints the given singular value in the JSON format to the given output.
+fn print_json_value(value: protobuf::reflect::ReflectValueRef, output: &mut String) {
+    use protobuf::reflect::ReflectValueRef::*;
+    use std::fmt::Write as _;
+
+    // Writing to a string cannot fail.
+    match value {
+        U32(value) => write!(output, "{value}").unwrap(),
+        I32(value) => write!(output, "{value}").unwrap(),
+        // 64-bit integers are represented as strings in JSON as they cannot be
+        // represented exactly as floating point numbers.
+        U64(value) => write!(output, "\"{value}\"").unwrap(),
+        I64(value) => write!(output, "\"{value}\"").unwrap(),
+        F32(value) => print_json_float(f64::from(value), output),
+        F64(value) => print_json_float(value, output),
+        Bool(value) => write!(output, "{value}").unwrap(),
+        String(value) => print_json_string(value, output),
+        Bytes(value) => print_json_string(&base64_encode(value), output),
+        Enum(value) => print_json_string(value.name(), output),
+        Message(value) => print_json_message(value, output),
+    }
+}
+
+/// Prints the given floating point number in the JSON format.
+fn print_json_float(value: f64, output: &mut String) {
+    use std::fmt::Write as _;
+
+    // Writing to a string cannot fail.
+    if value.is_nan() {
+        output.push_str("\"NaN\"");
+    } else if value == f64::INFINITY {
+        output.push_str("\"Infinity\"");
+    } else if value == f64::NEG_INFINITY {
+        output.push_str("\"-Infinity\"");
+    } else {
+        write!(output, "{value}").unwrap();
+    }
+}
+
+/// Prints the given string as a quoted JSON string literal.
+fn print_json_string(value: &str, output: &mut String) {
+    use std::fmt::Write as _;
+
+    output.push('"');
+    for char in value.chars() {
+        match char {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            // Writing to a string cannot fail.
+            _ if char.is_control() => write!(output, "\\u{:04x}", char as u32).unwrap(),
+            _ => output.push(char),
+        }
+    }
+    output.push('"');
+}
+
+/// Converts a field name to its JSON (lower camel case) form.
+fn json_name(name: &str) -> String {
+    let mut result = String::new();
+
+    let mut capitalize = false;
+    for char in name.chars() {
+        if char == '_' {
+            capitalize = true;
+        } else if capitalize {
+            result.push(char.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            result.push(char);
+        }
+    }
+
+    result
+}
+
+/// Alphabet of the standard base64 encoding.
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the given bytes using the standard base64 encoding (with padding).
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let mut buf = [0u8; 3];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from(buf[0]) << 16 | u32::from(buf[1]) << 8 | u32::from(buf[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3F;
+                result.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+/// Decodes the given standard or URL-safe base64 data (padding is optional).
+///
+/// If the data is not a valid base64 encoding, `None` is returned.
+fn base64_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let data = match data.iter().position(|byte| *byte == b'=') {
+        Some(pos) if data[pos..].iter().all(|byte| *byte == b'=') => &data[..pos],
+        Some(_) => return None,
+        None => data,
+    };
+
+    let mut result = Vec::new();
+    for chunk in data.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let mut bits = 0u32;
+        for (i, byte) in chunk.iter().enumerate() {
+            let index = match byte {
+                b'-' => 62,
+                b'_' => 63,
+                _ => BASE64_ALPHABET.iter().position(|char| char == byte)?,
+            };
+            bits |= (index as u32) << (18 - 6 * i);
+        }
+
+        result.extend(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::v2::get_file_hash;
+    use crate::v2::list_connections;
+
+    #[test]
+    fn parse_text_empty() {
+        let args = parse_text::<get_file_hash::Args>("")
+            .unwrap();
+
+        assert!(!args.has_path());
+        assert!(!args.get_md5());
+    }
+
+    #[test]
+    fn parse_text_nested() {
+        let args = parse_text::<get_file_hash::Args>(r#"
+            # This is a comment.
+            path { raw_bytes: "/foo/bar" }
+            md5: true
+            sha256: true
+        "#).unwrap();
+
+        assert_eq!(args.get_path().get_raw_bytes(), b"/foo/bar");
+        assert!(args.get_md5());
+        assert!(!args.get_sha1());
+        assert!(args.get_sha256());
+    }
+
+    #[test]
+    fn parse_text_repeated() {
+        let args = parse_text::<list_connections::Args>(r#"
+            pids: 1
+            pids: [2, 0x3]
+            tcp_states: LISTEN,
+            tcp_states: 2;
+        "#).unwrap();
+
+        use crate::v2::net::TcpState::*;
+
+        assert_eq!(args.get_pids(), &[1, 2, 3]);
+        assert_eq!(args.get_tcp_states(), &[LISTEN, SYN_SENT]);
+    }
+
+    #[test]
+    fn parse_text_string_escapes() {
+        let args = parse_text::<get_file_hash::Args>(r#"
+            path { raw_bytes: "\x00\377\n" 'foo\'' }
+        "#).unwrap();
+
+        assert_eq!(args.get_path().get_raw_bytes(), b"\x00\xFF\nfoo'");
+    }
+
+    #[test]
+    fn parse_text_unknown_field() {
+        assert!(parse_text::<get_file_hash::Args>("foo: 42").is_err());
+    }
+
+    #[test]
+    fn parse_text_invalid_value() {
+        assert!(parse_text::<get_file_hash::Args>("md5: 42").is_err());
+        assert!(parse_text::<list_connections::Args>("pids: -1").is_err());
+        assert!(parse_text::<list_connections::Args>("tcp_states: FOO").is_err());
+    }
+
+    #[test]
+    fn parse_text_malformed() {
+        assert!(parse_text::<get_file_hash::Args>("path {").is_err());
+        assert!(parse_text::<get_file_hash::Args>("md5 true").is_err());
+        assert!(parse_text::<get_file_hash::Args>("path { raw_bytes: \"foo }").is_err());
+    }
+
+    #[test]
+    fn parse_json_nested() {
+        let args = parse_json::<get_file_hash::Args>(r#"{
+            "path": {"rawBytes": "L2Zvby9iYXI="},
+            "md5": true,
+            "sha256": null
+        }"#).unwrap();
+
+        assert_eq!(args.get_path().get_raw_bytes(), b"/foo/bar");
+        assert!(args.get_md5());
+        assert!(!args.get_sha256());
+    }
+
+    #[test]
+    fn parse_json_repeated() {
+        let args = parse_json::<list_connections::Args>(r#"{
+            "pids": [1, "2"],
+            "tcp_states": ["LISTEN", 2]
+        }"#).unwrap();
+
+        use crate::v2::net::TcpState::*;
+
+        assert_eq!(args.get_pids(), &[1, 2]);
+        assert_eq!(args.get_tcp_states(), &[LISTEN, SYN_SENT]);
+    }
+
+    #[test]
+    fn parse_json_malformed() {
+        assert!(parse_json::<get_file_hash::Args>("").is_err());
+        assert!(parse_json::<get_file_hash::Args>("[]").is_err());
+        assert!(parse_json::<get_file_hash::Args>(r#"{"md5": true"#).is_err());
+        assert!(parse_json::<get_file_hash::Args>(r#"{"md5": true}}"#).is_err());
+    }
+
+    #[test]
+    fn print_json_default() {
+        let args = get_file_hash::Args::new();
+        assert_eq!(print_json(&args), "{}");
+    }
+
+    #[test]
+    fn print_json_roundtrip() {
+        let mut args = list_connections::Args::new();
+        args.set_pids(vec![1, 2, 3]);
+        args.set_tcp_states(vec![crate::v2::net::TcpState::LISTEN]);
+
+        let json = print_json(&args);
+        assert_eq!(json, r#"{"pids":[1,2,3],"tcpStates":["LISTEN"]}"#);
+        assert_eq!(parse_json::<list_connections::Args>(&json).unwrap(), args);
+    }
+
+    #[test]
+    fn base64_roundtrip() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"\xFF\x00\xFE"] {
+            assert_eq!(base64_decode(base64_encode(data).as_bytes()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn base64_decode_invalid() {
+        assert!(base64_decode(b"Zm9v!").is_none());
+        assert!(base64_decode(b"Z").is_none());
+        assert!(base64_decode(b"Zm=9").is_none());
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+fn main() {
+    // We respect the `SOURCE_DATE_EPOCH` variable [1] so that it is possible
+    // to make builds reproducible. Otherwise, the current time is used.
+    //
+    // [1]: https://reproducible-builds.org/specs/source-date-epoch/
+    let build_time = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(build_time) => build_time,
+        Err(_) => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("current time before epoch")
+            .as_secs()
+            .to_string(),
+    };
+
+    println!("cargo:rustc-env=RRG_BUILD_TIME={build_time}");
+
+    // Without this, Cargo would reuse the embedded timestamp from a previous
+    // build even if the variable has changed. Emitting any `rerun-if` directive
+    // disables the default of rerunning on any change in the package, so we
+    // need to restore it explicitly.
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+}
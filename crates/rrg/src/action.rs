@@ -18,12 +18,42 @@
 #[allow(dead_code)]
 pub mod deprecated; // TODO(@panhania): Unexpose this module.
 
+#[cfg(feature = "action-get_agent_metadata")]
+pub mod get_agent_metadata;
+
 #[cfg(feature = "action-get_system_metadata")]
 pub mod get_system_metadata;
 
 #[cfg(feature = "action-get_file_metadata")]
 pub mod get_file_metadata;
 
+#[cfg(feature = "action-get_file_hash")]
+pub mod get_file_hash;
+
+#[cfg(feature = "action-list_directory")]
+pub mod list_directory;
+
+#[cfg(feature = "action-list_processes")]
+pub mod list_processes;
+
+#[cfg(feature = "action-list_connections")]
+pub mod list_connections;
+
+#[cfg(feature = "action-list_users")]
+pub mod list_users;
+
+#[cfg(feature = "action-get_filesystem_timeline")]
+pub mod get_filesystem_timeline;
+
+#[cfg(feature = "action-cancel_request")]
+pub mod cancel_request;
+
+#[cfg(feature = "action-find_files")]
+pub mod find_files;
+
+#[cfg(feature = "action-scan_files")]
+pub mod scan_files;
+
 /// Dispatches the given `request` to an appropriate action handler.
 ///
 /// This method is a mapping between action names (as specified in the protocol)
@@ -42,7 +72,11 @@ where
 {
     use crate::request::Action::*;
 
-    match request.action() {
+    match request.action()? {
+        #[cfg(feature = "action-get_agent_metadata")]
+        GetAgentMetadata => {
+            handle(session, request, self::get_agent_metadata::handle)
+        }
         #[cfg(feature = "action-get_system_metadata")]
         GetSystemMetadata => {
             handle(session, request, self::get_system_metadata::handle)
@@ -51,6 +85,42 @@ where
         GetFileMetadata => {
             handle(session, request, self::get_file_metadata::handle)
         }
+        #[cfg(feature = "action-get_file_hash")]
+        GetFileHash => {
+            handle(session, request, self::get_file_hash::handle)
+        }
+        #[cfg(feature = "action-list_directory")]
+        ListDirectory => {
+            handle(session, request, self::list_directory::handle)
+        }
+        #[cfg(feature = "action-list_processes")]
+        ListProcesses => {
+            handle(session, request, self::list_processes::handle)
+        }
+        #[cfg(feature = "action-list_connections")]
+        ListConnections => {
+            handle(session, request, self::list_connections::handle)
+        }
+        #[cfg(feature = "action-list_users")]
+        ListUsers => {
+            handle(session, request, self::list_users::handle)
+        }
+        #[cfg(feature = "action-get_filesystem_timeline")]
+        GetFilesystemTimeline => {
+            handle(session, request, self::get_filesystem_timeline::handle)
+        }
+        #[cfg(feature = "action-cancel_request")]
+        CancelRequest => {
+            handle(session, request, self::cancel_request::handle)
+        }
+        #[cfg(feature = "action-find_files")]
+        FindFiles => {
+            handle(session, request, self::find_files::handle)
+        }
+        #[cfg(feature = "action-scan_files")]
+        ScanFiles => {
+            handle(session, request, self::scan_files::handle)
+        }
     }
 }
 
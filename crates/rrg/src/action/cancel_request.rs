@@ -0,0 +1,85 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+/// Arguments of the `cancel_request` action.
+pub struct Args {
+    /// Identifier of the request to cancel.
+    request_id: crate::RequestId,
+}
+
+/// Result of the `cancel_request` action.
+struct Item {
+    /// Whether the request was queued or being executed at the moment of
+    /// cancellation.
+    cancelled: bool,
+}
+
+/// Handles invocations of the `cancel_request` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    let cancelled = crate::session::cancel(args.request_id);
+    if cancelled {
+        log::info!("cancelled request '{}'", args.request_id);
+    }
+
+    session.reply(Item {
+        cancelled,
+    })?;
+
+    Ok(())
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::cancel_request::Args;
+
+    fn from_proto(proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        Ok(Args {
+            request_id: crate::RequestId::new(proto.get_flow_id(), proto.get_request_id()),
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::cancel_request::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::cancel_request::Result::new();
+        proto.set_cancelled(self.cancelled);
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handle_running() {
+        let request_id = crate::RequestId::new(0xCA4CE1, 1);
+        let cancellation = crate::session::Cancellation::new(request_id);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, Args { request_id }).is_ok());
+
+        assert!(cancellation.is_cancelled());
+        assert!(session.reply::<Item>(0).cancelled);
+    }
+
+    #[test]
+    fn handle_not_running() {
+        let request_id = crate::RequestId::new(0xCA4CE1, 2);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, Args { request_id }).is_ok());
+
+        assert!(!session.reply::<Item>(0).cancelled);
+    }
+}
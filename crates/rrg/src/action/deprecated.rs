@@ -12,7 +12,7 @@
 #[cfg(target_os = "linux")]
 pub mod filesystems;
 
-#[cfg(feature = "action-finder")]
+#[cfg(any(feature = "action-finder", feature = "action-find_files", feature = "action-scan_files"))]
 pub mod finder;
 
 #[cfg(feature = "action-insttime")]
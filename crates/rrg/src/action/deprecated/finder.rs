@@ -4,11 +4,17 @@
 // in the LICENSE file or at https://opensource.org/licenses/MIT.
 
 //! Handler for `client side file finder` action.
+//!
+//! Modules that do not depend on the legacy protocol are also used by the
+//! `find_files` and `scan_files` actions.
 
+#[cfg(feature = "action-finder")]
 pub mod download;
 pub mod glob;
 pub mod chunks;
 pub mod condition;
-pub mod groups;
+#[cfg(feature = "action-finder")]
 pub mod request;
+#[cfg(any(feature = "action-finder", feature = "action-find_files"))]
 pub mod hash;
+pub mod matcher;
@@ -1,11 +1,10 @@
 use super::chunks::{get_file_chunks, GetFileChunksConfig};
-use super::request::{
-    Condition, ContentsMatchCondition, MatchMode,
-};
+use super::matcher::{Matcher, ScanOpts};
 use crate::fs::Entry;
 use log::warn;
 use rrg_macro::ack;
-use std::cmp::{max, min};
+use std::cmp::min;
+use std::ops::ControlFlow;
 #[cfg(target_family = "unix")]
 use std::fs::Metadata;
 #[cfg(target_family = "unix")]
@@ -13,6 +12,136 @@ use std::os::unix::fs::MetadataExt;
 #[cfg(target_os = "linux")]
 use ospect::fs::linux::flags;
 
+/// Maximum number of hits reported for a single contents condition.
+///
+/// Once the limit is reached, the scan of the file stops early (as if the
+/// remaining contents did not match at all).
+pub const MAX_PATTERN_HITS: usize = 1024;
+
+#[derive(Debug)]
+pub enum Condition {
+    ModificationTime {
+        min: Option<std::time::SystemTime>,
+        max: Option<std::time::SystemTime>,
+    },
+    AccessTime {
+        min: Option<std::time::SystemTime>,
+        max: Option<std::time::SystemTime>,
+    },
+    InodeChangeTime {
+        min: Option<std::time::SystemTime>,
+        max: Option<std::time::SystemTime>,
+    },
+    Size {
+        min: Option<u64>,
+        max: Option<u64>,
+    },
+    ExtFlags {
+        linux_bits_set: Option<u32>,
+        linux_bits_unset: Option<u32>,
+        osx_bits_set: Option<u32>,
+        osx_bits_unset: Option<u32>,
+    },
+    /// Owner of the file (only specified identifiers are checked).
+    Owner {
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+    /// Permission mode bits that have to be set or unset.
+    Mode {
+        bits_set: u32,
+        bits_unset: u32,
+    },
+    /// Type of the file (symlinks are not followed).
+    FileType {
+        file_type: FileType,
+    },
+    /// Inode number of the file.
+    Inode {
+        inode: u64,
+    },
+    /// Regular expression that the (lossily converted) path has to match.
+    PathRegex {
+        regex: regex::Regex,
+    },
+    /// Extended attribute that the file has to have (with the given value if
+    /// it is specified).
+    ExtAttr {
+        name: std::ffi::OsString,
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// Type of a file for the file type condition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileType {
+    Regular,
+    Dir,
+    Symlink,
+    /// Any other type of file (e.g. a device or a socket).
+    Other,
+}
+
+/// A boolean expression over conditions.
+#[derive(Debug)]
+pub enum Expr {
+    /// A single condition.
+    Condition(Condition),
+    /// Expression met if all of the subexpressions are met (or if there are
+    /// none).
+    And(Vec<Expr>),
+    /// Expression met if any of the subexpressions is met.
+    Or(Vec<Expr>),
+    /// Expression met if the subexpression is not met.
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MatchMode {
+    AllHits,
+    FirstHit,
+}
+
+#[derive(Debug)]
+pub struct ContentsMatchCondition {
+    pub regex: regex::bytes::Regex,
+    pub mode: MatchMode,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub start_offset: u64,
+    pub length: u64,
+}
+
+/// A condition on file contents matching any of multiple byte patterns.
+#[derive(Debug)]
+pub struct ContentsPatternsCondition {
+    /// Compiled patterns to look for.
+    pub matcher: Matcher,
+    /// Whether to report all hits or only the first one.
+    pub mode: MatchMode,
+    /// Number of bytes before each hit to include in the reported fragment.
+    pub bytes_before: u64,
+    /// Number of bytes after each hit to include in the reported fragment.
+    pub bytes_after: u64,
+    /// Offset at which the scan should start.
+    pub start_offset: u64,
+    /// Number of bytes to scan (starting at the specified offset).
+    pub length: u64,
+}
+
+/// A fragment of file contents matching a contents match condition.
+#[derive(Debug)]
+pub struct Match {
+    /// Offset of the fragment within the file.
+    pub offset: u64,
+    /// Length of the fragment.
+    pub length: u64,
+    /// Contents of the fragment.
+    pub data: Vec<u8>,
+    /// Index of the pattern that matched (for contents patterns conditions).
+    pub pattern: Option<usize>,
+}
+
 /// Returns true if all conditions were met.
 /// If the data required for checking the condition cannot be obtained then
 /// the condition is assumed to be met.
@@ -20,6 +149,19 @@ pub fn check_conditions(conditions: &Vec<Condition>, entry: &Entry) -> bool {
     conditions.into_iter().all(|c| check_condition(c, &entry))
 }
 
+/// Returns true if the expression is met.
+///
+/// Conditions are checked lazily, i.e. a subexpression is checked only if its
+/// result can affect the result of the whole expression.
+pub fn check_expr(expr: &Expr, entry: &Entry) -> bool {
+    match expr {
+        Expr::Condition(condition) => check_condition(condition, entry),
+        Expr::And(exprs) => exprs.iter().all(|expr| check_expr(expr, entry)),
+        Expr::Or(exprs) => exprs.iter().any(|expr| check_expr(expr, entry)),
+        Expr::Not(expr) => !check_expr(expr, entry),
+    }
+}
+
 /// Returns positions of matches from all conditions when all
 /// `match_conditions` found at least 1 match. Returns empty `Vec` otherwise.
 /// If the file content cannot be obtained the condition is assumed to
@@ -27,7 +169,7 @@ pub fn check_conditions(conditions: &Vec<Condition>, entry: &Entry) -> bool {
 pub fn find_matches(
     match_conditions: &Vec<ContentsMatchCondition>,
     entry: &Entry,
-) -> Vec<rrg_proto::jobs::BufferReference> {
+) -> Vec<Match> {
     let mut ret = vec![];
     for match_condition in match_conditions {
         let mut matches = matches(match_condition, &entry);
@@ -40,9 +182,44 @@ pub fn find_matches(
     ret
 }
 
+/// Returns hits of all patterns conditions if each of them found at least one
+/// hit. Returns empty `Vec` otherwise.
+///
+/// If the file contents cannot be read the condition is assumed to be not met.
+pub fn find_pattern_matches(
+    conditions: &[ContentsPatternsCondition],
+    entry: &Entry,
+) -> Vec<Match> {
+    let mut ret = vec![];
+    for condition in conditions {
+        let mut matches = match pattern_matches(condition, entry) {
+            Ok(matches) => matches,
+            Err(err) => {
+                warn!(
+                    "failed to scan file: {}, error: {}",
+                    entry.path.display(),
+                    err
+                );
+                return vec![];
+            }
+        };
+        if matches.is_empty() {
+            return vec![];
+        }
+        ret.append(&mut matches);
+    }
+
+    ret
+}
+
 /// Checks is the condition is met by the entry.
 /// In case of simple conditions if the data required for checking the condition
 /// cannot be obtained then the condition is assumed to be met.
+///
+/// Conditions on the owner, mode, inode and extended attributes are the
+/// exception: if the data cannot be obtained (e.g. because it is not available
+/// on the platform), they are not met. Otherwise these conditions would make
+/// alternatives match everything when used in expressions.
 fn check_condition(condition: &Condition, entry: &Entry) -> bool {
     match condition {
         Condition::ModificationTime { min, max } => {
@@ -100,7 +277,7 @@ fn check_condition(condition: &Condition, entry: &Entry) -> bool {
 
             if let Ok(flags) = flags(&entry.path) {
                 if let Some(linux_bits_set) = linux_bits_set {
-                    ok &= flags & linux_bits_set == flags;
+                    ok &= flags & linux_bits_set == *linux_bits_set;
                 }
                 if let Some(linux_bits_unset) = linux_bits_unset {
                     ok &= flags & linux_bits_unset == 0;
@@ -117,6 +294,55 @@ fn check_condition(condition: &Condition, entry: &Entry) -> bool {
 
         #[cfg(not(target_os = "linux"))]
         Condition::ExtFlags {..} => true,
+
+        #[cfg(target_family = "unix")]
+        Condition::Owner { uid, gid } => {
+            uid.iter().all(|uid| entry.metadata.uid() == *uid) &&
+            gid.iter().all(|gid| entry.metadata.gid() == *gid)
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        Condition::Owner { .. } => false,
+
+        #[cfg(target_family = "unix")]
+        Condition::Mode { bits_set, bits_unset } => {
+            let mode = entry.metadata.mode();
+            mode & bits_set == *bits_set && mode & bits_unset == 0
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        Condition::Mode { .. } => false,
+
+        Condition::FileType { file_type } => {
+            let actual = entry.metadata.file_type();
+            match file_type {
+                FileType::Regular => actual.is_file(),
+                FileType::Dir => actual.is_dir(),
+                FileType::Symlink => actual.is_symlink(),
+                FileType::Other => {
+                    !actual.is_file() && !actual.is_dir() && !actual.is_symlink()
+                }
+            }
+        }
+
+        #[cfg(target_family = "unix")]
+        Condition::Inode { inode } => entry.metadata.ino() == *inode,
+
+        #[cfg(not(target_family = "unix"))]
+        Condition::Inode { .. } => false,
+
+        Condition::PathRegex { regex } => {
+            regex.is_match(&entry.path.to_string_lossy())
+        }
+
+        Condition::ExtAttr { name, value } => {
+            match ospect::fs::ext_attr_value(&entry.path, name) {
+                Ok(actual) => value.iter().all(|value| actual == *value),
+                // Missing attributes are reported as errors as well, so there
+                // is no point in logging them.
+                Err(_) => false,
+            }
+        }
     }
 }
 
@@ -140,7 +366,7 @@ fn is_in_range<T: Ord>(value: &T, range: (&Option<T>, &Option<T>)) -> bool {
 fn matches(
     condition: &ContentsMatchCondition,
     entry: &Entry,
-) -> Vec<rrg_proto::jobs::BufferReference> {
+) -> Vec<Match> {
     const BYTES_PER_CHUNK: u64 = 10 * 1024 * 1024;
     const OVERLAP_BYTES: u64 = 1024 * 1024;
 
@@ -174,21 +400,23 @@ fn matches(
             }
         };
         for m in condition.regex.find_iter(chunk.as_slice()) {
-            let start = max(m.start() as u64 - condition.bytes_before, 0);
-            let end =
-                min(m.end() as u64 + condition.bytes_after, chunk.len() as u64);
+            let start = (m.start() as u64).saturating_sub(condition.bytes_before);
+            let end = min(
+                (m.end() as u64).saturating_add(condition.bytes_after),
+                chunk.len() as u64,
+            );
             let data = chunk[(start as usize)..(end as usize)].to_vec();
 
-            let mut buf_ref = rrg_proto::jobs::BufferReference::new();
-            buf_ref.set_offset(offset + start);
-            buf_ref.set_length(end - start);
-            buf_ref.set_data(data);
-            buf_ref.set_pathspec(entry.path.clone().into());
-
-            matches.push(buf_ref);
+            matches.push(Match {
+                offset: offset + start,
+                length: end - start,
+                data,
+                pattern: None,
+            });
 
             match condition.mode {
                 MatchMode::FirstHit => return matches,
+                MatchMode::AllHits if matches.len() >= MAX_PATTERN_HITS => return matches,
                 MatchMode::AllHits => (),
             }
         }
@@ -198,6 +426,44 @@ fn matches(
     matches
 }
 
+/// Scans contents of the entry for hits of the condition patterns.
+///
+/// In the "all hits" mode at most [`MAX_PATTERN_HITS`] hits are returned, so
+/// that files with plenty of hits do not make us keep all of them in memory.
+fn pattern_matches(
+    condition: &ContentsPatternsCondition,
+    entry: &Entry,
+) -> std::io::Result<Vec<Match>> {
+    use std::io::{Read as _, Seek as _};
+
+    let mut file = std::fs::File::open(&entry.path)?;
+    file.seek(std::io::SeekFrom::Start(condition.start_offset))?;
+
+    let opts = ScanOpts {
+        bytes_before: usize::try_from(condition.bytes_before).unwrap_or(usize::MAX),
+        bytes_after: usize::try_from(condition.bytes_after).unwrap_or(usize::MAX),
+        offset: condition.start_offset,
+    };
+
+    let mut matches = vec![];
+    condition.matcher.scan(file.take(condition.length), opts, |hit| {
+        matches.push(Match {
+            offset: hit.offset,
+            length: hit.data.len() as u64,
+            data: hit.data,
+            pattern: Some(hit.pattern),
+        });
+
+        match condition.mode {
+            MatchMode::FirstHit => ControlFlow::Break(()),
+            MatchMode::AllHits if matches.len() >= MAX_PATTERN_HITS => ControlFlow::Break(()),
+            MatchMode::AllHits => ControlFlow::Continue(()),
+        }
+    })?;
+
+    Ok(matches)
+}
+
 /// Reads inode change time from metadata.
 #[cfg(target_family = "unix")]
 fn read_ctime(metadata: &Metadata) -> Option<std::time::SystemTime> {
@@ -238,6 +504,32 @@ mod tests {
         assert_eq!(ctime, mtime);
     }
 
+    #[test]
+    fn test_pattern_matches_max_hits() {
+        use super::super::matcher::BytePattern;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "a".repeat(MAX_PATTERN_HITS * 2)).unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        let condition = ContentsPatternsCondition {
+            matcher: Matcher::new(vec![BytePattern::Literal(b"a".to_vec())]).unwrap(),
+            mode: MatchMode::AllHits,
+            bytes_before: 0,
+            bytes_after: 0,
+            start_offset: 0,
+            length: u64::MAX,
+        };
+
+        let matches = pattern_matches(&condition, &entry).unwrap();
+        assert_eq!(matches.len(), MAX_PATTERN_HITS);
+        assert_eq!(matches.last().unwrap().offset, MAX_PATTERN_HITS as u64 - 1);
+    }
+
     #[test]
     fn test_size_condition() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -330,6 +622,46 @@ mod tests {
         ));
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_extflags_condition_bits_set_subset() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path: path.clone(),
+        };
+
+        let flags = flags(&path).unwrap();
+        assert_ne!(flags, 0);
+
+        // The lowest bit that is set and the lowest bit that is not set.
+        let set_bit = flags & flags.wrapping_neg();
+        let unset_bit = !flags & (!flags).wrapping_neg();
+
+        // Bits that are required to be set can be a subset of the file flags.
+        assert!(check_condition(
+            &Condition::ExtFlags {
+                linux_bits_set: Some(set_bit),
+                linux_bits_unset: None,
+                osx_bits_set: None,
+                osx_bits_unset: None
+            },
+            &entry
+        ));
+        // But all of them have to be set, even if the file flags are a subset.
+        assert!(!check_condition(
+            &Condition::ExtFlags {
+                linux_bits_set: Some(flags | unset_bit),
+                linux_bits_unset: None,
+                osx_bits_set: None,
+                osx_bits_unset: None
+            },
+            &entry
+        ));
+    }
+
     #[test]
     #[cfg(target_family = "unix")]
     fn test_modification_time_condition() {
@@ -554,10 +886,90 @@ mod tests {
         assert_eq!(matches.len(), 1);
 
         let m = matches.first().unwrap();
-        assert_eq!(m.get_data(), &"3te123st12".as_bytes().to_vec());
-        assert_eq!(m.get_length(), 10);
-        assert_eq!(m.get_offset(), 2);
-        assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+        assert_eq!(m.data, "3te123st12".as_bytes().to_vec());
+        assert_eq!(m.length, 10);
+        assert_eq!(m.offset, 2);
+    }
+
+    #[test]
+    fn test_contents_match_condition_bytes_before_file_start() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "12test123").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path: path.clone(),
+        };
+
+        let matches = matches(
+            &ContentsMatchCondition {
+                regex: regex::bytes::Regex::new("test").unwrap(),
+                mode: MatchMode::AllHits,
+                bytes_before: 10,
+                bytes_after: 1,
+                start_offset: 0,
+                length: 1000,
+            },
+            &entry,
+        );
+        assert_eq!(matches.len(), 1);
+
+        let m = matches.first().unwrap();
+        assert_eq!(m.data, "12test1".as_bytes().to_vec());
+        assert_eq!(m.length, 7);
+        assert_eq!(m.offset, 0);
+    }
+
+    #[test]
+    fn test_contents_match_condition_bytes_after_overflow() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "12test123").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path: path.clone(),
+        };
+
+        let matches = matches(
+            &ContentsMatchCondition {
+                regex: regex::bytes::Regex::new("test").unwrap(),
+                mode: MatchMode::AllHits,
+                bytes_before: 0,
+                bytes_after: u64::MAX,
+                start_offset: 0,
+                length: 1000,
+            },
+            &entry,
+        );
+        assert_eq!(matches.len(), 1);
+
+        let m = matches.first().unwrap();
+        assert_eq!(m.data, "test123".as_bytes().to_vec());
+        assert_eq!(m.offset, 2);
+    }
+
+    #[test]
+    fn test_contents_match_condition_max_hits() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "a".repeat(MAX_PATTERN_HITS * 2)).unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path: path.clone(),
+        };
+
+        let matches = matches(
+            &ContentsMatchCondition {
+                regex: regex::bytes::Regex::new(".").unwrap(),
+                mode: MatchMode::AllHits,
+                bytes_before: 0,
+                bytes_after: 0,
+                start_offset: 0,
+                length: u64::MAX,
+            },
+            &entry,
+        );
+        assert_eq!(matches.len(), MAX_PATTERN_HITS);
     }
 
     #[test]
@@ -584,10 +996,9 @@ mod tests {
         assert_eq!(matches.len(), 1);
 
         let m = matches.first().unwrap();
-        assert_eq!(m.get_data(), &"test".as_bytes().to_vec());
-        assert_eq!(m.get_length(), 4);
-        assert_eq!(m.get_offset(), 10);
-        assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+        assert_eq!(m.data, "test".as_bytes().to_vec());
+        assert_eq!(m.length, 4);
+        assert_eq!(m.offset, 10);
     }
 
     #[test]
@@ -614,10 +1025,9 @@ mod tests {
         assert_eq!(matches.len(), 1);
 
         let m = matches.first().unwrap();
-        assert_eq!(m.get_data(), &"test".as_bytes().to_vec());
-        assert_eq!(m.get_length(), 4);
-        assert_eq!(m.get_offset(), 3);
-        assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+        assert_eq!(m.data, "test".as_bytes().to_vec());
+        assert_eq!(m.length, 4);
+        assert_eq!(m.offset, 3);
     }
 
     #[test]
@@ -645,18 +1055,16 @@ mod tests {
 
         {
             let m = matches.get(0).unwrap();
-            assert_eq!(m.get_data(), &"test".as_bytes().to_vec());
-            assert_eq!(m.get_length(), 4);
-            assert_eq!(m.get_offset(), 3);
-            assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+            assert_eq!(m.data, "test".as_bytes().to_vec());
+            assert_eq!(m.length, 4);
+            assert_eq!(m.offset, 3);
         }
 
         {
             let m = matches.get(1).unwrap();
-            assert_eq!(m.get_data(), &"tttt".as_bytes().to_vec());
-            assert_eq!(m.get_length(), 4);
-            assert_eq!(m.get_offset(), 10);
-            assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+            assert_eq!(m.data, "tttt".as_bytes().to_vec());
+            assert_eq!(m.length, 4);
+            assert_eq!(m.offset, 10);
         }
     }
 
@@ -684,10 +1092,9 @@ mod tests {
         assert_eq!(matches.len(), 1);
 
         let m = matches.first().unwrap();
-        assert_eq!(m.get_data(), &"test".as_bytes().to_vec());
-        assert_eq!(m.get_length(), 4);
-        assert_eq!(m.get_offset(), 3);
-        assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+        assert_eq!(m.data, "test".as_bytes().to_vec());
+        assert_eq!(m.length, 4);
+        assert_eq!(m.offset, 3);
     }
 
     #[test]
@@ -725,18 +1132,16 @@ mod tests {
 
         {
             let m = matches.get(0).unwrap();
-            assert_eq!(m.get_data(), &"test".as_bytes().to_vec());
-            assert_eq!(m.get_length(), 4);
-            assert_eq!(m.get_offset(), 3);
-            assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+            assert_eq!(m.data, "test".as_bytes().to_vec());
+            assert_eq!(m.length, 4);
+            assert_eq!(m.offset, 3);
         }
 
         {
             let m = matches.get(1).unwrap();
-            assert_eq!(m.get_data(), &"tttt".as_bytes().to_vec());
-            assert_eq!(m.get_length(), 4);
-            assert_eq!(m.get_offset(), 10);
-            assert_eq!(m.get_pathspec().get_path(), path.to_str().unwrap());
+            assert_eq!(m.data, "tttt".as_bytes().to_vec());
+            assert_eq!(m.length, 4);
+            assert_eq!(m.offset, 10);
         }
     }
 
@@ -774,4 +1179,229 @@ mod tests {
         );
         assert!(matches.is_empty());
     }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_owner_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+        let metadata = path.metadata().unwrap();
+        let uid = metadata.uid();
+        let gid = metadata.gid();
+        let entry = Entry {
+            metadata,
+            path,
+        };
+
+        assert!(check_condition(
+            &Condition::Owner {
+                uid: Some(uid),
+                gid: None,
+            },
+            &entry
+        ));
+        assert!(check_condition(
+            &Condition::Owner {
+                uid: Some(uid),
+                gid: Some(gid),
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::Owner {
+                uid: Some(uid.wrapping_add(1)),
+                gid: Some(gid),
+            },
+            &entry
+        ));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_mode_condition() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640))
+            .unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        assert!(check_condition(
+            &Condition::Mode {
+                bits_set: 0o600,
+                bits_unset: 0o007,
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::Mode {
+                bits_set: 0o4000,
+                bits_unset: 0,
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::Mode {
+                bits_set: 0,
+                bits_unset: 0o040,
+            },
+            &entry
+        ));
+    }
+
+    #[test]
+    fn test_file_type_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let entry = Entry {
+            metadata: tempdir.path().symlink_metadata().unwrap(),
+            path: tempdir.path().to_path_buf(),
+        };
+
+        assert!(check_condition(
+            &Condition::FileType {
+                file_type: FileType::Dir,
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::FileType {
+                file_type: FileType::Regular,
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::FileType {
+                file_type: FileType::Other,
+            },
+            &entry
+        ));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_inode_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+        let metadata = path.metadata().unwrap();
+        let inode = metadata.ino();
+        let entry = Entry {
+            metadata,
+            path,
+        };
+
+        assert!(check_condition(&Condition::Inode { inode }, &entry));
+        assert!(!check_condition(&Condition::Inode { inode: inode + 1 }, &entry));
+    }
+
+    #[test]
+    fn test_path_regex_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.txt");
+        std::fs::write(&path, "test").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        assert!(check_condition(
+            &Condition::PathRegex {
+                regex: regex::Regex::new(r"foo\.txt$").unwrap(),
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::PathRegex {
+                regex: regex::Regex::new(r"\.bin$").unwrap(),
+            },
+            &entry
+        ));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", feature = "test-setfattr"))]
+    fn test_ext_attr_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+
+        assert! {
+            std::process::Command::new("setfattr")
+                .arg("--name").arg("user.norf")
+                .arg("--value").arg("quux")
+                .arg(&path)
+                .status()
+                .unwrap()
+                .success()
+        };
+
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        assert!(check_condition(
+            &Condition::ExtAttr {
+                name: "user.norf".into(),
+                value: None,
+            },
+            &entry
+        ));
+        assert!(check_condition(
+            &Condition::ExtAttr {
+                name: "user.norf".into(),
+                value: Some(b"quux".to_vec()),
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::ExtAttr {
+                name: "user.norf".into(),
+                value: Some(b"thud".to_vec()),
+            },
+            &entry
+        ));
+        assert!(!check_condition(
+            &Condition::ExtAttr {
+                name: "user.blargh".into(),
+                value: None,
+            },
+            &entry
+        ));
+    }
+
+    #[test]
+    fn test_expr() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "test").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        let size = |min| Expr::Condition(Condition::Size {
+            min: Some(min),
+            max: None,
+        });
+
+        assert!(check_expr(&Expr::And(vec![]), &entry));
+        assert!(!check_expr(&Expr::Or(vec![]), &entry));
+        assert!(check_expr(&Expr::And(vec![size(1), size(4)]), &entry));
+        assert!(!check_expr(&Expr::And(vec![size(1), size(5)]), &entry));
+        assert!(check_expr(&Expr::Or(vec![size(5), size(4)]), &entry));
+        assert!(!check_expr(&Expr::Or(vec![size(5), size(6)]), &entry));
+        assert!(check_expr(&Expr::Not(Box::new(size(5))), &entry));
+        assert!(!check_expr(&Expr::Not(Box::new(size(4))), &entry));
+        assert!(check_expr(&Expr::And(vec! {
+            Expr::Or(vec![size(5), Expr::Not(Box::new(size(6)))]),
+            size(2),
+        }), &entry));
+    }
 }
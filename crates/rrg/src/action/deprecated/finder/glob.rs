@@ -3,77 +3,1040 @@
 // Use of this source code is governed by an MIT-style license that can be found
 // in the LICENSE file or at https://opensource.org/licenses/MIT.
 
-use regex::Regex;
+//! Matching of filesystem paths against glob patterns.
+//!
+//! A pattern is an absolute path in which components can contain wildcards:
+//!
+//!   * `*` matches any sequence of characters (within a single component),
+//!   * `?` matches any single character,
+//!   * `[...]` matches any character from the set (`[!...]` negates the set,
+//!     ranges like `[a-z]` are supported),
+//!   * `{foo,bar}` matches any of the alternatives (which can contain other
+//!     wildcards, path separators and nested groups),
+//!   * `**` as a whole component matches up to 3 levels of directories and
+//!     `**N` matches up to `N` levels.
+//!
+//! The whole pattern is compiled into a single non-deterministic automaton that
+//! is advanced component by component during a directory walk. This way a walk
+//! never descends into subtrees that cannot contain matching paths and groups
+//! of alternatives do not multiply the number of walks.
 
-/// Converts glob expression into a Rust Regex.
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+/// Depth of recursive components with no explicit depth (`**`).
+const DEFAULT_RECURSION_DEPTH: u32 = 3;
+
+/// Maximum depth of recursive components.
+const MAX_RECURSION_DEPTH: u32 = 1024;
+
+/// Maximum number of literal names for which we check existence directly.
 ///
-/// This implementation is inspired by CPython code:
-/// https://github.com/python/cpython/blob/2.7/Lib/fnmatch.py
-pub fn glob_to_regex(pat: &str) -> Result<Regex, regex::Error> {
-    let chars: Vec<char> = pat.chars().collect();
-    let mut i: usize = 0;
-    let n: usize = chars.len();
-    let mut res = String::new();
-    while i < n {
-        let c = chars[i];
-        i = i + 1;
-        if c == '*' {
-            res = res + ".*";
-        } else if c == '?' {
-            res = res + ".";
-        } else if c == '[' {
-            let mut j = i;
-            if j < n && chars[j] == '!' {
-                j = j + 1;
-            }
-            if j < n && chars[j] == ']' {
-                j = j + 1;
-            }
-            while j < n && chars[j] != ']' {
-                j = j + 1;
-            }
-            if j >= n {
-                res = res + r"\[";
-            } else {
-                let mut stuff = pat[i..j].replace(r"\", r"\\").to_owned();
-                let stuff_first_char: char = stuff.chars().next().unwrap();
-                i = j + 1;
-                if stuff_first_char == '!' {
-                    stuff = String::from("^") + &stuff[1..];
-                } else if stuff_first_char == '^' {
-                    stuff = String::from(r"\") + &stuff;
+/// If there are more of possible names of the next component, the directory is
+/// listed instead.
+const MAX_LITERAL_NAMES: usize = 32;
+
+/// Path separator used by the automaton (regardless of the platform).
+const SEP: char = '/';
+
+/// A compiled glob pattern.
+#[derive(Debug)]
+pub struct Pattern {
+    /// Longest prefix of the pattern without any wildcards.
+    root: PathBuf,
+    /// Automaton matching the remainder of the pattern (with a leading
+    /// separator) after the root.
+    nfa: Nfa,
+}
+
+/// Options that configure the search for paths matching a pattern.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FindOpts {
+    /// Whether to descend into directories that symlinks point to.
+    pub follow_symlinks: bool,
+    /// Whether to skip directories on devices other than the one of the root.
+    pub one_device: bool,
+}
+
+impl Pattern {
+
+    /// Parses and compiles the given glob pattern.
+    pub fn parse(pattern: &str) -> Result<Pattern, ParseError> {
+        if Path::new(pattern).is_relative() {
+            return Err(ParseError::RelativePath);
+        }
+
+        // The root ends at the last separator preceding the first wildcard (or
+        // spans the entire pattern if there are no wildcards at all).
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let root_len = match chars.iter().position(|char| is_special(*char)) {
+            Some(idx) => match chars[..idx].iter().rposition(|char| std::path::is_separator(*char)) {
+                Some(idx) => idx,
+                // Absolute paths always start with a separator (or a prefix on
+                // Windows, but prefixes cannot contain wildcards).
+                None => return Err(ParseError::RelativePath),
+            },
+            None => chars.len(),
+        };
+
+        // The separator that ends the root (if any) is the first character of
+        // the remainder. However, for the filesystem root the separator itself
+        // is the entire root path.
+        let mut root = chars[..root_len].iter().collect::<String>();
+        if !Path::new(&root).has_root() {
+            root.push(chars[root_len]);
+        }
+
+        let mut parser = Parser {
+            chars: &chars[root_len..],
+            pos: 0,
+        };
+        let nodes = parser.seq(false, false)?;
+
+        Ok(Pattern {
+            root: PathBuf::from(root),
+            nfa: Nfa::compile(&nodes),
+        })
+    }
+
+    /// Returns the longest prefix of the pattern without any wildcards.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Checks whether the given path matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        let rest = match path.strip_prefix(&self.root) {
+            Ok(rest) => rest,
+            Err(_) => return false,
+        };
+
+        let mut scratch = Scratch::default();
+        let mut states = self.nfa.start();
+        for component in rest.components() {
+            let name = component.as_os_str().to_string_lossy();
+            states = self.nfa.feed(&states, &name, &mut scratch);
+            if states.is_empty() {
+                return false;
+            }
+        }
+
+        self.nfa.is_match(&states)
+    }
+
+    /// Calls `f` on all existing paths that match the pattern.
+    ///
+    /// The filesystem is walked starting at the pattern root, descending only
+    /// into directories that can contain matching paths. Components that can be
+    /// matched only by a few literal names are checked directly, without
+    /// listing the parent directory.
+    ///
+    /// Errors encountered during the walk are logged and skipped. Errors
+    /// returned by `f` stop the walk and are propagated to the caller.
+    pub fn find<F, E>(&self, opts: FindOpts, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(crate::fs::Entry) -> Result<(), E>,
+    {
+        // Denied paths are treated as if they did not exist (in the same way
+        // as they are skipped when listing directories).
+        if crate::fs::is_denied(&self.root) {
+            return Ok(());
+        }
+
+        let metadata = match std::fs::symlink_metadata(&self.root) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    warn!("failed to stat '{}': {}", self.root.display(), error);
+                }
+                return Ok(());
+            }
+        };
+
+        // The root is a path given explicitly, so we always resolve symlinks
+        // that it points to.
+        let target_metadata = match std::fs::metadata(&self.root) {
+            Ok(metadata) => metadata,
+            Err(_) => metadata.clone(),
+        };
+
+        let mut walk = Walk {
+            nfa: &self.nfa,
+            opts,
+            #[cfg(target_family = "unix")]
+            dev: std::os::unix::fs::MetadataExt::dev(&target_metadata),
+            scratch: Scratch::default(),
+            f: &mut f,
+        };
+
+        let entry = crate::fs::Entry {
+            path: self.root.clone(),
+            metadata,
+        };
+        walk.visit(entry, self.nfa.start(), target_metadata.is_dir())
+    }
+}
+
+/// State of an ongoing search for paths matching a pattern.
+struct Walk<'p, 'f, F> {
+    /// Automaton of the pattern.
+    nfa: &'p Nfa,
+    /// Options that configure the search.
+    opts: FindOpts,
+    /// Device of the pattern root.
+    #[cfg(target_family = "unix")]
+    dev: u64,
+    /// Buffers reused across automaton transitions.
+    scratch: Scratch,
+    /// Callback to call on every matching path.
+    f: &'f mut F,
+}
+
+impl<'p, 'f, F, E> Walk<'p, 'f, F>
+where
+    F: FnMut(crate::fs::Entry) -> Result<(), E>,
+{
+    /// Reports the entry if it matches and visits its matching children.
+    ///
+    /// Note that patterns can match paths of bounded depth only, so there is
+    /// no risk of infinite loops even if we follow symlinks.
+    fn visit(&mut self, entry: crate::fs::Entry, states: States, descend: bool) -> Result<(), E> {
+        let can_continue = descend && self.nfa.can_continue(&states);
+
+        let path = if can_continue {
+            Some(entry.path.clone())
+        } else {
+            None
+        };
+
+        if self.nfa.is_match(&states) {
+            (self.f)(entry)?;
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        match self.nfa.literal_names(&states, &mut self.scratch) {
+            Some(names) => {
+                for name in names {
+                    let child_states = self.nfa.feed(&states, &name, &mut self.scratch);
+                    if child_states.is_empty() {
+                        continue;
+                    }
+
+                    let child_path = path.join(&name);
+                    if crate::fs::is_denied(&child_path) {
+                        continue;
+                    }
+
+                    let metadata = match std::fs::symlink_metadata(&child_path) {
+                        Ok(metadata) => metadata,
+                        // Most of the literal names are just guesses, so it
+                        // is expected that many of them do not exist.
+                        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                            continue;
+                        }
+                        Err(error) => {
+                            warn!("failed to stat '{}': {}", child_path.display(), error);
+                            continue;
+                        }
+                    };
+
+                    let child = crate::fs::Entry {
+                        path: child_path,
+                        metadata,
+                    };
+
+                    // Unlike the root, literal names are subject to the same
+                    // symlink and device rules as listed entries.
+                    let descend = self.should_descend(&child);
+                    self.visit(child, child_states, descend)?;
+                }
+            }
+            None => {
+                let children = match crate::fs::list_dir(&path) {
+                    Ok(children) => children,
+                    Err(error) => {
+                        warn!("failed to read '{}': {}", path.display(), error);
+                        return Ok(());
+                    }
+                };
+
+                for child in children {
+                    let name = match child.path.file_name() {
+                        Some(name) => name.to_string_lossy(),
+                        None => continue,
+                    };
+
+                    let child_states = self.nfa.feed(&states, &name, &mut self.scratch);
+                    if child_states.is_empty() {
+                        continue;
+                    }
+
+                    let descend = self.should_descend(&child);
+                    self.visit(child, child_states, descend)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether the walk should descend into the given child entry.
+    fn should_descend(&self, entry: &crate::fs::Entry) -> bool {
+        let target_metadata;
+        let metadata = if self.opts.follow_symlinks && entry.metadata.is_symlink() {
+            // Symlinks can be dangling, in which case there is simply nothing
+            // to descend into.
+            target_metadata = match std::fs::metadata(&entry.path) {
+                Ok(metadata) => metadata,
+                Err(_) => return false,
+            };
+            &target_metadata
+        } else {
+            &entry.metadata
+        };
+
+        if !metadata.is_dir() {
+            return false;
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            let dev = std::os::unix::fs::MetadataExt::dev(metadata);
+            if self.opts.one_device && self.dev != dev {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Checks whether the given character has a special meaning in patterns.
+fn is_special(char: char) -> bool {
+    matches!(char, '*' | '?' | '[' | '{')
+}
+
+/// A node of a parsed pattern.
+#[derive(Debug)]
+enum Node {
+    /// A literal character (path separators are normalized to [`SEP`]).
+    Char(char),
+    /// Any single character (`?`).
+    Any,
+    /// Any sequence of characters (`*`).
+    Star,
+    /// Any character from the set (`[...]`).
+    Class(Class),
+    /// Any of the alternatives (`{...,...}`).
+    Group(Vec<Vec<Node>>),
+    /// Up to the given number of directory levels (`**N`).
+    Recursive(u32),
+}
+
+/// A set of characters.
+#[derive(Clone, Debug)]
+struct Class {
+    /// Whether the set consists of characters not in the ranges.
+    negated: bool,
+    /// Inclusive ranges of characters in the set.
+    ranges: Vec<(char, char)>,
+}
+
+impl Class {
+
+    /// Checks whether the given character belongs to the set.
+    fn contains(&self, char: char) -> bool {
+        let in_ranges = self.ranges.iter()
+            .any(|(min, max)| *min <= char && char <= *max);
+
+        in_ranges != self.negated
+    }
+}
+
+/// A parser of the pattern syntax.
+struct Parser<'c> {
+    /// Characters of the pattern.
+    chars: &'c [char],
+    /// Position of the next character to parse.
+    pos: usize,
+}
+
+impl<'c> Parser<'c> {
+
+    /// Parses a sequence of nodes until the end of the current group.
+    ///
+    /// `at_start` tells whether the sequence starts at a component boundary
+    /// (which is the only place where recursive components can begin).
+    fn seq(&mut self, in_group: bool, mut at_start: bool) -> Result<Vec<Node>, ParseError> {
+        let mut nodes = Vec::new();
+
+        while let Some(&char) = self.chars.get(self.pos) {
+            if in_group && (char == ',' || char == '}') {
+                break;
+            }
+
+            if at_start {
+                if let Some(depth) = self.recursive(in_group)? {
+                    nodes.push(Node::Recursive(depth));
+                    at_start = false;
+                    continue;
                 }
-                res = format!("{}[{}]", res, stuff);
             }
+
+            self.pos += 1;
+
+            let node = match char {
+                _ if std::path::is_separator(char) => Node::Char(SEP),
+                '*' => Node::Star,
+                '?' => Node::Any,
+                // Like in shells, unterminated classes and groups are treated
+                // as literals.
+                '[' => match self.class() {
+                    Some(class) => Node::Class(class),
+                    None => Node::Char('['),
+                },
+                '{' => match self.group(at_start)? {
+                    Some(alternatives) => Node::Group(alternatives),
+                    None => Node::Char('{'),
+                },
+                _ => Node::Char(char),
+            };
+
+            at_start = matches!(node, Node::Char(SEP));
+            nodes.push(node);
+        }
+
+        Ok(nodes)
+    }
+
+    /// Parses a recursive component (if there is one at the current position).
+    fn recursive(&mut self, in_group: bool) -> Result<Option<u32>, ParseError> {
+        let rest = &self.chars[self.pos..];
+        if !rest.starts_with(&['*', '*']) {
+            return Ok(None);
+        }
+
+        let digits = rest[2..].iter()
+            .take_while(|char| char.is_ascii_digit())
+            .collect::<String>();
+
+        match rest.get(2 + digits.len()) {
+            None => (),
+            Some(&char) if std::path::is_separator(char) => (),
+            Some(',' | '}') if in_group => (),
+            // Stars followed by anything else are ordinary wildcards.
+            Some(_) => return Ok(None),
+        }
+
+        let depth = if digits.is_empty() {
+            DEFAULT_RECURSION_DEPTH
         } else {
-            res = res + &regex::escape(&c.to_string());
+            match digits.parse::<u32>() {
+                Ok(depth) if 0 < depth && depth <= MAX_RECURSION_DEPTH => depth,
+                _ => return Err(ParseError::InvalidRecursionDepth(digits)),
+            }
+        };
+
+        self.pos += 2 + digits.len();
+        Ok(Some(depth))
+    }
+
+    /// Parses a character class (after the opening bracket).
+    fn class(&mut self) -> Option<Class> {
+        let mut pos = self.pos;
+
+        let negated = self.chars.get(pos) == Some(&'!');
+        if negated {
+            pos += 1;
+        }
+
+        // A closing bracket right at the beginning is a member of the set.
+        let start = pos;
+        if self.chars.get(pos) == Some(&']') {
+            pos += 1;
+        }
+        while self.chars.get(pos)? != &']' {
+            pos += 1;
+        }
+
+        let members = &self.chars[start..pos];
+        self.pos = pos + 1;
+
+        let mut ranges = Vec::new();
+        let mut idx = 0;
+        while idx < members.len() {
+            match members.get(idx..idx + 3) {
+                Some(&[min, '-', max]) => {
+                    ranges.push((min, max));
+                    idx += 3;
+                }
+                _ => {
+                    ranges.push((members[idx], members[idx]));
+                    idx += 1;
+                }
+            }
+        }
+
+        Some(Class {
+            negated,
+            ranges,
+        })
+    }
+
+    /// Parses a group of alternatives (after the opening brace).
+    fn group(&mut self, at_start: bool) -> Result<Option<Vec<Vec<Node>>>, ParseError> {
+        let start = self.pos;
+
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(self.seq(true, at_start)?);
+
+            match self.chars.get(self.pos) {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {
+                    self.pos = start;
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Empty braces are not a group.
+        if alternatives.len() == 1 && alternatives[0].is_empty() {
+            self.pos = start;
+            return Ok(None);
         }
+
+        Ok(Some(alternatives))
     }
+}
 
-    res = res.replace(r"\\", r"\");
+/// A state of the pattern automaton.
+#[derive(Debug)]
+enum State {
+    /// Accepts the input.
+    Match,
+    /// Consumes the given character and moves to the next state.
+    Char(char, usize),
+    /// Consumes any character other than a separator and moves to the next
+    /// state.
+    Any(usize),
+    /// Consumes a character (other than a separator) from the set and moves to
+    /// the next state.
+    Class(Class, usize),
+    /// Moves to all the given states without consuming anything.
+    Split(Vec<usize>),
+}
 
-    // Resulting regex is supposed to perform full matches on the text.
-    res = format!("^{}$", res);
+/// A set of automaton states (sorted indices, closed under splits).
+type States = Vec<usize>;
 
-    Regex::new(&res)
+/// Buffers reused across automaton transitions to avoid allocating on every
+/// consumed character.
+#[derive(Debug, Default)]
+struct Scratch {
+    /// Marks of states already reached by the ongoing closure computation.
+    ///
+    /// All marks are cleared once the computation finishes.
+    visited: Vec<bool>,
+    /// States yet to be explored by the ongoing closure computation.
+    pending: Vec<usize>,
+    /// Spare set of states used when feeding names to the automaton.
+    states: States,
+}
+
+/// A non-deterministic automaton matching paths character by character.
+#[derive(Debug)]
+struct Nfa {
+    /// All states of the automaton.
+    states: Vec<State>,
+    /// Index of the initial state.
+    start: usize,
+}
+
+impl Nfa {
+
+    /// Compiles the given sequence of pattern nodes into an automaton.
+    fn compile(nodes: &[Node]) -> Nfa {
+        let mut nfa = Nfa {
+            states: vec![State::Match],
+            start: 0,
+        };
+        nfa.start = nfa.compile_seq(nodes, 0);
+
+        nfa
+    }
+
+    /// Compiles the given nodes so that they continue at the `next` state.
+    ///
+    /// Returns the index of the state at which the compiled nodes start.
+    fn compile_seq(&mut self, nodes: &[Node], mut next: usize) -> usize {
+        for node in nodes.iter().rev() {
+            next = self.compile_node(node, next);
+        }
+
+        next
+    }
+
+    /// Compiles the given node so that it continues at the `next` state.
+    ///
+    /// Returns the index of the state at which the compiled node starts.
+    fn compile_node(&mut self, node: &Node, next: usize) -> usize {
+        use Node::*;
+
+        match node {
+            Char(char) => self.push(State::Char(*char, next)),
+            Any => self.push(State::Any(next)),
+            Class(class) => self.push(State::Class(class.clone(), next)),
+            Star => self.compile_star(next),
+            Group(alternatives) => {
+                let starts = alternatives.iter()
+                    .map(|alternative| self.compile_seq(alternative, next))
+                    .collect();
+
+                self.push(State::Split(starts))
+            }
+            Recursive(depth) => {
+                // Recursive components match a sequence of 1 to `depth` names
+                // separated by separators, so we unroll it from the end.
+                let mut start = self.compile_name(next);
+                for _ in 1..*depth {
+                    let sep = self.push(State::Char(SEP, start));
+                    let split = self.push(State::Split(vec![next, sep]));
+                    start = self.compile_name(split);
+                }
+
+                start
+            }
+        }
+    }
+
+    /// Compiles a sequence of any characters (other than a separator).
+    fn compile_star(&mut self, next: usize) -> usize {
+        let split = self.push(State::Split(Vec::new()));
+        let any = self.push(State::Any(split));
+        self.states[split] = State::Split(vec![any, next]);
+
+        split
+    }
+
+    /// Compiles a non-empty sequence of any characters (other than a separator).
+    fn compile_name(&mut self, next: usize) -> usize {
+        let split = self.compile_star(next);
+        self.push(State::Any(split))
+    }
+
+    /// Adds a new state to the automaton and returns its index.
+    fn push(&mut self, state: State) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// Returns the set of initial states.
+    fn start(&self) -> States {
+        let mut scratch = Scratch::default();
+        scratch.pending.push(self.start);
+
+        let mut states = States::new();
+        self.closure(&mut scratch, &mut states);
+
+        states
+    }
+
+    /// Computes the set of states reachable from the pending ones without
+    /// consuming any input and stores it in `out`.
+    fn closure(&self, scratch: &mut Scratch, out: &mut States) {
+        scratch.visited.resize(self.states.len(), false);
+        out.clear();
+
+        while let Some(state) = scratch.pending.pop() {
+            if std::mem::replace(&mut scratch.visited[state], true) {
+                continue;
+            }
+            out.push(state);
+            if let State::Split(nexts) = &self.states[state] {
+                scratch.pending.extend(nexts);
+            }
+        }
+
+        out.sort_unstable();
+
+        // We clear only the marks that we have set, so that each step costs
+        // time proportional to the number of reached states only.
+        for state in out.iter() {
+            scratch.visited[*state] = false;
+        }
+    }
+
+    /// Computes the set of states after consuming the given character and
+    /// stores it in `out`.
+    fn step(&self, states: &States, char: char, scratch: &mut Scratch, out: &mut States) {
+        let nexts = states.iter().filter_map(|state| match &self.states[*state] {
+            State::Char(expected, next) if *expected == char => Some(*next),
+            State::Any(next) if char != SEP => Some(*next),
+            State::Class(class, next) if char != SEP && class.contains(char) => Some(*next),
+            _ => None,
+        });
+        scratch.pending.extend(nexts);
+
+        self.closure(scratch, out)
+    }
+
+    /// Returns the set of states after consuming a separator and the given
+    /// component name.
+    fn feed(&self, states: &States, name: &str, scratch: &mut Scratch) -> States {
+        let mut current = States::new();
+        self.step(states, SEP, scratch, &mut current);
+
+        let mut next = std::mem::take(&mut scratch.states);
+        for char in name.chars() {
+            if current.is_empty() {
+                break;
+            }
+            self.step(&current, char, scratch, &mut next);
+            std::mem::swap(&mut current, &mut next);
+        }
+        scratch.states = next;
+
+        current
+    }
+
+    /// Checks whether the given set of states accepts the input.
+    fn is_match(&self, states: &States) -> bool {
+        states.iter().any(|state| matches!(self.states[*state], State::Match))
+    }
+
+    /// Checks whether the given set of states can consume another component.
+    fn can_continue(&self, states: &States) -> bool {
+        states.iter().any(|state| matches!(self.states[*state], State::Char(SEP, _)))
+    }
+
+    /// Returns all names of the next component that the given set of states
+    /// can consume if there are only a few literal ones.
+    fn literal_names(&self, states: &States, scratch: &mut Scratch) -> Option<Vec<String>> {
+        let mut names = Vec::new();
+
+        let mut nexts = States::new();
+        self.step(states, SEP, scratch, &mut nexts);
+
+        let mut pending = nexts.into_iter()
+            .map(|state| (state, String::new()))
+            .collect::<Vec<_>>();
+
+        // Cycles in the automaton always go through wildcard states, so the
+        // exploration terminates. The number of explored paths can still grow
+        // quickly with groups though, so we limit it as well.
+        let mut budget = MAX_LITERAL_NAMES * 32;
+
+        while let Some((state, name)) = pending.pop() {
+            budget = budget.checked_sub(1)?;
+
+            match &self.states[state] {
+                State::Match | State::Char(SEP, _) => {
+                    if !name.is_empty() && !names.contains(&name) {
+                        names.push(name);
+                    }
+                    if names.len() > MAX_LITERAL_NAMES {
+                        return None;
+                    }
+                }
+                State::Char(char, next) => {
+                    let mut name = name;
+                    name.push(*char);
+                    pending.push((*next, name));
+                }
+                State::Split(nexts) => {
+                    pending.extend(nexts.iter().map(|next| (*next, name.clone())));
+                }
+                State::Any(_) | State::Class(..) => return None,
+            }
+        }
+
+        Some(names)
+    }
+}
+
+/// The error type for cases when parsing a glob pattern fails.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The pattern is not an absolute path.
+    RelativePath,
+    /// Depth of a recursive component is zero or too big.
+    InvalidRecursionDepth(String),
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ParseError::*;
+
+        match *self {
+            RelativePath => {
+                write!(fmt, "relative path")
+            }
+            InvalidRecursionDepth(ref depth) => {
+                write!(fmt, "invalid recursion depth: {}", depth)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
 }
 
 #[cfg(test)]
 mod tests {
+
     use super::*;
 
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(Path::new(path))
+    }
+
+    fn find(pattern: &Path, opts: FindOpts) -> Vec<PathBuf> {
+        let pattern = Pattern::parse(pattern.to_str().unwrap()).unwrap();
+
+        let mut paths = Vec::new();
+        pattern.find(opts, |entry| {
+            paths.push(entry.path);
+            Ok::<(), ()>(())
+        }).unwrap();
+        paths.sort();
+
+        paths
+    }
+
+    #[test]
+    fn parse_relative() {
+        assert!(Pattern::parse("foo/*").is_err());
+    }
+
+    #[test]
+    fn parse_invalid_recursion_depth() {
+        assert!(Pattern::parse("/foo/**0").is_err());
+        assert!(Pattern::parse("/foo/**99999999999").is_err());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn parse_root() {
+        assert_eq!(Pattern::parse("/foo/bar").unwrap().root(), Path::new("/foo/bar"));
+        assert_eq!(Pattern::parse("/foo/bar/*").unwrap().root(), Path::new("/foo/bar"));
+        assert_eq!(Pattern::parse("/foo/b*r/baz").unwrap().root(), Path::new("/foo"));
+        assert_eq!(Pattern::parse("/foo/{bar,baz}").unwrap().root(), Path::new("/foo"));
+        assert_eq!(Pattern::parse("/*").unwrap().root(), Path::new("/"));
+    }
+
+    #[cfg(target_family = "unix")]
     #[test]
-    fn test_glob_to_regex() {
-        assert_eq!(glob_to_regex("*").unwrap().as_str(), "^.*$");
-        assert_eq!(glob_to_regex("?").unwrap().as_str(), "^.$");
-        assert_eq!(glob_to_regex("a?b*").unwrap().as_str(), "^a.b.*$");
-        assert_eq!(glob_to_regex("[abc]").unwrap().as_str(), "^[abc]$");
-        assert_eq!(glob_to_regex("[]]").unwrap().as_str(), "^[]]$");
-        assert_eq!(glob_to_regex("[!x]").unwrap().as_str(), "^[^x]$");
-        assert_eq!(glob_to_regex("[^x]").unwrap().as_str(), r"^[\^x]$");
-        assert_eq!(glob_to_regex("[x").unwrap().as_str(), r"^\[x$");
-        assert_eq!(glob_to_regex("[a]]").unwrap().as_str(), r"^[a]\]$");
-        assert_eq!(glob_to_regex(r"[\\]\\").unwrap().as_str(), r"^[\\]\\$");
-        assert_eq!(glob_to_regex("ąźć").unwrap().as_str(), "^ąźć$");
+    fn matches_wildcards() {
+        assert!(is_match("/foo/*", "/foo/bar"));
+        assert!(is_match("/foo/*", "/foo/.bar"));
+        assert!(!is_match("/foo/*", "/foo/bar/baz"));
+        assert!(is_match("/foo/b?r", "/foo/bar"));
+        assert!(!is_match("/foo/b?r", "/foo/br"));
+        assert!(is_match("/foo/a?b*", "/foo/axbyyy"));
+        assert!(is_match("/foo/ąźć", "/foo/ąźć"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn matches_classes() {
+        assert!(is_match("/[abc]", "/b"));
+        assert!(!is_match("/[abc]", "/d"));
+        assert!(is_match("/[a-z]", "/q"));
+        assert!(!is_match("/[a-z]", "/Q"));
+        assert!(is_match("/[]]", "/]"));
+        assert!(is_match("/[!x]", "/y"));
+        assert!(!is_match("/[!x]", "/x"));
+        assert!(is_match("/[^x]", "/^"));
+        assert!(is_match("/[a]]", "/a]"));
+        assert!(is_match("/[x", "/[x"));
+        assert!(!is_match("/foo[!x]bar", "/foo/bar"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn matches_groups() {
+        assert!(is_match("/some/path", "/some/path"));
+        assert!(is_match("/some/{1,2,3}", "/some/2"));
+        assert!(!is_match("/some/{1,2,3}", "/some/4"));
+        assert!(is_match("/some/{1,2}/{3,4}", "/some/2/3"));
+        assert!(is_match("/some/{1,}a/{3,}a", "/some/1a/a"));
+        assert!(is_match("/some/{1,}a/{3,}a", "/some/a/3a"));
+        assert!(is_match("/some/{123}", "/some/123"));
+        assert!(is_match("/some/{a,b/c}/d", "/some/b/c/d"));
+        assert!(is_match("/some/{a,{b,c}*}", "/some/cxx"));
+        assert!(is_match("/some/{a", "/some/{a"));
+        assert!(is_match("/some/{}", "/some/{}"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn matches_recursive() {
+        assert!(!is_match("/foo/**/bar", "/foo/bar"));
+        assert!(is_match("/foo/**/bar", "/foo/x/bar"));
+        assert!(is_match("/foo/**/bar", "/foo/x/y/z/bar"));
+        assert!(!is_match("/foo/**/bar", "/foo/x/y/z/w/bar"));
+        assert!(is_match("/foo/**1/bar", "/foo/x/bar"));
+        assert!(!is_match("/foo/**1/bar", "/foo/x/y/bar"));
+        assert!(is_match("/foo/**", "/foo/x/y"));
+        assert!(is_match("/foo/**/bar/**2", "/foo/x/bar/y/z"));
+        assert!(is_match("/foo/**x", "/foo/ax"));
+        assert!(!is_match("/foo/**x", "/foo/a/x"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn literal_names() {
+        let pattern = Pattern::parse("/foo/{bar,baz}/quux").unwrap();
+        let mut scratch = Scratch::default();
+        let states = pattern.nfa.start();
+
+        let mut names = pattern.nfa.literal_names(&states, &mut scratch).unwrap();
+        names.sort();
+        assert_eq!(names, vec! {
+            String::from("bar"),
+            String::from("baz"),
+        });
+
+        let states = pattern.nfa.feed(&states, "bar", &mut scratch);
+        assert_eq!(pattern.nfa.literal_names(&states, &mut scratch), Some(vec! {
+            String::from("quux"),
+        }));
+
+        let pattern = Pattern::parse("/foo/{bar,b*}").unwrap();
+        let states = pattern.nfa.start();
+        assert_eq!(pattern.nfa.literal_names(&states, &mut scratch), None);
+    }
+
+    #[test]
+    fn find_non_existent_root() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let pattern = tempdir.path().join("foo").join("*");
+
+        assert!(find(&pattern, FindOpts::default()).is_empty());
+    }
+
+    #[test]
+    fn find_literal() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"").unwrap();
+
+        let pattern = tempdir.path().join("foo");
+        assert_eq!(find(&pattern, FindOpts::default()), vec![pattern]);
+    }
+
+    #[test]
+    fn find_groups() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("foo")).unwrap();
+        std::fs::create_dir(tempdir.path().join("bar")).unwrap();
+        std::fs::write(tempdir.path().join("foo").join("a"), b"").unwrap();
+        std::fs::write(tempdir.path().join("foo").join("b"), b"").unwrap();
+        std::fs::write(tempdir.path().join("bar").join("a"), b"").unwrap();
+
+        let pattern = tempdir.path().join("{foo,bar,baz}").join("{a,b}");
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("bar").join("a"),
+            tempdir.path().join("foo").join("a"),
+            tempdir.path().join("foo").join("b"),
+        });
+    }
+
+    #[test]
+    fn find_recursive() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tempdir.path().join("a").join("b").join("c")).unwrap();
+        std::fs::write(tempdir.path().join("a").join("foo.txt"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("b").join("foo.txt"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("b").join("foo.bin"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("b").join("c").join("foo.txt"), b"").unwrap();
+
+        let pattern = tempdir.path().join("**2").join("*.txt");
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("a").join("b").join("foo.txt"),
+            tempdir.path().join("a").join("foo.txt"),
+        });
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn find_follow_symlinks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("dir")).unwrap();
+        std::fs::write(tempdir.path().join("dir").join("foo"), b"").unwrap();
+        std::os::unix::fs::symlink(tempdir.path().join("dir"), tempdir.path().join("link")).unwrap();
+
+        let pattern = tempdir.path().join("*").join("foo");
+
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("dir").join("foo"),
+        });
+
+        let opts = FindOpts {
+            follow_symlinks: true,
+            ..FindOpts::default()
+        };
+        assert_eq!(find(&pattern, opts), vec! {
+            tempdir.path().join("dir").join("foo"),
+            tempdir.path().join("link").join("foo"),
+        });
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn find_literal_follow_symlinks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("dir")).unwrap();
+        std::fs::write(tempdir.path().join("dir").join("foo"), b"").unwrap();
+        std::os::unix::fs::symlink(tempdir.path().join("dir"), tempdir.path().join("link")).unwrap();
+
+        let pattern = tempdir.path().join("{dir,link}").join("foo");
+
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("dir").join("foo"),
+        });
+
+        let opts = FindOpts {
+            follow_symlinks: true,
+            ..FindOpts::default()
+        };
+        assert_eq!(find(&pattern, opts), vec! {
+            tempdir.path().join("dir").join("foo"),
+            tempdir.path().join("link").join("foo"),
+        });
+    }
+
+    #[test]
+    fn find_denied() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("foo")).unwrap();
+        std::fs::create_dir(tempdir.path().join("bar")).unwrap();
+        std::fs::write(tempdir.path().join("foo").join("a"), b"").unwrap();
+        std::fs::write(tempdir.path().join("bar").join("a"), b"").unwrap();
+
+        crate::fs::deny(tempdir.path().join("bar"));
+
+        let pattern = tempdir.path().join("*").join("a");
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("foo").join("a"),
+        });
+
+        let pattern = tempdir.path().join("{foo,bar}").join("a");
+        assert_eq!(find(&pattern, FindOpts::default()), vec! {
+            tempdir.path().join("foo").join("a"),
+        });
+
+        let pattern = tempdir.path().join("bar").join("*");
+        assert!(find(&pattern, FindOpts::default()).is_empty());
     }
 }
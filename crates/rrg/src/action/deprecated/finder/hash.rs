@@ -1,3 +1,4 @@
+#[cfg(feature = "action-finder")]
 use super::request::HashActionOptions;
 use crate::fs::Entry;
 use digest::Digest as _;
@@ -49,6 +50,7 @@ impl std::io::Write for Hasher {
 }
 
 /// Performs `hash` action on the file in `entry` and returns the result to be reported in case of success.
+#[cfg(feature = "action-finder")]
 pub fn hash(entry: &Entry, config: &HashActionOptions) -> Option<FileHash> {
     use rrg_proto::flows::FileFinderHashActionOptions_OversizedFilePolicy::*;
     match config.oversized_file_policy {
@@ -60,11 +62,16 @@ pub fn hash(entry: &Entry, config: &HashActionOptions) -> Option<FileHash> {
         HASH_TRUNCATED => {}
     };
 
+    hash_file(entry, config.max_size)
+}
+
+/// Hashes at most `max_size` first bytes of the file in `entry`.
+pub fn hash_file(entry: &Entry, max_size: u64) -> Option<FileHash> {
     let file = ack! {
         File::open(&entry.path),
         error: "failed to open file: {}", entry.path.display()
     }?;
-    let mut file = file.take(config.max_size);
+    let mut file = file.take(max_size);
 
     let mut hasher = Hasher::new();
     let read_bytes = ack! {
@@ -72,7 +79,7 @@ pub fn hash(entry: &Entry, config: &HashActionOptions) -> Option<FileHash> {
         error: "failed to copy data from: {}", entry.path.display()
     }?;
 
-    let expected_bytes = min(entry.metadata.len(), config.max_size);
+    let expected_bytes = min(entry.metadata.len(), max_size);
     if read_bytes != expected_bytes {
         warn!(
             "failed to read all data from: {}, {} bytes were read, but {} were expected",
@@ -104,6 +111,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "action-finder")]
     fn test_hash_values() {
         let tempdir = tempfile::tempdir().unwrap();
         let path = tempdir.path().join("f");
@@ -152,6 +160,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "action-finder")]
     fn test_trim_file_over_max_size() {
         let tempdir = tempfile::tempdir().unwrap();
         let path = tempdir.path().join("f");
@@ -174,6 +183,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "action-finder")]
     fn test_skip_file_over_max_size() {
         let tempdir = tempfile::tempdir().unwrap();
         let path = tempdir.path().join("f");
@@ -192,4 +202,44 @@ mod tests {
         )
         .is_none());
     }
+
+    #[test]
+    fn test_hash_file_values() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "some_test_data").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        let result = hash_file(&entry, 14).unwrap();
+
+        assert_eq!(
+            result.sha256,
+            vec![
+                0xd7, 0x6d, 0x85, 0xad, 0xca, 0x8a, 0xfa, 0xd2, 0x05, 0xed,
+                0xeb, 0xc1, 0x1f, 0x9b, 0x50, 0x86, 0xbc, 0xa7, 0x5a, 0xcb,
+                0x51, 0x2a, 0x74, 0x8b, 0xc7, 0x96, 0x60, 0xe1, 0x34, 0x6a,
+                0xf5, 0x46
+            ]
+            .to_vec()
+        );
+        assert_eq!(result.num_bytes, 14);
+    }
+
+    #[test]
+    fn test_hash_file_truncated() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("f");
+        std::fs::write(&path, "some_test_data").unwrap();
+        let entry = Entry {
+            metadata: path.metadata().unwrap(),
+            path,
+        };
+
+        let result = hash_file(&entry, 10).unwrap();
+
+        assert_eq!(result.num_bytes, 10);
+    }
 }
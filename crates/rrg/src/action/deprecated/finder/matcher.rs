@@ -0,0 +1,502 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+//! Streaming search for multiple byte patterns.
+//!
+//! All patterns are looked for at once using the [Aho-Corasick] algorithm, so
+//! the cost of a scan is roughly independent of the number of patterns. Data
+//! is read in chunks into a buffer of bounded size that retains just enough of
+//! the previous chunk to find hits crossing chunk boundaries.
+//!
+//! [Aho-Corasick]: https://en.wikipedia.org/wiki/Aho%E2%80%93Corasick_algorithm
+
+use std::io::Read;
+use std::ops::ControlFlow;
+
+/// Number of bytes read from the scanned data at once.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of context bytes to report before or after a hit.
+pub const MAX_CONTEXT_LEN: usize = 1024 * 1024;
+
+/// A byte pattern to look for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BytePattern {
+    /// An exact sequence of bytes.
+    Literal(Vec<u8>),
+    /// A sequence of bytes where only bits in the mask have to match.
+    Masked(Vec<MaskedByte>),
+}
+
+/// A single byte of a masked pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaskedByte {
+    /// Expected value of the byte (bits outside of the mask are zero).
+    pub value: u8,
+    /// Bits of the byte that have to match.
+    pub mask: u8,
+}
+
+impl BytePattern {
+
+    /// Parses a pattern from its hexadecimal representation.
+    ///
+    /// Every byte is represented by two hexadecimal digits, any of which can
+    /// be replaced with a `?` wildcard that matches any nibble. Whitespace is
+    /// ignored, so e.g. `4D 5A ?? ?? 5?` is a valid pattern. The pattern needs
+    /// to have at least one byte with no wildcards.
+    pub fn parse_hex(hex: &str) -> Result<BytePattern, ParseHexError> {
+        let nibbles = hex.chars()
+            .filter(|char| !char.is_whitespace())
+            .map(|char| match char {
+                '?' => Ok((0, 0x0)),
+                _ => match char.to_digit(16) {
+                    Some(value) => Ok((value as u8, 0xF)),
+                    None => Err(ParseHexError::InvalidChar(char)),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if nibbles.is_empty() {
+            return Err(ParseHexError::Empty);
+        }
+        if nibbles.len() % 2 != 0 {
+            return Err(ParseHexError::OddLength);
+        }
+
+        let bytes = nibbles.chunks(2)
+            .map(|nibbles| MaskedByte {
+                value: nibbles[0].0 << 4 | nibbles[1].0,
+                mask: nibbles[0].1 << 4 | nibbles[1].1,
+            })
+            .collect::<Vec<_>>();
+
+        if bytes.iter().all(|byte| byte.mask == 0xFF) {
+            return Ok(BytePattern::Literal(bytes.iter().map(|byte| byte.value).collect()));
+        }
+        if !bytes.iter().any(|byte| byte.mask == 0xFF) {
+            return Err(ParseHexError::NoFixedBytes);
+        }
+
+        Ok(BytePattern::Masked(bytes))
+    }
+
+    /// Returns the number of bytes the pattern matches.
+    fn len(&self) -> usize {
+        match self {
+            BytePattern::Literal(bytes) => bytes.len(),
+            BytePattern::Masked(bytes) => bytes.len(),
+        }
+    }
+
+    /// Checks whether the pattern matches the given bytes (of equal length).
+    fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            BytePattern::Literal(bytes) => bytes == data,
+            BytePattern::Masked(bytes) => {
+                bytes.iter().zip(data)
+                    .all(|(byte, actual)| actual & byte.mask == byte.value)
+            }
+        }
+    }
+
+    /// Returns the longest part of the pattern with no wildcards (along with
+    /// its offset within the pattern).
+    fn anchor(&self) -> (usize, Vec<u8>) {
+        match self {
+            BytePattern::Literal(bytes) => (0, bytes.clone()),
+            BytePattern::Masked(bytes) => {
+                let mut best = 0..0;
+                let mut start = 0;
+                for (idx, byte) in bytes.iter().enumerate() {
+                    if byte.mask != 0xFF {
+                        start = idx + 1;
+                    } else if idx + 1 - start > best.len() {
+                        best = start..idx + 1;
+                    }
+                }
+
+                let anchor = bytes[best.clone()].iter()
+                    .map(|byte| byte.value)
+                    .collect();
+                (best.start, anchor)
+            }
+        }
+    }
+}
+
+/// A compiled set of byte patterns to look for.
+#[derive(Debug)]
+pub struct Matcher {
+    /// Automaton finding anchors of all the patterns.
+    automaton: aho_corasick::AhoCorasick,
+    /// Patterns in the order they were given.
+    patterns: Vec<BytePattern>,
+    /// Offsets of anchors within the patterns (in the order of patterns).
+    anchor_offsets: Vec<usize>,
+    /// Length of the longest pattern.
+    max_len: usize,
+}
+
+/// Options that configure a scan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanOpts {
+    /// Number of bytes before each hit to include in the reported fragment
+    /// (at most [`MAX_CONTEXT_LEN`]).
+    pub bytes_before: usize,
+    /// Number of bytes after each hit to include in the reported fragment (at
+    /// most [`MAX_CONTEXT_LEN`]).
+    pub bytes_after: usize,
+    /// Offset of the scanned data to add to offsets of reported fragments.
+    pub offset: u64,
+}
+
+/// A hit of a pattern in the scanned data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Index of the pattern that matched.
+    pub pattern: usize,
+    /// Offset of the reported fragment (including the context bytes).
+    pub offset: u64,
+    /// Contents of the reported fragment (including the context bytes).
+    pub data: Vec<u8>,
+}
+
+impl Matcher {
+
+    /// Compiles the given patterns.
+    pub fn new(patterns: Vec<BytePattern>) -> Result<Matcher, BuildError> {
+        if patterns.is_empty() {
+            return Err(BuildError::NoPatterns);
+        }
+        if patterns.iter().any(|pattern| pattern.len() == 0) {
+            return Err(BuildError::EmptyPattern);
+        }
+
+        let (anchor_offsets, anchors): (Vec<_>, Vec<_>) = patterns.iter()
+            .map(BytePattern::anchor)
+            .unzip();
+
+        let automaton = aho_corasick::AhoCorasick::builder()
+            .match_kind(aho_corasick::MatchKind::Standard)
+            .build(anchors)
+            .map_err(BuildError::Automaton)?;
+
+        let max_len = patterns.iter()
+            .map(BytePattern::len)
+            .max()
+            .unwrap_or(0);
+
+        Ok(Matcher {
+            automaton,
+            patterns,
+            anchor_offsets,
+            max_len,
+        })
+    }
+
+    /// Scans the given data and calls `f` on every hit of any of the patterns.
+    ///
+    /// Hits are reported in the order of their end offsets and overlapping hits
+    /// are all reported. The scan stops early if `f` returns a break.
+    pub fn scan<R, F>(&self, reader: R, opts: ScanOpts, f: F) -> std::io::Result<()>
+    where
+        R: Read,
+        F: FnMut(Hit) -> ControlFlow<()>,
+    {
+        self.scan_chunked(reader, opts, CHUNK_SIZE, f)
+    }
+
+    /// Scans the given data reading it in chunks of the given size.
+    fn scan_chunked<R, F>(
+        &self,
+        mut reader: R,
+        opts: ScanOpts,
+        chunk_size: usize,
+        mut f: F,
+    ) -> std::io::Result<()>
+    where
+        R: Read,
+        F: FnMut(Hit) -> ControlFlow<()>,
+    {
+        let bytes_before = std::cmp::min(opts.bytes_before, MAX_CONTEXT_LEN);
+        let bytes_after = std::cmp::min(opts.bytes_after, MAX_CONTEXT_LEN);
+
+        // To find hits crossing chunk boundaries, along with their context, we
+        // need to retain this many bytes of the previous chunk.
+        let retained_len = self.max_len - 1 + bytes_before + bytes_after;
+
+        let mut buf = Vec::with_capacity(retained_len + chunk_size);
+        // Offset of the first byte of the buffer within the scanned data.
+        let mut buf_offset = 0u64;
+        // End offset (within the scanned data) of the previous buffer. All hits
+        // with context fitting before it have already been reported.
+        let mut prev_end = 0u64;
+
+        loop {
+            let eof = fill(&mut reader, &mut buf, chunk_size)?;
+            let buf_end = buf_offset + buf.len() as u64;
+
+            for candidate in self.automaton.find_overlapping_iter(&buf[..]) {
+                let idx = candidate.pattern().as_usize();
+                let pattern = &self.patterns[idx];
+
+                let start = match candidate.start().checked_sub(self.anchor_offsets[idx]) {
+                    Some(start) => start,
+                    None => continue,
+                };
+                let end = start + pattern.len();
+                if end > buf.len() || !pattern.matches(&buf[start..end]) {
+                    continue;
+                }
+
+                // Hits whose context fits in the previous buffer have already
+                // been reported and hits whose context does not fit into the
+                // current buffer will be reported with the next one.
+                let context_end = buf_offset + (end + bytes_after) as u64;
+                if context_end <= prev_end || (context_end > buf_end && !eof) {
+                    continue;
+                }
+
+                let context = start.saturating_sub(bytes_before)..std::cmp::min(end + bytes_after, buf.len());
+                let hit = Hit {
+                    pattern: idx,
+                    offset: opts.offset + buf_offset + context.start as u64,
+                    data: buf[context].to_vec(),
+                };
+
+                if f(hit).is_break() {
+                    return Ok(());
+                }
+            }
+
+            if eof {
+                return Ok(());
+            }
+
+            prev_end = buf_end;
+
+            let consumed_len = buf.len().saturating_sub(retained_len);
+            buf.drain(..consumed_len);
+            buf_offset += consumed_len as u64;
+        }
+    }
+}
+
+/// Reads up to `len` more bytes to the buffer.
+///
+/// Returns `true` if the end of the data has been reached.
+fn fill<R: Read>(reader: &mut R, buf: &mut Vec<u8>, len: usize) -> std::io::Result<bool> {
+    let mut chunk = reader.take(len as u64);
+    let read_len = chunk.read_to_end(buf)?;
+
+    Ok(read_len < len)
+}
+
+/// The error type for cases when parsing a hexadecimal pattern fails.
+#[derive(Debug, PartialEq)]
+pub enum ParseHexError {
+    /// The pattern has no bytes.
+    Empty,
+    /// The pattern has a character that is not a digit or a wildcard.
+    InvalidChar(char),
+    /// The pattern has an odd number of digits.
+    OddLength,
+    /// The pattern consists of wildcards only.
+    NoFixedBytes,
+}
+
+impl std::fmt::Display for ParseHexError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ParseHexError::*;
+
+        match *self {
+            Empty => write!(fmt, "empty pattern"),
+            InvalidChar(char) => write!(fmt, "invalid character: {:?}", char),
+            OddLength => write!(fmt, "odd number of digits"),
+            NoFixedBytes => write!(fmt, "no bytes without wildcards"),
+        }
+    }
+}
+
+impl std::error::Error for ParseHexError {
+}
+
+/// The error type for cases when compiling patterns fails.
+#[derive(Debug)]
+pub enum BuildError {
+    /// There are no patterns to compile.
+    NoPatterns,
+    /// One of the patterns is empty.
+    EmptyPattern,
+    /// The patterns are too big to build the automaton.
+    Automaton(aho_corasick::BuildError),
+}
+
+impl std::fmt::Display for BuildError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use BuildError::*;
+
+        match *self {
+            NoPatterns => write!(fmt, "no patterns"),
+            EmptyPattern => write!(fmt, "empty pattern"),
+            Automaton(ref error) => write!(fmt, "failed to build automaton: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use BuildError::*;
+
+        match *self {
+            NoPatterns | EmptyPattern => None,
+            Automaton(ref error) => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn literal(bytes: &[u8]) -> BytePattern {
+        BytePattern::Literal(bytes.to_vec())
+    }
+
+    fn hex(hex: &str) -> BytePattern {
+        BytePattern::parse_hex(hex).unwrap()
+    }
+
+    fn hits_chunked(
+        patterns: Vec<BytePattern>,
+        data: &[u8],
+        opts: ScanOpts,
+        chunk_size: usize,
+    ) -> Vec<Hit> {
+        let matcher = Matcher::new(patterns).unwrap();
+
+        let mut hits = Vec::new();
+        matcher.scan_chunked(data, opts, chunk_size, |hit| {
+            hits.push(hit);
+            ControlFlow::Continue(())
+        }).unwrap();
+
+        hits
+    }
+
+    fn hits(patterns: Vec<BytePattern>, data: &[u8], opts: ScanOpts) -> Vec<Hit> {
+        hits_chunked(patterns, data, opts, CHUNK_SIZE)
+    }
+
+    #[test]
+    fn parse_hex_literal() {
+        assert_eq!(hex("4D5A"), literal(b"MZ"));
+        assert_eq!(hex(" 4d 5a\n"), literal(b"MZ"));
+    }
+
+    #[test]
+    fn parse_hex_wildcards() {
+        assert_eq!(hex("4D ?? 5?"), BytePattern::Masked(vec! {
+            MaskedByte { value: 0x4D, mask: 0xFF },
+            MaskedByte { value: 0x00, mask: 0x00 },
+            MaskedByte { value: 0x50, mask: 0xF0 },
+        }));
+    }
+
+    #[test]
+    fn parse_hex_errors() {
+        assert_eq!(BytePattern::parse_hex(""), Err(ParseHexError::Empty));
+        assert_eq!(BytePattern::parse_hex("4D5"), Err(ParseHexError::OddLength));
+        assert_eq!(BytePattern::parse_hex("4G"), Err(ParseHexError::InvalidChar('G')));
+        assert_eq!(BytePattern::parse_hex("?? 4?"), Err(ParseHexError::NoFixedBytes));
+    }
+
+    #[test]
+    fn new_errors() {
+        assert!(Matcher::new(vec![]).is_err());
+        assert!(Matcher::new(vec![literal(b"foo"), literal(b"")]).is_err());
+    }
+
+    #[test]
+    fn scan_literal() {
+        assert_eq!(hits(vec![literal(b"foo")], b"xfooxxfoo", ScanOpts::default()), vec! {
+            Hit { pattern: 0, offset: 1, data: b"foo".to_vec() },
+            Hit { pattern: 0, offset: 6, data: b"foo".to_vec() },
+        });
+    }
+
+    #[test]
+    fn scan_multiple_patterns() {
+        let patterns = vec![literal(b"foo"), literal(b"oob"), literal(b"quux")];
+        assert_eq!(hits(patterns, b"foobar", ScanOpts::default()), vec! {
+            Hit { pattern: 0, offset: 0, data: b"foo".to_vec() },
+            Hit { pattern: 1, offset: 1, data: b"oob".to_vec() },
+        });
+    }
+
+    #[test]
+    fn scan_hex() {
+        let patterns = vec![hex("66 ?? 6F"), hex("6? 61")];
+        assert_eq!(hits(patterns, b"fxobar", ScanOpts::default()), vec! {
+            Hit { pattern: 0, offset: 0, data: b"fxo".to_vec() },
+            Hit { pattern: 1, offset: 3, data: b"ba".to_vec() },
+        });
+    }
+
+    #[test]
+    fn scan_context() {
+        let opts = ScanOpts {
+            bytes_before: 2,
+            bytes_after: 3,
+            offset: 100,
+        };
+
+        assert_eq!(hits(vec![literal(b"foo")], b"xfooxxxxfoox", opts), vec! {
+            Hit { pattern: 0, offset: 100, data: b"xfooxxx".to_vec() },
+            Hit { pattern: 0, offset: 106, data: b"xxfoox".to_vec() },
+        });
+    }
+
+    #[test]
+    fn scan_stop() {
+        let matcher = Matcher::new(vec![literal(b"foo")]).unwrap();
+
+        let mut hits = Vec::new();
+        matcher.scan(&b"foofoofoo"[..], ScanOpts::default(), |hit| {
+            hits.push(hit);
+            ControlFlow::Break(())
+        }).unwrap();
+
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn scan_chunk_boundaries() {
+        let data = b"foo_barfoo__bar_foobarfo_o_foobar____baroof";
+        let patterns = || vec![literal(b"foo"), literal(b"foobar"), hex("62 ?? 72"), hex("6F")];
+        let opts = ScanOpts {
+            bytes_before: 2,
+            bytes_after: 1,
+            offset: 0,
+        };
+
+        let mut expected = hits(patterns(), data, opts);
+        expected.sort_by_key(|hit| (hit.offset, hit.pattern));
+
+        // Splitting the data into chunks should not affect the results at all,
+        // no matter how small the chunks are.
+        for chunk_size in 1..data.len() {
+            let mut hits = hits_chunked(patterns(), data, opts, chunk_size);
+            hits.sort_by_key(|hit| (hit.offset, hit.pattern));
+
+            assert_eq!(hits, expected, "chunk size: {}", chunk_size);
+        }
+    }
+}
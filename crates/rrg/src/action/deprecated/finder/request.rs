@@ -11,6 +11,8 @@ use log::info;
 use protobuf::ProtobufEnum;
 use std::fmt::Write;
 
+pub use super::condition::{Condition, ContentsMatchCondition, MatchMode};
+
 #[derive(Debug)]
 pub struct Request {
     /// A list of paths to glob that supports `**` path recursion and
@@ -72,48 +74,6 @@ pub struct DownloadActionOptions {
     pub chunk_size: u64,
 }
 
-#[derive(Debug)]
-pub enum Condition {
-    ModificationTime {
-        min: Option<std::time::SystemTime>,
-        max: Option<std::time::SystemTime>,
-    },
-    AccessTime {
-        min: Option<std::time::SystemTime>,
-        max: Option<std::time::SystemTime>,
-    },
-    InodeChangeTime {
-        min: Option<std::time::SystemTime>,
-        max: Option<std::time::SystemTime>,
-    },
-    Size {
-        min: Option<u64>,
-        max: Option<u64>,
-    },
-    ExtFlags {
-        linux_bits_set: Option<u32>,
-        linux_bits_unset: Option<u32>,
-        osx_bits_set: Option<u32>,
-        osx_bits_unset: Option<u32>,
-    },
-}
-
-#[derive(Debug, PartialEq)]
-pub enum MatchMode {
-    AllHits,
-    FirstHit,
-}
-
-#[derive(Debug)]
-pub struct ContentsMatchCondition {
-    pub regex: regex::bytes::Regex,
-    pub mode: MatchMode,
-    pub bytes_before: u64,
-    pub bytes_after: u64,
-    pub start_offset: u64,
-    pub length: u64,
-}
-
 impl From<rrg_proto::flows::FileFinderStatActionOptions> for StatActionOptions {
     fn from(proto: rrg_proto::flows::FileFinderStatActionOptions) -> StatActionOptions {
         StatActionOptions {
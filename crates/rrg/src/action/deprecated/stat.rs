@@ -115,6 +115,9 @@ pub fn handle<S>(session: &mut S, request: Request) -> session::Result<()>
 where
     S: Session,
 {
+    crate::fs::check_access(&request.path)
+        .map_err(Error::Metadata)?;
+
     let metadata = if request.follow_symlink {
         std::fs::metadata(&request.path)
     } else {
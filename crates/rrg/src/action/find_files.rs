@@ -0,0 +1,922 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+use std::path::PathBuf;
+
+use log::warn;
+use sha2::Digest as _;
+
+use crate::action::deprecated::finder::{chunks, condition, glob, hash};
+
+/// Default size of chunks that downloaded files are split into.
+const DEFAULT_CHUNK_SIZE: u64 = 512 * 1024;
+
+/// Arguments of the `find_files` action.
+pub struct Args {
+    /// Patterns specifying files to find.
+    paths: Vec<glob::Pattern>,
+    /// Options of the search for files matching the patterns.
+    find_opts: glob::FindOpts,
+    /// Whether to report files that are not regular files.
+    process_non_regular_files: bool,
+    /// Expression over conditions on file metadata that found files have to
+    /// meet.
+    condition: condition::Expr,
+    /// Conditions on file contents that found files have to meet.
+    contents_match_conditions: Vec<condition::ContentsMatchCondition>,
+    /// Conditions on file contents given as sets of byte patterns.
+    contents_patterns_conditions: Vec<condition::ContentsPatternsCondition>,
+    /// Whether to compute digests of found files.
+    hash: bool,
+    /// Whether to download contents of found files.
+    download: bool,
+    /// Maximum number of bytes of each file to hash or download (if any).
+    max_file_size: Option<u64>,
+    /// Policy for files bigger than the maximum size.
+    oversized_file_policy: OversizedFilePolicy,
+    /// Size of chunks that downloaded files are split into.
+    chunk_size: u64,
+}
+
+/// Policy for files that are bigger than the maximum size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OversizedFilePolicy {
+    /// Oversized files are reported without digests or contents.
+    Skip,
+    /// Only the first part of oversized files is hashed or downloaded.
+    Truncate,
+}
+
+/// Result of the `find_files` action.
+struct Item {
+    /// Path to the found file.
+    path: PathBuf,
+    /// Metadata of the found file.
+    metadata: std::fs::Metadata,
+    /// Fragments of the file contents that matched the contents conditions.
+    matches: Vec<condition::Match>,
+    /// Digests of the file (if requested).
+    hash: Option<hash::FileHash>,
+    /// SHA-256 digests of all the blobs with file contents sent to the blob
+    /// sink (if requested).
+    blob_sha256s: Vec<[u8; 32]>,
+}
+
+/// A chunk of the file contents sent to the blob sink.
+struct Blob {
+    /// Raw bytes of the blob.
+    data: Vec<u8>,
+}
+
+/// Handles invocations of the `find_files` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    // Patterns can overlap (e.g. because of recursive components), so we keep
+    // track of already reported files.
+    let mut reported = std::collections::HashSet::new();
+
+    for pattern in &args.paths {
+        pattern.find(args.find_opts, |entry| {
+            if !reported.insert(entry.path.clone()) {
+                return Ok(());
+            }
+
+            process(session, &args, entry)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Checks the conditions on the given file and reports it if it meets them.
+fn process<S>(
+    session: &mut S,
+    args: &Args,
+    entry: crate::fs::Entry,
+) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    if !args.process_non_regular_files && !entry.metadata.is_file() {
+        return Ok(());
+    }
+
+    if !condition::check_expr(&args.condition, &entry) {
+        return Ok(());
+    }
+
+    let mut matches = condition::find_matches(&args.contents_match_conditions, &entry);
+    if !args.contents_match_conditions.is_empty() && matches.is_empty() {
+        return Ok(());
+    }
+
+    let mut pattern_matches = condition::find_pattern_matches(&args.contents_patterns_conditions, &entry);
+    if !args.contents_patterns_conditions.is_empty() && pattern_matches.is_empty() {
+        return Ok(());
+    }
+    matches.append(&mut pattern_matches);
+
+    let max_size = match args.max_file_size {
+        Some(max_file_size) if entry.metadata.len() > max_file_size => {
+            match args.oversized_file_policy {
+                OversizedFilePolicy::Skip => None,
+                OversizedFilePolicy::Truncate => Some(max_file_size),
+            }
+        }
+        _ => Some(entry.metadata.len()),
+    };
+
+    let hash = match max_size {
+        Some(max_size) if args.hash => hash::hash_file(&entry, max_size),
+        _ => None,
+    };
+
+    let mut blob_sha256s = Vec::new();
+    if let Some(max_size) = max_size.filter(|_| args.download) {
+        let chunks = chunks::get_file_chunks(&entry.path, &chunks::GetFileChunksConfig {
+            start_offset: 0,
+            max_read_bytes: max_size,
+            bytes_per_chunk: args.chunk_size,
+            overlap_bytes: 0,
+        });
+
+        // Errors are logged by the function, the file is skipped in such case.
+        let chunks = match chunks {
+            Some(chunks) => chunks,
+            None => return Ok(()),
+        };
+
+        for chunk in chunks {
+            let data = match chunk {
+                Ok(data) => data,
+                Err(error) => {
+                    warn!("failed to read '{}': {}", entry.path.display(), error);
+                    return Ok(());
+                }
+            };
+
+            blob_sha256s.push(sha2::Sha256::digest(&data).into());
+            session.send(crate::Sink::Blob, Blob { data })?;
+        }
+    }
+
+    session.reply(Item {
+        path: entry.path,
+        metadata: entry.metadata,
+        matches,
+        hash,
+        blob_sha256s,
+    })?;
+
+    Ok(())
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::find_files::Args;
+
+    fn from_proto(mut proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+        use crate::action::deprecated::finder::matcher;
+
+        let mut paths = Vec::new();
+        for path in proto.get_paths() {
+            let pattern = glob::Pattern::parse(path)
+                .map_err(|error| ParseArgsError::invalid_field("paths", error))?;
+            paths.push(pattern);
+        }
+
+        let mut conditions = Vec::new();
+        for condition in proto.take_conditions() {
+            let condition = parse_condition(condition)
+                .map_err(|error| ParseArgsError::invalid_field("conditions", error))?;
+            conditions.push(condition);
+        }
+
+        let mut contents_match_conditions = Vec::new();
+        for condition in proto.take_contents_match_conditions() {
+            let regex = regex::bytes::Regex::new(condition.get_regex())
+                .map_err(|error| ParseArgsError::invalid_field("contents_match_conditions", error))?;
+
+            let max_context_len = matcher::MAX_CONTEXT_LEN as u64;
+            if condition.get_bytes_before() > max_context_len || condition.get_bytes_after() > max_context_len {
+                let error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "context too long");
+                return Err(ParseArgsError::invalid_field("contents_match_conditions", error));
+            }
+
+            let mode = match condition.get_mode() {
+                rrg_proto::v2::find_files::MatchMode::ALL_HITS => condition::MatchMode::AllHits,
+                rrg_proto::v2::find_files::MatchMode::FIRST_HIT => condition::MatchMode::FirstHit,
+            };
+
+            let length = match condition.get_length() {
+                0 => u64::MAX,
+                length => length,
+            };
+
+            contents_match_conditions.push(condition::ContentsMatchCondition {
+                regex,
+                mode,
+                bytes_before: condition.get_bytes_before(),
+                bytes_after: condition.get_bytes_after(),
+                start_offset: condition.get_start_offset(),
+                length,
+            });
+        }
+
+        let mut contents_patterns_conditions = Vec::new();
+        for condition in proto.take_contents_patterns_conditions() {
+            let condition = parse_contents_patterns_condition(condition)
+                .map_err(|error| ParseArgsError::invalid_field("contents_patterns_conditions", error))?;
+            contents_patterns_conditions.push(condition);
+        }
+
+        let max_file_size = match proto.get_max_file_size() {
+            0 => None,
+            max_file_size => Some(max_file_size),
+        };
+
+        let oversized_file_policy = match proto.get_oversized_file_policy() {
+            rrg_proto::v2::find_files::OversizedFilePolicy::SKIP => OversizedFilePolicy::Skip,
+            rrg_proto::v2::find_files::OversizedFilePolicy::TRUNCATE => OversizedFilePolicy::Truncate,
+        };
+
+        let chunk_size = match proto.get_chunk_size() {
+            0 => DEFAULT_CHUNK_SIZE,
+            chunk_size => chunk_size,
+        };
+
+        Ok(Args {
+            paths,
+            find_opts: glob::FindOpts {
+                follow_symlinks: proto.get_follow_symlinks(),
+                one_device: proto.get_one_device(),
+            },
+            process_non_regular_files: proto.get_process_non_regular_files(),
+            condition: condition::Expr::And(conditions),
+            contents_match_conditions,
+            contents_patterns_conditions,
+            hash: proto.get_hash(),
+            download: proto.get_download(),
+            max_file_size,
+            oversized_file_policy,
+            chunk_size,
+        })
+    }
+}
+
+/// Parses a metadata condition into an expression.
+fn parse_condition(
+    mut proto: rrg_proto::v2::find_files::Condition,
+) -> Result<condition::Expr, std::io::Error> {
+    use rrg_proto::v2::find_files::Condition_oneof_condition::*;
+
+    let condition = match proto.condition.take() {
+        Some(condition) => condition,
+        // Empty conditions are always met.
+        None => return Ok(condition::Expr::And(vec![])),
+    };
+
+    let parse_list = |mut list: rrg_proto::v2::find_files::ConditionList| {
+        list.take_conditions().into_iter()
+            .map(parse_condition)
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let condition = match condition {
+        modification_time(range) => {
+            let (min, max) = parse_time_range(&range)?;
+            condition::Condition::ModificationTime { min, max }
+        }
+        access_time(range) => {
+            let (min, max) = parse_time_range(&range)?;
+            condition::Condition::AccessTime { min, max }
+        }
+        inode_change_time(range) => {
+            let (min, max) = parse_time_range(&range)?;
+            condition::Condition::InodeChangeTime { min, max }
+        }
+        size(range) => {
+            let max = match range.get_max() {
+                0 => None,
+                max => Some(max),
+            };
+            condition::Condition::Size {
+                min: Some(range.get_min()),
+                max,
+            }
+        }
+        ext_flags(flags) => {
+            let bits = |bits| match bits {
+                0 => None,
+                bits => Some(bits),
+            };
+            condition::Condition::ExtFlags {
+                linux_bits_set: bits(flags.get_linux_bits_set()),
+                linux_bits_unset: bits(flags.get_linux_bits_unset()),
+                osx_bits_set: None,
+                osx_bits_unset: None,
+            }
+        }
+        owner_uid(uid) => condition::Condition::Owner {
+            uid: Some(uid),
+            gid: None,
+        },
+        owner_gid(gid) => condition::Condition::Owner {
+            uid: None,
+            gid: Some(gid),
+        },
+        mode(bits) => condition::Condition::Mode {
+            bits_set: bits.get_bits_set(),
+            bits_unset: bits.get_bits_unset(),
+        },
+        file_type(kind) => {
+            use rrg_proto::v2::fs::FileMetadata_Type::*;
+
+            condition::Condition::FileType {
+                file_type: match kind {
+                    FILE => condition::FileType::Regular,
+                    DIR => condition::FileType::Dir,
+                    SYMLINK => condition::FileType::Symlink,
+                    UNKNOWN => condition::FileType::Other,
+                },
+            }
+        }
+        inode(ino) => condition::Condition::Inode {
+            inode: ino,
+        },
+        path_regex(regex) => {
+            let regex = regex::Regex::new(&regex)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+            condition::Condition::PathRegex { regex }
+        }
+        ext_attr(mut attr) => {
+            let value = if attr.has_value() {
+                Some(attr.take_value())
+            } else {
+                None
+            };
+
+            condition::Condition::ExtAttr {
+                name: os_string_from_bytes(attr.take_name()),
+                value,
+            }
+        }
+        all_of(list) => return Ok(condition::Expr::And(parse_list(list)?)),
+        any_of(list) => return Ok(condition::Expr::Or(parse_list(list)?)),
+        negated(condition) => {
+            let expr = parse_condition(*condition)?;
+            return Ok(condition::Expr::Not(Box::new(expr)));
+        }
+    };
+
+    Ok(condition::Expr::Condition(condition))
+}
+
+/// Parses a contents patterns condition from its protobuf representation.
+///
+/// Patterns, context lengths and the scanned range are validated here, so
+/// that invalid requests are rejected before any file is visited.
+fn parse_contents_patterns_condition(
+    mut proto: rrg_proto::v2::find_files::ContentsPatternsCondition,
+) -> Result<condition::ContentsPatternsCondition, std::io::Error> {
+    use rrg_proto::v2::find_files::BytePattern_oneof_pattern::*;
+    use crate::action::deprecated::finder::matcher;
+
+    let mut patterns = Vec::new();
+    for pattern in proto.take_patterns() {
+        let pattern = match pattern.pattern {
+            Some(literal(bytes)) => matcher::BytePattern::Literal(bytes),
+            Some(hex(string)) => {
+                matcher::BytePattern::parse_hex(&string)
+                    .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?
+            }
+            None => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing pattern"));
+            }
+        };
+        patterns.push(pattern);
+    }
+
+    let matcher = matcher::Matcher::new(patterns)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+
+    let max_context_len = matcher::MAX_CONTEXT_LEN as u64;
+    if proto.get_bytes_before() > max_context_len || proto.get_bytes_after() > max_context_len {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "context too long"));
+    }
+
+    let mode = match proto.get_mode() {
+        rrg_proto::v2::find_files::MatchMode::ALL_HITS => condition::MatchMode::AllHits,
+        rrg_proto::v2::find_files::MatchMode::FIRST_HIT => condition::MatchMode::FirstHit,
+    };
+
+    let length = match proto.get_length() {
+        0 => u64::MAX,
+        length => length,
+    };
+
+    Ok(condition::ContentsPatternsCondition {
+        matcher,
+        mode,
+        bytes_before: proto.get_bytes_before(),
+        bytes_after: proto.get_bytes_after(),
+        start_offset: proto.get_start_offset(),
+        length,
+    })
+}
+
+/// Converts raw bytes (of e.g. an extended attribute name) to an OS string.
+fn os_string_from_bytes(bytes: Vec<u8>) -> std::ffi::OsString {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStringExt as _;
+        std::ffi::OsString::from_vec(bytes)
+    }
+
+    // Extended attributes are not supported on Windows anyway, so a lossy
+    // conversion is good enough.
+    #[cfg(target_family = "windows")]
+    {
+        std::ffi::OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Parses bounds of a time range (each bound is optional).
+fn parse_time_range(
+    range: &rrg_proto::v2::find_files::TimeRange,
+) -> Result<(Option<std::time::SystemTime>, Option<std::time::SystemTime>), std::io::Error> {
+    let min = if range.has_min() {
+        Some(parse_timestamp(range.get_min())?)
+    } else {
+        None
+    };
+    let max = if range.has_max() {
+        Some(parse_timestamp(range.get_max())?)
+    } else {
+        None
+    };
+
+    Ok((min, max))
+}
+
+/// Converts a Protocol Buffers `Timestamp` message to system time.
+fn parse_timestamp(
+    proto: &protobuf::well_known_types::Timestamp,
+) -> Result<std::time::SystemTime, std::io::Error> {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let nanos = u32::try_from(proto.get_nanos()).ok()
+        .filter(|nanos| *nanos < 1_000_000_000);
+
+    // Timestamps before the epoch are represented by negative seconds (but the
+    // nanosecond part is still counted forward).
+    let secs = proto.get_seconds();
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs.unsigned_abs()))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    };
+
+    match (time, nanos) {
+        (Some(time), Some(nanos)) => time.checked_add(Duration::from_nanos(nanos.into())),
+        _ => None,
+    }.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid timestamp")
+    })
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::find_files::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::find_files::Result::default();
+        proto.set_path(self.path.into());
+        proto.set_metadata(self.metadata.into());
+
+        for data_match in self.matches {
+            let mut match_proto = rrg_proto::v2::find_files::Match::default();
+            match_proto.set_offset(data_match.offset);
+            match_proto.set_data(data_match.data);
+            if let Some(pattern) = data_match.pattern {
+                match_proto.set_pattern_index(pattern as u32);
+            }
+
+            proto.mut_matches().push(match_proto);
+        }
+
+        if let Some(hash) = self.hash {
+            proto.set_md5(hash.md5);
+            proto.set_sha1(hash.sha1);
+            proto.set_sha256(hash.sha256);
+        }
+
+        for blob_sha256 in self.blob_sha256s {
+            proto.mut_blob_sha256s().push(blob_sha256.to_vec());
+        }
+
+        proto
+    }
+}
+
+impl crate::response::Item for Blob {
+
+    type Proto = rrg_proto::v2::blob::Blob;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::blob::Blob::default();
+        proto.set_data(self.data);
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(paths: &[&std::path::Path]) -> Args {
+        Args {
+            paths: paths.iter()
+                .map(|path| glob::Pattern::parse(path.to_str().unwrap()).unwrap())
+                .collect(),
+            find_opts: glob::FindOpts::default(),
+            process_non_regular_files: false,
+            condition: condition::Expr::And(vec![]),
+            contents_match_conditions: vec![],
+            contents_patterns_conditions: vec![],
+            hash: false,
+            download: false,
+            max_file_size: None,
+            oversized_file_policy: OversizedFilePolicy::Skip,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    fn paths(session: &crate::session::FakeSession) -> Vec<PathBuf> {
+        let mut paths = session.replies::<Item>()
+            .map(|item| item.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths
+    }
+
+    #[test]
+    fn handle_literal() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"").unwrap();
+
+        let args = args(&[
+            &tempdir.path().join("foo"),
+            &tempdir.path().join("bar"),
+        ]);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec![tempdir.path().join("foo")]);
+    }
+
+    #[test]
+    fn handle_glob() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo.txt"), b"").unwrap();
+        std::fs::write(tempdir.path().join("bar.txt"), b"").unwrap();
+        std::fs::write(tempdir.path().join("baz.bin"), b"").unwrap();
+
+        let args = args(&[&tempdir.path().join("*.txt")]);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec! {
+            tempdir.path().join("bar.txt"),
+            tempdir.path().join("foo.txt"),
+        });
+    }
+
+    #[test]
+    fn handle_recursive() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tempdir.path().join("a").join("b").join("c")).unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("foo"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("b").join("foo"), b"").unwrap();
+        std::fs::write(tempdir.path().join("a").join("b").join("c").join("foo"), b"").unwrap();
+
+        let args = args(&[&tempdir.path().join("**2").join("foo")]);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec! {
+            tempdir.path().join("a").join("b").join("foo"),
+            tempdir.path().join("a").join("foo"),
+        });
+    }
+
+    #[test]
+    fn handle_group() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"").unwrap();
+        std::fs::write(tempdir.path().join("baz"), b"").unwrap();
+
+        let args = args(&[&tempdir.path().join("{foo,bar,quux}")]);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec! {
+            tempdir.path().join("bar"),
+            tempdir.path().join("foo"),
+        });
+    }
+
+    #[test]
+    fn handle_overlapping_queries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"").unwrap();
+
+        let args = args(&[
+            &tempdir.path().join("foo"),
+            &tempdir.path().join("f*"),
+        ]);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec![tempdir.path().join("foo")]);
+    }
+
+    #[test]
+    fn handle_non_regular_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("foo")).unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"").unwrap();
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args(&[&tempdir.path().join("*")])).is_ok());
+        assert_eq!(paths(&session), vec![tempdir.path().join("bar")]);
+
+        let mut args = args(&[&tempdir.path().join("*")]);
+        args.process_non_regular_files = true;
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+        assert_eq!(paths(&session), vec! {
+            tempdir.path().join("bar"),
+            tempdir.path().join("foo"),
+        });
+    }
+
+    #[test]
+    fn handle_size_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"0123456789").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"0123").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("*")]);
+        args.condition = condition::Expr::Condition(condition::Condition::Size {
+            min: Some(5),
+            max: None,
+        });
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec![tempdir.path().join("foo")]);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn handle_condition_expr() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"foo").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"bar").unwrap();
+        std::fs::write(tempdir.path().join("baz"), b"baz").unwrap();
+        std::fs::write(tempdir.path().join("quux"), b"quuxquux").unwrap();
+
+        let setuid = std::fs::Permissions::from_mode(0o4755);
+        std::fs::set_permissions(tempdir.path().join("bar"), setuid.clone()).unwrap();
+        std::fs::set_permissions(tempdir.path().join("quux"), setuid).unwrap();
+
+        // Files that are setuid or are named `baz` and are smaller than 5 bytes.
+        let mut mode = rrg_proto::v2::find_files::Condition::default();
+        mode.mut_mode().set_bits_set(0o4000);
+
+        let mut path_regex = rrg_proto::v2::find_files::Condition::default();
+        path_regex.set_path_regex(String::from("baz$"));
+
+        let mut any_of = rrg_proto::v2::find_files::Condition::default();
+        any_of.mut_any_of().mut_conditions().push(mode);
+        any_of.mut_any_of().mut_conditions().push(path_regex);
+
+        let mut size = rrg_proto::v2::find_files::Condition::default();
+        size.mut_size().set_min(5);
+
+        let mut negated = rrg_proto::v2::find_files::Condition::default();
+        negated.set_negated(size);
+
+        let mut proto = rrg_proto::v2::find_files::Args::default();
+        proto.mut_paths().push(tempdir.path().join("*").to_str().unwrap().to_string());
+        proto.mut_conditions().push(any_of);
+        proto.mut_conditions().push(negated);
+
+        let args = <Args as crate::request::Args>::from_proto(proto).unwrap();
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(paths(&session), vec! {
+            tempdir.path().join("bar"),
+            tempdir.path().join("baz"),
+        });
+    }
+
+    #[test]
+    fn handle_contents_match_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"xxxfooxxx").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"xxxbarxxx").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("*")]);
+        args.contents_match_conditions.push(condition::ContentsMatchCondition {
+            regex: regex::bytes::Regex::new("foo").unwrap(),
+            mode: condition::MatchMode::AllHits,
+            bytes_before: 1,
+            bytes_after: 1,
+            start_offset: 0,
+            length: u64::MAX,
+        });
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.path, tempdir.path().join("foo"));
+        assert_eq!(item.matches.len(), 1);
+        assert_eq!(item.matches[0].offset, 2);
+        assert_eq!(item.matches[0].data, b"xfoox");
+    }
+
+    #[test]
+    fn from_proto_contents_match_context_too_long() {
+        let mut condition = rrg_proto::v2::find_files::ContentsMatchCondition::default();
+        condition.set_regex(String::from("foo"));
+        condition.set_bytes_after(u64::MAX);
+
+        let mut proto = rrg_proto::v2::find_files::Args::default();
+        proto.mut_paths().push(String::from("/foo"));
+        proto.mut_contents_match_conditions().push(condition);
+
+        assert!(<Args as crate::request::Args>::from_proto(proto).is_err());
+    }
+
+    #[test]
+    fn handle_contents_patterns_condition() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"MZxxfooxxbar").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"MZxxbarxxbar").unwrap();
+        std::fs::write(tempdir.path().join("baz"), b"xxxxfooxxbar").unwrap();
+
+        let mut literal = rrg_proto::v2::find_files::BytePattern::default();
+        literal.set_literal(b"foo".to_vec());
+
+        let mut hex = rrg_proto::v2::find_files::BytePattern::default();
+        hex.set_hex(String::from("4D 5? ?? 78"));
+
+        let mut condition = rrg_proto::v2::find_files::ContentsPatternsCondition::default();
+        condition.mut_patterns().push(literal);
+        condition.mut_patterns().push(hex);
+        condition.set_bytes_before(1);
+
+        let mut proto = rrg_proto::v2::find_files::Args::default();
+        proto.mut_paths().push(tempdir.path().join("foo").to_str().unwrap().to_string());
+        proto.mut_contents_patterns_conditions().push(condition);
+
+        let args = <Args as crate::request::Args>::from_proto(proto).unwrap();
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.matches.len(), 2);
+        assert_eq!(item.matches[0].offset, 0);
+        assert_eq!(item.matches[0].data, b"MZxx");
+        assert_eq!(item.matches[0].pattern, Some(1));
+        assert_eq!(item.matches[1].offset, 3);
+        assert_eq!(item.matches[1].data, b"xfoo");
+        assert_eq!(item.matches[1].pattern, Some(0));
+    }
+
+    #[test]
+    fn handle_hash() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"hello\n").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("foo")]);
+        args.hash = true;
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let hash = session.reply::<Item>(0).hash.as_ref().unwrap();
+        assert_eq!(hash.sha256, sha2::Sha256::digest(b"hello\n").to_vec());
+        assert_eq!(hash.num_bytes, 6);
+    }
+
+    #[test]
+    fn handle_hash_oversized_skip() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"hello\n").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("foo")]);
+        args.hash = true;
+        args.max_file_size = Some(4);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+        assert!(session.reply::<Item>(0).hash.is_none());
+    }
+
+    #[test]
+    fn handle_download() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"0123456789").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("foo")]);
+        args.download = true;
+        args.chunk_size = 4;
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let blobs = session.parcels::<Blob>(crate::Sink::Blob)
+            .map(|blob| blob.data.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(blobs, vec! {
+            b"0123".to_vec(),
+            b"4567".to_vec(),
+            b"89".to_vec(),
+        });
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.blob_sha256s, blobs.iter()
+            .map(|blob| <[u8; 32]>::from(sha2::Sha256::digest(blob)))
+            .collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn handle_download_oversized_truncate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"0123456789").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("foo")]);
+        args.download = true;
+        args.max_file_size = Some(6);
+        args.oversized_file_policy = OversizedFilePolicy::Truncate;
+        args.chunk_size = 4;
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let blobs = session.parcels::<Blob>(crate::Sink::Blob)
+            .map(|blob| blob.data.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(blobs, vec! {
+            b"0123".to_vec(),
+            b"45".to_vec(),
+        });
+        assert_eq!(session.reply::<Item>(0).blob_sha256s.len(), 2);
+    }
+
+    #[test]
+    fn parse_timestamp_pre_epoch() {
+        let mut proto = protobuf::well_known_types::Timestamp::default();
+        proto.set_seconds(-2);
+        proto.set_nanos(500_000_000);
+
+        let time = parse_timestamp(&proto).unwrap();
+        assert_eq! {
+            std::time::UNIX_EPOCH.duration_since(time).unwrap(),
+            std::time::Duration::from_millis(1500),
+        };
+    }
+}
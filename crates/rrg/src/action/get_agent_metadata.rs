@@ -0,0 +1,77 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+/// A result of the the `get_agent_metadata` action.
+struct Item {
+    /// Metadata about the agent (name, version and build time).
+    metadata: crate::startup::Metadata,
+    /// Actions that the agent was built with.
+    supported_actions: Vec<crate::request::Action>,
+}
+
+impl Item {
+
+    /// Returns metadata of the agent that is currently running.
+    fn new() -> Item {
+        Item {
+            metadata: crate::startup::Metadata::from_cargo(),
+            supported_actions: crate::request::Action::ALL.to_vec(),
+        }
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::get_agent_metadata::Result;
+
+    fn into_proto(self) -> rrg_proto::v2::get_agent_metadata::Result {
+        let supported_actions = self.supported_actions.into_iter()
+            .map(rrg_proto::v2::rrg::Action::from)
+            .collect();
+
+        let mut proto = rrg_proto::v2::get_agent_metadata::Result::new();
+        proto.set_metadata(self.metadata.into());
+        proto.set_supported_actions(supported_actions);
+
+        proto
+    }
+}
+
+// Handles invocations of the `get_agent_metadata` action.
+pub fn handle<S>(session: &mut S, _: ()) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    session.reply(Item::new())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handle_metadata() {
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, ()).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.metadata.name, "rrg");
+        assert!(item.metadata.build_time.is_some());
+    }
+
+    #[test]
+    fn handle_supported_actions() {
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, ()).is_ok());
+
+        let item = session.reply::<Item>(0);
+        assert!(item.supported_actions.contains(&crate::request::Action::GetAgentMetadata));
+    }
+}
@@ -0,0 +1,356 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+use std::path::PathBuf;
+
+use digest::Digest as _;
+
+/// Arguments of the `get_file_hash` action.
+pub struct Args {
+    /// Path to the file to compute the hash of.
+    path: PathBuf,
+    /// Byte offset at which the hashing should start.
+    offset: u64,
+    /// Number of bytes to hash (or `None` if the file should be hashed until
+    /// its end).
+    length: Option<u64>,
+    /// Whether to compute the MD5 digest of the file.
+    md5: bool,
+    /// Whether to compute the SHA-1 digest of the file.
+    sha1: bool,
+    /// Whether to compute the SHA-256 digest of the file.
+    sha256: bool,
+}
+
+/// Result of the `get_file_hash` action.
+struct Item {
+    /// Canonical path to the file that was hashed.
+    path: PathBuf,
+    /// Byte offset at which the hashing started.
+    offset: u64,
+    /// Number of bytes that were hashed.
+    length: u64,
+    /// MD5 digest of the file (if requested).
+    md5: Option<Vec<u8>>,
+    /// SHA-1 digest of the file (if requested).
+    sha1: Option<Vec<u8>>,
+    /// SHA-256 digest of the file (if requested).
+    sha256: Option<Vec<u8>>,
+}
+
+/// Handles invocations of the `get_file_hash` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    use std::io::{Read as _, Seek as _};
+
+    if args.path.is_relative() {
+        use std::io::{Error, ErrorKind};
+
+        let error = Error::new(ErrorKind::InvalidInput, "relative path");
+        return Err(crate::session::Error::action(error));
+    }
+
+    crate::fs::check_access(&args.path)
+        .map_err(crate::session::Error::action)?;
+
+    let path = args.path.canonicalize()
+        .map_err(crate::session::Error::action)?;
+
+    let mut file = std::fs::File::open(&path)
+        .map_err(crate::session::Error::action)?;
+
+    file.seek(std::io::SeekFrom::Start(args.offset))
+        .map_err(crate::session::Error::action)?;
+
+    let mut file = file.take(args.length.unwrap_or(u64::MAX));
+
+    let mut hasher = Hasher {
+        md5: args.md5.then(md5::Md5::new),
+        sha1: args.sha1.then(sha1::Sha1::new),
+        sha256: args.sha256.then(sha2::Sha256::new),
+    };
+
+    let length = std::io::copy(&mut file, &mut hasher)
+        .map_err(crate::session::Error::action)?;
+
+    session.reply(Item {
+        path,
+        offset: args.offset,
+        length,
+        md5: hasher.md5.map(|md5| md5.finalize().to_vec()),
+        sha1: hasher.sha1.map(|sha1| sha1.finalize().to_vec()),
+        sha256: hasher.sha256.map(|sha256| sha256.finalize().to_vec()),
+    })?;
+
+    Ok(())
+}
+
+/// Computes digests of data written to it using only requested algorithms.
+struct Hasher {
+    /// Digest with MD5 hash (if requested).
+    md5: Option<md5::Md5>,
+    /// Digest with SHA-1 hash (if requested).
+    sha1: Option<sha1::Sha1>,
+    /// Digest with SHA-256 hash (if requested).
+    sha256: Option<sha2::Sha256>,
+}
+
+impl std::io::Write for Hasher {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(md5) = &mut self.md5 {
+            md5.update(buf);
+        }
+        if let Some(sha1) = &mut self.sha1 {
+            sha1.update(buf);
+        }
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(buf);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::get_file_hash::Args;
+
+    fn from_proto(mut proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+
+        let path = PathBuf::try_from(proto.take_path())
+            .map_err(|error| ParseArgsError::invalid_field("path", error))?;
+
+        let length = match proto.get_length() {
+            0 => None,
+            length => Some(length),
+        };
+
+        Ok(Args {
+            path,
+            offset: proto.get_offset(),
+            length,
+            md5: proto.get_md5(),
+            sha1: proto.get_sha1(),
+            sha256: proto.get_sha256(),
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::get_file_hash::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::get_file_hash::Result::default();
+        proto.set_path(self.path.into());
+        proto.set_offset(self.offset);
+        proto.set_length(self.length);
+
+        if let Some(md5) = self.md5 {
+            proto.set_md5(md5);
+        }
+        if let Some(sha1) = self.sha1 {
+            proto.set_sha1(sha1);
+        }
+        if let Some(sha256) = self.sha256 {
+            proto.set_sha256(sha256);
+        }
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handle_non_existent() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+            offset: 0,
+            length: None,
+            md5: true,
+            sha1: true,
+            sha256: true,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_relative() {
+        let args = Args {
+            path: PathBuf::from("foo/bar/baz"),
+            offset: 0,
+            length: None,
+            md5: true,
+            sha1: true,
+            sha256: true,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_denied() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::write(tempdir.path().join("foo"), b"hello\n")
+            .unwrap();
+
+        crate::fs::deny(tempdir.path().join("foo"));
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+            offset: 0,
+            length: None,
+            md5: true,
+            sha1: true,
+            sha256: true,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_all_digests() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+        let tempdir = tempdir.path().canonicalize()
+            .unwrap();
+
+        std::fs::write(tempdir.join("foo"), b"hello\n")
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.join("foo"),
+            offset: 0,
+            length: None,
+            md5: true,
+            sha1: true,
+            sha256: true,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.path, tempdir.join("foo"));
+        assert_eq!(item.offset, 0);
+        assert_eq!(item.length, 6);
+        assert_eq!(item.md5.as_deref(), Some(&[
+            0xb1, 0x94, 0x6a, 0xc9, 0x24, 0x92, 0xd2, 0x34,
+            0x7c, 0x62, 0x35, 0xb4, 0xd2, 0x61, 0x11, 0x84,
+        ][..]));
+        assert_eq!(item.sha1.as_deref(), Some(&[
+            0xf5, 0x72, 0xd3, 0x96, 0xfa, 0xe9, 0x20, 0x66, 0x28, 0x71,
+            0x4f, 0xb2, 0xce, 0x00, 0xf7, 0x2e, 0x94, 0xf2, 0x25, 0x8f,
+        ][..]));
+        assert_eq!(item.sha256.as_deref(), Some(&[
+            0x58, 0x91, 0xb5, 0xb5, 0x22, 0xd5, 0xdf, 0x08,
+            0x6d, 0x0f, 0xf0, 0xb1, 0x10, 0xfb, 0xd9, 0xd2,
+            0x1b, 0xb4, 0xfc, 0x71, 0x63, 0xaf, 0x34, 0xd0,
+            0x82, 0x86, 0xa2, 0xe8, 0x46, 0xf6, 0xbe, 0x03,
+        ][..]));
+    }
+
+    #[test]
+    fn handle_only_requested_digests() {
+        let tempfile = tempfile::NamedTempFile::new()
+            .unwrap();
+
+        let args = Args {
+            path: tempfile.path().to_path_buf(),
+            offset: 0,
+            length: None,
+            md5: false,
+            sha1: false,
+            sha256: true,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert!(item.md5.is_none());
+        assert!(item.sha1.is_none());
+        assert!(item.sha256.is_some());
+    }
+
+    #[test]
+    fn handle_offset_and_length() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::write(tempdir.path().join("foo"), b"foohello\nbar")
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+            offset: 3,
+            length: Some(6),
+            md5: true,
+            sha1: false,
+            sha256: false,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.offset, 3);
+        assert_eq!(item.length, 6);
+        assert_eq!(item.md5.as_deref(), Some(&[
+            0xb1, 0x94, 0x6a, 0xc9, 0x24, 0x92, 0xd2, 0x34,
+            0x7c, 0x62, 0x35, 0xb4, 0xd2, 0x61, 0x11, 0x84,
+        ][..]));
+    }
+
+    #[test]
+    fn handle_length_beyond_end() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::write(tempdir.path().join("foo"), b"foobar")
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+            offset: 3,
+            length: Some(1024),
+            md5: true,
+            sha1: false,
+            sha256: false,
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.length, 3);
+    }
+}
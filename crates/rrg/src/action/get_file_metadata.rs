@@ -43,6 +43,9 @@ where
         return Err(crate::session::Error::action(error));
     }
 
+    crate::fs::check_access(&args.path)
+        .map_err(crate::session::Error::action)?;
+
     let metadata = args.path.symlink_metadata()
         .map_err(crate::session::Error::action)?;
 
@@ -172,6 +175,24 @@ mod tests {
         assert!(handle(&mut session, args).is_err());
     }
 
+    #[test]
+    fn handle_denied() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::File::create(tempdir.path().join("foo"))
+            .unwrap();
+
+        crate::fs::deny(tempdir.path().join("foo"));
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
     #[test]
     fn handle_regular_file() {
         let tempdir = tempfile::tempdir()
@@ -0,0 +1,412 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+use std::path::PathBuf;
+
+use sha2::Digest as _;
+
+/// Arguments of the `get_filesystem_timeline` action.
+pub struct Args {
+    /// Root directory to get the timeline of.
+    root: PathBuf,
+    /// Options of the recursive traversal of the root directory.
+    walk_opts: crate::fs::WalkDirOpts,
+}
+
+/// Result of the `get_filesystem_timeline` action.
+struct Item {
+    /// SHA-256 digests of all the blobs sent to the blob sink.
+    blob_sha256s: Vec<[u8; 32]>,
+    /// Number of timeline entries in all the blobs.
+    entry_count: u64,
+    /// Total size of all the blobs (in bytes).
+    blob_size: u64,
+}
+
+/// A part of the timeline sent to the blob sink.
+struct Blob {
+    /// Raw bytes of the blob.
+    data: Vec<u8>,
+}
+
+/// An error type for failures that can occur during the timeline action.
+#[derive(Debug)]
+enum Error {
+    /// A failure occurred during an attempt to start the recursive walk.
+    WalkDir(std::io::Error),
+    /// A failure occurred during encoding of the timeline entries.
+    Encode(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Error::*;
+
+        match *self {
+            WalkDir(ref error) => {
+                write!(fmt, "failed to start the recursive walk: {}", error)
+            }
+            Encode(ref error) => {
+                write!(fmt, "failed to encode timeline entries: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            WalkDir(ref error) => Some(error),
+            Encode(ref error) => Some(error),
+        }
+    }
+}
+
+impl From<Error> for crate::session::Error {
+
+    fn from(error: Error) -> crate::session::Error {
+        crate::session::Error::action(error)
+    }
+}
+
+/// Handles invocations of the `get_filesystem_timeline` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    if args.root.is_relative() {
+        use std::io::{Error, ErrorKind};
+
+        let error = Error::new(ErrorKind::InvalidInput, "relative path");
+        return Err(crate::session::Error::action(error));
+    }
+
+    let mut entry_count = 0;
+    let entries = crate::fs::walk_dir_with_opts(&args.root, args.walk_opts)
+        .map_err(Error::WalkDir)?
+        .inspect(|_| entry_count += 1)
+        .map(rrg_proto::v2::get_filesystem_timeline::Entry::from);
+
+    let mut blob_sha256s = Vec::new();
+    let mut blob_size = 0;
+
+    for part in crate::gzchunked::encode(entries) {
+        let data = part.map_err(Error::Encode)?;
+
+        blob_sha256s.push(sha2::Sha256::digest(&data).into());
+        blob_size += data.len() as u64;
+
+        session.send(crate::Sink::Blob, Blob { data })?;
+    }
+
+    session.reply(Item {
+        blob_sha256s,
+        entry_count,
+        blob_size,
+    })?;
+
+    Ok(())
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::get_filesystem_timeline::Args;
+
+    fn from_proto(mut proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+
+        let root = PathBuf::try_from(proto.take_root())
+            .map_err(|error| ParseArgsError::invalid_field("root", error))?;
+
+        let max_depth = match proto.get_max_depth() {
+            0 => None,
+            max_depth => Some(max_depth),
+        };
+
+        Ok(Args {
+            root,
+            walk_opts: crate::fs::WalkDirOpts {
+                one_device: !proto.get_cross_devices(),
+                follow_symlinks: proto.get_follow_symlinks(),
+                max_depth,
+            },
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::get_filesystem_timeline::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::get_filesystem_timeline::Result::default();
+        for blob_sha256 in self.blob_sha256s {
+            proto.mut_blob_sha256s().push(blob_sha256.to_vec());
+        }
+        proto.set_entry_count(self.entry_count);
+        proto.set_blob_size(self.blob_size);
+
+        proto
+    }
+}
+
+impl crate::response::Item for Blob {
+
+    type Proto = rrg_proto::v2::blob::Blob;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::blob::Blob::default();
+        proto.set_data(self.data);
+
+        proto
+    }
+}
+
+impl From<crate::fs::Entry> for rrg_proto::v2::get_filesystem_timeline::Entry {
+
+    fn from(entry: crate::fs::Entry) -> rrg_proto::v2::get_filesystem_timeline::Entry {
+        let mut proto = rrg_proto::v2::get_filesystem_timeline::Entry::default();
+        proto.set_size(entry.metadata.len());
+
+        let nanos = |time: std::io::Result<std::time::SystemTime>| {
+            time.ok()
+                .and_then(|time| rrg_proto::nanos(time).ok())
+                .and_then(|nanos| i64::try_from(nanos).ok())
+        };
+
+        if let Some(atime_nanos) = nanos(entry.metadata.accessed()) {
+            proto.set_atime_nanos(atime_nanos);
+        }
+        if let Some(mtime_nanos) = nanos(entry.metadata.modified()) {
+            proto.set_mtime_nanos(mtime_nanos);
+        }
+        if let Some(btime_nanos) = nanos(entry.metadata.created()) {
+            proto.set_btime_nanos(btime_nanos);
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::MetadataExt as _;
+
+            proto.set_mode(entry.metadata.mode());
+            proto.set_dev(entry.metadata.dev());
+            proto.set_ino(entry.metadata.ino());
+            proto.set_uid(entry.metadata.uid());
+            proto.set_gid(entry.metadata.gid());
+
+            let ctime_nanos = entry.metadata.ctime()
+                .checked_mul(1_000_000_000)
+                .and_then(|nanos| nanos.checked_add(entry.metadata.ctime_nsec()));
+            if let Some(ctime_nanos) = ctime_nanos {
+                proto.set_ctime_nanos(ctime_nanos);
+            }
+        }
+
+        proto.set_path(entry.path.into());
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handle_non_existent_path() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        let args = Args {
+            root: tempdir.path().join("foo"),
+            walk_opts: crate::fs::WalkDirOpts::default(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_relative_path() {
+        let args = Args {
+            root: PathBuf::from("foo/bar"),
+            walk_opts: crate::fs::WalkDirOpts::default(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_empty_dir() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        let args = Args {
+            root: tempdir.path().to_path_buf(),
+            walk_opts: crate::fs::WalkDirOpts::default(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let entries = entries(&session);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(path(&entries[0]), tempdir.path());
+    }
+
+    #[test]
+    fn handle_dir_with_files() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::write(tempdir.path().join("a"), b"123456789")
+            .unwrap();
+        std::fs::create_dir(tempdir.path().join("b"))
+            .unwrap();
+        std::fs::File::create(tempdir.path().join("b").join("c"))
+            .unwrap();
+
+        let args = Args {
+            root: tempdir.path().to_path_buf(),
+            walk_opts: crate::fs::WalkDirOpts::default(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let mut entries = entries(&session);
+        entries.sort_by_key(path);
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(path(&entries[0]), tempdir.path());
+        assert_eq!(path(&entries[1]), tempdir.path().join("a"));
+        assert_eq!(entries[1].get_size(), 9);
+        assert_eq!(path(&entries[2]), tempdir.path().join("b"));
+        assert_eq!(path(&entries[3]), tempdir.path().join("b").join("c"));
+    }
+
+    #[test]
+    fn from_proto_default_one_device() {
+        let mut proto = rrg_proto::v2::get_filesystem_timeline::Args::default();
+        proto.set_root(std::path::PathBuf::from("/").into());
+
+        let args = <Args as crate::request::Args>::from_proto(proto)
+            .unwrap();
+
+        assert!(args.walk_opts.one_device);
+    }
+
+    #[test]
+    fn from_proto_cross_devices() {
+        let mut proto = rrg_proto::v2::get_filesystem_timeline::Args::default();
+        proto.set_root(std::path::PathBuf::from("/").into());
+        proto.set_cross_devices(true);
+
+        let args = <Args as crate::request::Args>::from_proto(proto)
+            .unwrap();
+
+        assert!(!args.walk_opts.one_device);
+    }
+
+    #[test]
+    fn handle_max_depth() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::create_dir_all(tempdir.path().join("a").join("b"))
+            .unwrap();
+
+        let args = Args {
+            root: tempdir.path().to_path_buf(),
+            walk_opts: crate::fs::WalkDirOpts {
+                max_depth: Some(1),
+                ..crate::fs::WalkDirOpts::default()
+            },
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let mut entries = entries(&session);
+        entries.sort_by_key(path);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(path(&entries[0]), tempdir.path());
+        assert_eq!(path(&entries[1]), tempdir.path().join("a"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn handle_file_metadata() {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::File::create(tempdir.path().join("foo"))
+            .unwrap();
+
+        let metadata = std::fs::metadata(tempdir.path().join("foo"))
+            .unwrap();
+
+        let args = Args {
+            root: tempdir.path().to_path_buf(),
+            walk_opts: crate::fs::WalkDirOpts::default(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        let mut entries = entries(&session);
+        entries.sort_by_key(path);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].get_mode(), metadata.mode());
+        assert_eq!(entries[1].get_ino(), metadata.ino());
+        assert_eq!(entries[1].get_uid(), metadata.uid());
+        assert_eq!(entries[1].get_gid(), metadata.gid());
+    }
+
+    /// Retrieves timeline entries from the given session object.
+    fn entries(
+        session: &crate::session::FakeSession,
+    ) -> Vec<rrg_proto::v2::get_filesystem_timeline::Entry> {
+        use std::collections::HashMap;
+
+        assert_eq!(session.reply_count(), 1);
+        let item = session.reply::<Item>(0);
+
+        let blobs_by_sha256 = session.parcels::<Blob>(crate::Sink::Blob)
+            .map(|blob| (sha2::Sha256::digest(&blob.data).into(), blob))
+            .collect::<HashMap<[u8; 32], _>>();
+        assert_eq!(blobs_by_sha256.len(), item.blob_sha256s.len());
+
+        let blob_size = blobs_by_sha256.values()
+            .map(|blob| blob.data.len() as u64)
+            .sum::<u64>();
+        assert_eq!(blob_size, item.blob_size);
+
+        let blobs = item.blob_sha256s.iter()
+            .map(|blob_sha256| &blobs_by_sha256[blob_sha256].data[..]);
+
+        let entries = crate::gzchunked::decode(blobs)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len() as u64, item.entry_count);
+
+        entries
+    }
+
+    /// Extracts the path of the given timeline entry.
+    fn path(entry: &rrg_proto::v2::get_filesystem_timeline::Entry) -> PathBuf {
+        PathBuf::try_from(entry.get_path().clone())
+            .unwrap()
+    }
+}
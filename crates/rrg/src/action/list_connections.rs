@@ -0,0 +1,265 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+use rrg_macro::warn;
+
+/// Arguments of the `list_connections` action.
+pub struct Args {
+    /// Identifiers of processes to list the connections of (all processes are
+    /// considered if empty).
+    pids: Vec<u32>,
+    /// TCP states of connections to list (connections in all states and UDP
+    /// connections are listed if empty).
+    tcp_states: Vec<ospect::net::TcpState>,
+}
+
+impl Args {
+
+    /// Checks whether the given connection should be reported.
+    fn matches(&self, conn: &ospect::net::Connection) -> bool {
+        use ospect::net::Connection::*;
+
+        if self.tcp_states.is_empty() {
+            return true;
+        }
+
+        match conn {
+            Tcp(conn) => self.tcp_states.contains(&conn.state()),
+            Udp(_) => false,
+        }
+    }
+}
+
+/// A result of the `list_connections` action.
+struct Item {
+    /// Information about the individual connection.
+    conn: ospect::net::Connection,
+}
+
+/// Handles invocations of the `list_connections` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    let pids = if args.pids.is_empty() {
+        ospect::proc::ids()
+            .map_err(crate::session::Error::action)?
+            .filter_map(|pid| match pid {
+                Ok(pid) => Some(pid),
+                Err(error) => {
+                    warn!("failed to list process identifier: {}", error);
+                    None
+                }
+            })
+            .collect()
+    } else {
+        args.pids.clone()
+    };
+
+    for pid in pids {
+        // Processes can exit or be inaccessible to us, so failures to list the
+        // connections of individual processes should not abort the action.
+        let conns = match ospect::net::connections(pid) {
+            Ok(conns) => conns,
+            Err(error) => {
+                warn!("failed to list connections of process {}: {}", pid, error);
+                continue;
+            }
+        };
+
+        for conn in conns {
+            let conn = match conn {
+                Ok(conn) => conn,
+                Err(error) => {
+                    warn!("failed to parse connection of process {}: {}", pid, error);
+                    continue;
+                }
+            };
+
+            if !args.matches(&conn) {
+                continue;
+            }
+
+            session.reply(Item {
+                conn,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An error type for situations when the requested TCP state is not known.
+#[derive(Debug)]
+struct UnknownTcpStateError {
+    /// Raw value of the unknown state.
+    value: i32,
+}
+
+impl std::fmt::Display for UnknownTcpStateError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "unknown TCP state ({})", self.value)
+    }
+}
+
+impl std::error::Error for UnknownTcpStateError {
+}
+
+/// Converts the protobuf TCP state into its `ospect` counterpart.
+fn parse_tcp_state(
+    state: rrg_proto::v2::net::TcpState,
+) -> Result<ospect::net::TcpState, UnknownTcpStateError> {
+    use protobuf::ProtobufEnum as _;
+    use rrg_proto::v2::net::TcpState::*;
+
+    match state {
+        LISTEN => Ok(ospect::net::TcpState::Listen),
+        SYN_SENT => Ok(ospect::net::TcpState::SynSent),
+        SYN_RECEIVED => Ok(ospect::net::TcpState::SynReceived),
+        ESTABLISHED => Ok(ospect::net::TcpState::Established),
+        FIN_WAIT_1 => Ok(ospect::net::TcpState::FinWait1),
+        FIN_WAIT_2 => Ok(ospect::net::TcpState::FinWait2),
+        CLOSE_WAIT => Ok(ospect::net::TcpState::CloseWait),
+        CLOSING => Ok(ospect::net::TcpState::Closing),
+        LAST_ACK => Ok(ospect::net::TcpState::LastAck),
+        TIME_WAIT => Ok(ospect::net::TcpState::TimeWait),
+        CLOSED => Ok(ospect::net::TcpState::Closed),
+        UNKNOWN_STATE => Err(UnknownTcpStateError {
+            value: state.value(),
+        }),
+    }
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::list_connections::Args;
+
+    fn from_proto(mut proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+
+        let tcp_states = proto.get_tcp_states().iter()
+            .map(|state| parse_tcp_state(*state))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| ParseArgsError::invalid_field("tcp_states", error))?;
+
+        Ok(Args {
+            pids: proto.take_pids(),
+            tcp_states,
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::list_connections::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::list_connections::Result::default();
+        proto.set_connection(self.conn.into());
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_tcp_listener() {
+        let server = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap();
+        let server_addr = server.local_addr()
+            .unwrap();
+
+        let args = Args {
+            pids: vec![std::process::id()],
+            tcp_states: vec![ospect::net::TcpState::Listen],
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert! {
+            session.replies::<Item>().any(|item| match item.conn {
+                ospect::net::Connection::Tcp(conn) => {
+                    conn.local_addr() == server_addr &&
+                    conn.pid() == std::process::id()
+                }
+                ospect::net::Connection::Udp(_) => false,
+            })
+        };
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_tcp_states_filter() {
+        let server = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap();
+        let server_addr = server.local_addr()
+            .unwrap();
+
+        let args = Args {
+            pids: vec![std::process::id()],
+            tcp_states: vec![ospect::net::TcpState::Established],
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert! {
+            session.replies::<Item>().all(|item| match item.conn {
+                ospect::net::Connection::Tcp(conn) => {
+                    conn.local_addr() != server_addr &&
+                    conn.state() == ospect::net::TcpState::Established
+                }
+                ospect::net::Connection::Udp(_) => false,
+            })
+        };
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_udp_socket() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap();
+        let socket_addr = socket.local_addr()
+            .unwrap();
+
+        let args = Args {
+            pids: vec![std::process::id()],
+            tcp_states: vec![],
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert! {
+            session.replies::<Item>().any(|item| match item.conn {
+                ospect::net::Connection::Udp(conn) => {
+                    conn.local_addr() == socket_addr
+                }
+                ospect::net::Connection::Tcp(_) => false,
+            })
+        };
+    }
+
+    #[test]
+    fn handle_non_existent_pid() {
+        let args = Args {
+            // There is no system that would assign such a big identifier.
+            pids: vec![u32::MAX],
+            tcp_states: vec![],
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 0);
+    }
+}
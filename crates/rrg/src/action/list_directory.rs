@@ -0,0 +1,356 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+use std::path::PathBuf;
+
+/// Arguments of the `list_directory` action.
+pub struct Args {
+    /// Path to the directory to list the contents of.
+    path: PathBuf,
+}
+
+/// Result of the `list_directory` action.
+struct Item {
+    /// Path to the entry within the listed directory (if known).
+    path: Option<PathBuf>,
+    /// Metadata of the entry (if it was possible to collect it).
+    metadata: Option<std::fs::Metadata>,
+    /// Extended attributes of the entry.
+    #[cfg(target_family = "unix")]
+    ext_attrs: Vec<ospect::fs::ExtAttr>,
+    /// Error that occurred when collecting information about the entry.
+    error: Option<Error>,
+}
+
+impl Item {
+
+    /// Collects information about the given directory entry.
+    fn new(entry: crate::fs::Entry) -> Item {
+        #[cfg(target_family = "unix")]
+        let ext_attrs = || -> std::io::Result<Vec<ospect::fs::ExtAttr>> {
+            ospect::fs::ext_attrs(&entry.path)?
+                .collect()
+        }();
+
+        #[cfg(target_family = "unix")]
+        let (ext_attrs, error) = match ext_attrs {
+            Ok(ext_attrs) => (ext_attrs, None),
+            Err(error) => (Vec::new(), Some(Error::ExtAttrs(error))),
+        };
+
+        #[cfg(not(target_family = "unix"))]
+        let error = None;
+
+        Item {
+            path: Some(entry.path),
+            metadata: Some(entry.metadata),
+            #[cfg(target_family = "unix")]
+            ext_attrs,
+            error,
+        }
+    }
+
+    /// Creates an item for an entry that it was not possible to retrieve.
+    fn error(error: crate::fs::ListDirError) -> Item {
+        Item {
+            path: error.path().map(PathBuf::from),
+            metadata: None,
+            #[cfg(target_family = "unix")]
+            ext_attrs: Vec::new(),
+            error: Some(Error::ListDir(error)),
+        }
+    }
+}
+
+/// An error type for failures to collect information about an entry.
+#[derive(Debug)]
+enum Error {
+    /// It was not possible to retrieve the entry or its metadata.
+    ListDir(crate::fs::ListDirError),
+    /// It was not possible to collect extended attributes of the entry.
+    #[cfg(target_family = "unix")]
+    ExtAttrs(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Error::*;
+
+        match *self {
+            ListDir(ref error) => {
+                write!(fmt, "{}", error)
+            }
+            #[cfg(target_family = "unix")]
+            ExtAttrs(ref error) => {
+                write!(fmt, "failed to collect extended attributes: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            ListDir(ref error) => Some(error),
+            #[cfg(target_family = "unix")]
+            ExtAttrs(ref error) => Some(error),
+        }
+    }
+}
+
+/// Handles invocations of the `list_directory` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    if args.path.is_relative() {
+        use std::io::{Error, ErrorKind};
+
+        let error = Error::new(ErrorKind::InvalidInput, "relative path");
+        return Err(crate::session::Error::action(error));
+    }
+
+    let path = args.path.canonicalize()
+        .map_err(crate::session::Error::action)?;
+
+    let entries = crate::fs::try_list_dir(&path)
+        .map_err(crate::session::Error::action)?;
+
+    for entry in entries {
+        let item = match entry {
+            Ok(entry) => Item::new(entry),
+            Err(error) => Item::error(error),
+        };
+
+        session.reply(item)?;
+    }
+
+    Ok(())
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::list_directory::Args;
+
+    fn from_proto(mut proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+
+        let path = PathBuf::try_from(proto.take_path())
+            .map_err(|error| ParseArgsError::invalid_field("path", error))?;
+
+        Ok(Args {
+            path,
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::list_directory::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::list_directory::Result::default();
+        if let Some(path) = self.path {
+            proto.set_path(path.into());
+        }
+        if let Some(metadata) = self.metadata {
+            proto.set_metadata(metadata.into());
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            for ext_attr in self.ext_attrs {
+                proto.mut_ext_attrs().push(ext_attr.into());
+            }
+        }
+
+        if let Some(error) = self.error {
+            proto.set_error(error.to_string());
+        }
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handle_non_existent() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.path().join("foo"),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_relative() {
+        let args = Args {
+            path: PathBuf::from("foo/bar/baz"),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[test]
+    fn handle_empty() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.path().to_path_buf(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 0);
+    }
+
+    #[test]
+    fn handle_files_and_dirs() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+        let tempdir = tempdir.path().canonicalize()
+            .unwrap();
+
+        std::fs::File::create(tempdir.join("foo"))
+            .unwrap();
+        std::fs::create_dir(tempdir.join("bar"))
+            .unwrap();
+        std::fs::File::create(tempdir.join("bar").join("baz"))
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.clone(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 2);
+
+        let mut items = session.replies::<Item>().collect::<Vec<_>>();
+        items.sort_by_key(|item| item.path.clone());
+
+        assert_eq!(items[0].path, Some(tempdir.join("bar")));
+        assert!(items[0].metadata.as_ref().unwrap().is_dir());
+        assert!(items[0].error.is_none());
+
+        assert_eq!(items[1].path, Some(tempdir.join("foo")));
+        assert!(items[1].metadata.as_ref().unwrap().is_file());
+        assert!(items[1].error.is_none());
+    }
+
+    #[test]
+    fn handle_denied() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+        let tempdir = tempdir.path().canonicalize()
+            .unwrap();
+
+        std::fs::File::create(tempdir.join("foo"))
+            .unwrap();
+        std::fs::create_dir(tempdir.join("bar"))
+            .unwrap();
+
+        crate::fs::deny(tempdir.join("bar"));
+
+        let args = Args {
+            path: tempdir.clone(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+        assert_eq!(session.reply::<Item>(0).path, Some(tempdir.join("foo")));
+
+        let args = Args {
+            path: tempdir.join("bar"),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_err());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn handle_symlink() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+        let tempdir = tempdir.path().canonicalize()
+            .unwrap();
+
+        std::fs::File::create(tempdir.join("file"))
+            .unwrap();
+        std::os::unix::fs::symlink(tempdir.join("file"), tempdir.join("link"))
+            .unwrap();
+
+        let args = Args {
+            path: tempdir.clone(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 2);
+
+        let mut items = session.replies::<Item>().collect::<Vec<_>>();
+        items.sort_by_key(|item| item.path.clone());
+
+        assert_eq!(items[0].path, Some(tempdir.join("file")));
+        assert!(items[0].metadata.as_ref().unwrap().is_file());
+
+        assert_eq!(items[1].path, Some(tempdir.join("link")));
+        assert!(items[1].metadata.as_ref().unwrap().is_symlink());
+    }
+
+    #[cfg(feature = "test-setfattr")]
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_ext_attrs() {
+        let tempdir = tempfile::tempdir()
+            .unwrap();
+
+        std::fs::File::create(tempdir.path().join("foo"))
+            .unwrap();
+
+        assert! {
+            std::process::Command::new("setfattr")
+                .arg("--no-dereference")
+                .arg("--name").arg("user.foo")
+                .arg("--value").arg("bar")
+                .arg(tempdir.path().join("foo").as_os_str())
+                .status().unwrap()
+                .success()
+        };
+
+        let args = Args {
+            path: tempdir.path().to_path_buf(),
+        };
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.ext_attrs.len(), 1);
+        assert_eq!(item.ext_attrs[0].name, "user.foo");
+        assert_eq!(item.ext_attrs[0].value, b"bar");
+    }
+}
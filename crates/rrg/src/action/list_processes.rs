@@ -0,0 +1,74 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+use rrg_macro::warn;
+
+/// A result of the `list_processes` action.
+struct Item {
+    /// Information about the individual process.
+    process: ospect::proc::Process,
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::list_processes::Result;
+
+    fn into_proto(self) -> rrg_proto::v2::list_processes::Result {
+        let mut proto = rrg_proto::v2::list_processes::Result::new();
+        proto.set_process(self.process.into());
+
+        proto
+    }
+}
+
+/// Handles invocations of the `list_processes` action.
+pub fn handle<S>(session: &mut S, _: ()) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    let processes = ospect::proc::processes()
+        .map_err(crate::session::Error::action)?;
+
+    for process in processes {
+        // Processes can exit between the moment we list them and the moment we
+        // collect information about them, so failures for individual processes
+        // are expected and should not abort the entire action.
+        let process = match process {
+            Ok(process) => process,
+            Err(error) => {
+                warn!("failed to collect process information: {}", error);
+                continue;
+            }
+        };
+
+        session.reply(Item {
+            process,
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_self() {
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, ()).is_ok());
+
+        let item = session.replies::<Item>()
+            .find(|item| item.process.pid() == std::process::id())
+            .unwrap();
+
+        assert_eq! {
+            item.process.exe(),
+            Some(std::env::current_exe().unwrap().as_path())
+        };
+    }
+}
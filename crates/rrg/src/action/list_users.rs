@@ -0,0 +1,70 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+use rrg_macro::warn;
+
+/// A result of the `list_users` action.
+struct Item {
+    /// Information about the individual user account.
+    user: ospect::user::User,
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::list_users::Result;
+
+    fn into_proto(self) -> rrg_proto::v2::list_users::Result {
+        let mut proto = rrg_proto::v2::list_users::Result::new();
+        proto.set_user(self.user.into());
+
+        proto
+    }
+}
+
+/// Handles invocations of the `list_users` action.
+pub fn handle<S>(session: &mut S, _: ()) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    let users = ospect::user::users()
+        .map_err(crate::session::Error::action)?;
+
+    for user in users {
+        // A single malformed account entry should not prevent us from reporting
+        // all the other ones.
+        let user = match user {
+            Ok(user) => user,
+            Err(error) => {
+                warn!("failed to collect user information: {}", error);
+                continue;
+            }
+        };
+
+        session.reply(Item {
+            user,
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn handle_root() {
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, ()).is_ok());
+
+        let item = session.replies::<Item>()
+            .find(|item| item.user.uid() == 0)
+            .unwrap();
+
+        assert_eq!(item.user.name(), "root");
+    }
+}
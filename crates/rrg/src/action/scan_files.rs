@@ -0,0 +1,270 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+use std::io::Read as _;
+use std::path::PathBuf;
+
+use log::warn;
+
+use crate::action::deprecated::finder::glob;
+
+mod parser;
+mod rules;
+
+/// Default maximum size of files to scan.
+const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Arguments of the `scan_files` action.
+pub struct Args {
+    /// Patterns specifying files to scan.
+    paths: Vec<glob::Pattern>,
+    /// Options of the search for files matching the patterns.
+    find_opts: glob::FindOpts,
+    /// Rules to evaluate against found files.
+    rules: rules::Rules,
+    /// Maximum size of files to scan.
+    max_file_size: u64,
+}
+
+/// Result of the `scan_files` action.
+struct Item {
+    /// Path to the scanned file.
+    path: PathBuf,
+    /// Rules that matched the file.
+    rule_matches: Vec<rules::RuleMatch>,
+}
+
+/// Handles invocations of the `scan_files` action.
+pub fn handle<S>(session: &mut S, args: Args) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    // Patterns can overlap (e.g. because of recursive components), so we keep
+    // track of already scanned files.
+    let mut scanned = std::collections::HashSet::new();
+
+    for pattern in &args.paths {
+        pattern.find(args.find_opts, |entry| {
+            if !scanned.insert(entry.path.clone()) {
+                return Ok(());
+            }
+
+            scan(session, &args, entry)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Scans the given file and reports it if any of the rules matched.
+fn scan<S>(
+    session: &mut S,
+    args: &Args,
+    entry: crate::fs::Entry,
+) -> crate::session::Result<()>
+where
+    S: crate::session::Session,
+{
+    if !entry.metadata.is_file() {
+        return Ok(());
+    }
+
+    if entry.metadata.len() > args.max_file_size {
+        warn! {
+            "skipping '{}' ({} bytes): file too big to scan",
+            entry.path.display(),
+            entry.metadata.len(),
+        };
+        return Ok(());
+    }
+
+    // The file is streamed through the rules, so we never keep more than a
+    // chunk of it in memory. The file may still grow after we checked its
+    // size though, so we never read more than the limit.
+    let rule_matches = match std::fs::File::open(&entry.path) {
+        Ok(file) => args.rules.scan(file.take(args.max_file_size)),
+        Err(error) => Err(error),
+    };
+    let rule_matches = match rule_matches {
+        Ok(rule_matches) => rule_matches,
+        Err(error) => {
+            warn!("failed to scan '{}': {}", entry.path.display(), error);
+            return Ok(());
+        }
+    };
+    if rule_matches.is_empty() {
+        return Ok(());
+    }
+
+    session.reply(Item {
+        path: entry.path,
+        rule_matches,
+    })?;
+
+    Ok(())
+}
+
+impl crate::request::Args for Args {
+
+    type Proto = rrg_proto::v2::scan_files::Args;
+
+    fn from_proto(proto: Self::Proto) -> Result<Args, crate::request::ParseArgsError> {
+        use crate::request::ParseArgsError;
+
+        let mut paths = Vec::new();
+        for path in proto.get_paths() {
+            let pattern = glob::Pattern::parse(path)
+                .map_err(|error| ParseArgsError::invalid_field("paths", error))?;
+            paths.push(pattern);
+        }
+
+        let rules = rules::Rules::parse(proto.get_rules())
+            .map_err(|error| ParseArgsError::invalid_field("rules", error))?;
+
+        let max_file_size = match proto.get_max_file_size() {
+            0 => DEFAULT_MAX_FILE_SIZE,
+            max_file_size => max_file_size,
+        };
+
+        Ok(Args {
+            paths,
+            find_opts: glob::FindOpts {
+                follow_symlinks: proto.get_follow_symlinks(),
+                one_device: proto.get_one_device(),
+            },
+            rules,
+            max_file_size,
+        })
+    }
+}
+
+impl crate::response::Item for Item {
+
+    type Proto = rrg_proto::v2::scan_files::Result;
+
+    fn into_proto(self) -> Self::Proto {
+        let mut proto = rrg_proto::v2::scan_files::Result::default();
+        proto.set_path(self.path.into());
+
+        for rule_match in self.rule_matches {
+            let mut rule_match_proto = rrg_proto::v2::scan_files::RuleMatch::default();
+            rule_match_proto.set_rule(rule_match.rule);
+            rule_match_proto.set_tags(rule_match.tags.into());
+
+            for string_match in rule_match.strings {
+                let mut string_match_proto = rrg_proto::v2::scan_files::StringMatch::default();
+                string_match_proto.set_id(string_match.id);
+                string_match_proto.set_offset(string_match.offset);
+                string_match_proto.set_length(string_match.length);
+                string_match_proto.set_data(string_match.data);
+
+                rule_match_proto.mut_string_matches().push(string_match_proto);
+            }
+
+            proto.mut_rule_matches().push(rule_match_proto);
+        }
+
+        proto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(paths: &[&std::path::Path], rules: &str) -> Args {
+        let mut proto = rrg_proto::v2::scan_files::Args::default();
+        for path in paths {
+            proto.mut_paths().push(path.to_str().unwrap().to_string());
+        }
+        proto.set_rules(String::from(rules));
+
+        <Args as crate::request::Args>::from_proto(proto).unwrap()
+    }
+
+    #[test]
+    fn handle_matching_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"MZ\x90\x00xxxfooxxx").unwrap();
+        std::fs::write(tempdir.path().join("bar"), b"MZ\x90\x00xxxbarxxx").unwrap();
+        std::fs::write(tempdir.path().join("baz"), b"xxxfooxxx").unwrap();
+
+        let args = args(&[&tempdir.path().join("*")], r#"
+            rule foo : suspicious {
+                strings:
+                    $mz = { 4D 5A 90 ?? }
+                    $foo = "foo"
+                condition:
+                    $mz at 0 and $foo
+            }
+        "#);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+
+        let item = session.reply::<Item>(0);
+        assert_eq!(item.path, tempdir.path().join("foo"));
+        assert_eq!(item.rule_matches.len(), 1);
+        assert_eq!(item.rule_matches[0].rule, "foo");
+        assert_eq!(item.rule_matches[0].tags, vec!["suspicious"]);
+        assert_eq!(item.rule_matches[0].strings, vec! {
+            rules::StringMatch {
+                id: String::from("$mz"),
+                offset: 0,
+                length: 4,
+                data: b"MZ\x90\x00".to_vec(),
+            },
+            rules::StringMatch {
+                id: String::from("$foo"),
+                offset: 7,
+                length: 3,
+                data: b"foo".to_vec(),
+            },
+        });
+    }
+
+    #[test]
+    fn handle_non_regular_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("foo")).unwrap();
+
+        let args = args(&[&tempdir.path().join("*")], r#"
+            rule any_file { condition: true }
+        "#);
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 0);
+    }
+
+    #[test]
+    fn handle_oversized_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo"), b"foo").unwrap();
+        std::fs::write(tempdir.path().join("foobar"), b"foobar").unwrap();
+
+        let mut args = args(&[&tempdir.path().join("*")], r#"
+            rule any_file { condition: true }
+        "#);
+        args.max_file_size = 4;
+
+        let mut session = crate::session::FakeSession::new();
+        assert!(handle(&mut session, args).is_ok());
+
+        assert_eq!(session.reply_count(), 1);
+        assert_eq!(session.reply::<Item>(0).path, tempdir.path().join("foo"));
+    }
+
+    #[test]
+    fn from_proto_invalid_rules() {
+        let mut proto = rrg_proto::v2::scan_files::Args::default();
+        proto.set_rules(String::from("rule foo { condition: $a }"));
+
+        assert!(<Args as crate::request::Args>::from_proto(proto).is_err());
+    }
+}
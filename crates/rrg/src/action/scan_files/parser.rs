@@ -0,0 +1,1175 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+//! Parser of rules written in a subset of the YARA language.
+
+use super::rules::{ArithOp, CmpOp, Expr, Quantifier, Rule, StringDef};
+
+/// Parses and compiles all rules of the given source.
+pub fn parse(source: &str) -> Result<Vec<Rule>, ParseError> {
+    let mut parser = Parser {
+        lexer: Lexer {
+            source: source.as_bytes(),
+            pos: 0,
+            line: 1,
+        },
+        peeked: None,
+        depth: 0,
+        rules: Vec::new(),
+        strings: Vec::new(),
+    };
+
+    while parser.peek()?.is_some() {
+        let rule = parser.rule()?;
+        parser.rules.push(rule);
+    }
+
+    Ok(parser.rules)
+}
+
+/// A token of the rule source.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Identifier or keyword (e.g. `rule` or `filesize`).
+    Ident(String),
+    /// String identifier (e.g. `$a`).
+    StringId(String),
+    /// String identifier with a wildcard (e.g. `$a*`).
+    StringIdWildcard(String),
+    /// String count (e.g. `#a`).
+    StringCount(String),
+    /// String offset (e.g. `@a`).
+    StringOffset(String),
+    /// String length (e.g. `!a`).
+    StringLength(String),
+    /// Integer literal.
+    Int(i64),
+    /// Text string literal (with escape sequences resolved).
+    Text(Vec<u8>),
+    /// Regular expression literal (with its flags).
+    Regex {
+        pattern: String,
+        nocase: bool,
+        dotall: bool,
+    },
+    /// Operator or punctuation.
+    Punct(&'static str),
+}
+
+/// Maximum nesting depth of condition expressions.
+///
+/// Parsing, evaluating and dropping expressions is recursive, so without the
+/// limit a malicious rule could overflow the stack (and abort the agent).
+/// Chained binary operators count as nesting as well, as they make the tree of
+/// the expression just as deep.
+const MAX_EXPR_DEPTH: usize = 128;
+
+/// All operators and punctuation (longer ones first).
+const PUNCTS: &[&str] = &[
+    "..", "==", "!=", "<=", ">=",
+    "<", ">", "=", ":", "(", ")", "[", "]", "{", "}", ",",
+    "+", "-", "*", "\\", "%",
+];
+
+/// Splits the rule source into tokens.
+struct Lexer<'s> {
+    /// Source being split.
+    source: &'s [u8],
+    /// Position of the next byte to process.
+    pos: usize,
+    /// Line of the next byte to process.
+    line: usize,
+}
+
+impl<'s> Lexer<'s> {
+
+    /// Creates an error at the current line.
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.line,
+            kind,
+        }
+    }
+
+    /// Returns the next byte without consuming it.
+    fn peek_byte(&self) -> Option<u8> {
+        self.source.get(self.pos).copied()
+    }
+
+    /// Consumes the next byte.
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = self.peek_byte()?;
+        self.pos += 1;
+        if byte == b'\n' {
+            self.line += 1;
+        }
+
+        Some(byte)
+    }
+
+    /// Consumes the next byte failing if the source has ended.
+    fn expect_byte(&mut self) -> Result<u8, ParseError> {
+        self.next_byte()
+            .ok_or_else(|| self.error(ParseErrorKind::UnexpectedEnd))
+    }
+
+    /// Consumes bytes while they satisfy the predicate.
+    fn take_while<P>(&mut self, pred: P) -> &'s [u8]
+    where
+        P: Fn(u8) -> bool,
+    {
+        let start = self.pos;
+        while self.peek_byte().is_some_and(&pred) {
+            self.next_byte();
+        }
+
+        &self.source[start..self.pos]
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.take_while(|byte| byte.is_ascii_whitespace());
+
+            let rest = &self.source[self.pos..];
+            if rest.starts_with(b"//") {
+                self.take_while(|byte| byte != b'\n');
+            } else if rest.starts_with(b"/*") {
+                self.pos += 2;
+                while !self.source[self.pos..].starts_with(b"*/") {
+                    self.expect_byte()?;
+                }
+                self.pos += 2;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Consumes the next token (or returns `None` at the end of the source).
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        self.skip_trivia()?;
+
+        let byte = match self.peek_byte() {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+
+        let token = match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => Token::Ident(self.ident()),
+            b'0'..=b'9' => Token::Int(self.int()?),
+            b'"' => Token::Text(self.text()?),
+            b'/' => self.regex()?,
+            b'$' => {
+                self.next_byte();
+                let id = self.ident();
+                if self.peek_byte() == Some(b'*') {
+                    self.next_byte();
+                    Token::StringIdWildcard(id)
+                } else {
+                    Token::StringId(id)
+                }
+            }
+            b'#' | b'@' => {
+                self.next_byte();
+                let id = self.nonempty_ident()?;
+                match byte {
+                    b'#' => Token::StringCount(id),
+                    _ => Token::StringOffset(id),
+                }
+            }
+            b'!' if self.source[self.pos..].starts_with(b"!=") => {
+                self.pos += 2;
+                Token::Punct("!=")
+            }
+            b'!' => {
+                self.next_byte();
+                Token::StringLength(self.nonempty_ident()?)
+            }
+            _ => {
+                let rest = &self.source[self.pos..];
+                let punct = match PUNCTS.iter().copied().find(|punct| rest.starts_with(punct.as_bytes())) {
+                    Some(punct) => punct,
+                    None => {
+                        let char = String::from_utf8_lossy(rest).chars().next().unwrap_or('\u{FFFD}');
+                        return Err(self.error(ParseErrorKind::UnexpectedChar(char)));
+                    }
+                };
+                self.pos += punct.len();
+                Token::Punct(punct)
+            }
+        };
+
+        Ok(Some(token))
+    }
+
+    /// Consumes a (possibly empty) identifier.
+    fn ident(&mut self) -> String {
+        let ident = self.take_while(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+
+        // Identifiers consist of ASCII characters only.
+        String::from_utf8_lossy(ident).into_owned()
+    }
+
+    /// Consumes a non-empty identifier.
+    fn nonempty_ident(&mut self) -> Result<String, ParseError> {
+        let ident = self.ident();
+        if ident.is_empty() {
+            return Err(self.error(ParseErrorKind::Unsupported("anonymous string references")));
+        }
+
+        Ok(ident)
+    }
+
+    /// Consumes an integer literal (decimal or hexadecimal, with an optional
+    /// `KB` or `MB` multiplier).
+    fn int(&mut self) -> Result<i64, ParseError> {
+        let value = if self.source[self.pos..].starts_with(b"0x") {
+            self.pos += 2;
+            let digits = self.take_while(|byte| byte.is_ascii_hexdigit());
+            i64::from_str_radix(&String::from_utf8_lossy(digits), 16)
+        } else {
+            let digits = self.take_while(|byte| byte.is_ascii_digit());
+            String::from_utf8_lossy(digits).parse::<i64>()
+        };
+        let value = value
+            .map_err(|_| self.error(ParseErrorKind::InvalidInt))?;
+
+        let multiplier = match self.take_while(|byte| byte.is_ascii_alphanumeric()) {
+            b"" => 1,
+            b"KB" => 1024,
+            b"MB" => 1024 * 1024,
+            _ => return Err(self.error(ParseErrorKind::InvalidInt)),
+        };
+
+        value.checked_mul(multiplier)
+            .ok_or_else(|| self.error(ParseErrorKind::InvalidInt))
+    }
+
+    /// Consumes a text string literal.
+    fn text(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.next_byte();
+
+        let mut text = Vec::new();
+        loop {
+            let byte = match self.expect_byte()? {
+                b'"' => return Ok(text),
+                b'\n' => return Err(self.error(ParseErrorKind::UnexpectedEnd)),
+                b'\\' => match self.expect_byte()? {
+                    b'"' => b'"',
+                    b'\\' => b'\\',
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'x' => {
+                        let high = self.expect_byte()?;
+                        let low = self.expect_byte()?;
+                        match (hex_digit(high), hex_digit(low)) {
+                            (Some(high), Some(low)) => high << 4 | low,
+                            _ => return Err(self.error(ParseErrorKind::InvalidEscape)),
+                        }
+                    }
+                    _ => return Err(self.error(ParseErrorKind::InvalidEscape)),
+                },
+                byte => byte,
+            };
+            text.push(byte);
+        }
+    }
+
+    /// Consumes a regular expression literal.
+    fn regex(&mut self) -> Result<Token, ParseError> {
+        self.next_byte();
+
+        let mut pattern = Vec::new();
+        loop {
+            match self.expect_byte()? {
+                b'/' => break,
+                b'\n' => return Err(self.error(ParseErrorKind::UnexpectedEnd)),
+                b'\\' => match self.expect_byte()? {
+                    // Slashes are escaped only because of the YARA syntax, the
+                    // Rust regex syntax does not need it.
+                    b'/' => pattern.push(b'/'),
+                    byte => pattern.extend([b'\\', byte]),
+                },
+                byte => pattern.push(byte),
+            }
+        }
+
+        let mut nocase = false;
+        let mut dotall = false;
+        for flag in self.take_while(|byte| byte.is_ascii_alphanumeric()) {
+            match flag {
+                b'i' => nocase = true,
+                b's' => dotall = true,
+                _ => return Err(self.error(ParseErrorKind::Unsupported("regular expression flags other than `i` and `s`"))),
+            }
+        }
+
+        let pattern = String::from_utf8(pattern)
+            .map_err(|_| self.error(ParseErrorKind::InvalidRegexSyntax))?;
+
+        Ok(Token::Regex {
+            pattern,
+            nocase,
+            dotall,
+        })
+    }
+
+    /// Consumes a hex string and returns an equivalent regular expression.
+    fn hex_string(&mut self) -> Result<String, ParseError> {
+        self.next_byte();
+
+        let mut regex = String::new();
+        let mut depth = 0;
+        let mut has_bytes = false;
+        loop {
+            self.skip_trivia()?;
+
+            match self.expect_byte()? {
+                b'}' if depth == 0 => break,
+                b'(' => {
+                    depth += 1;
+                    regex.push_str("(?:");
+                }
+                b'|' if depth > 0 => regex.push('|'),
+                b')' if depth > 0 => {
+                    depth -= 1;
+                    regex.push(')');
+                }
+                b'[' => {
+                    let jump = self.jump()?;
+                    regex.push_str(&jump);
+                }
+                high => {
+                    let low = self.expect_byte()?;
+                    regex.push_str(&hex_byte_regex(high, low)
+                        .ok_or_else(|| self.error(ParseErrorKind::InvalidHexString))?);
+                    has_bytes = true;
+                }
+            }
+        }
+
+        if !has_bytes {
+            return Err(self.error(ParseErrorKind::InvalidHexString));
+        }
+
+        Ok(regex)
+    }
+
+    /// Consumes a jump of a hex string (after the opening bracket) and returns
+    /// an equivalent regular expression.
+    fn jump(&mut self) -> Result<String, ParseError> {
+        let min = self.jump_bound()?;
+        let jump = match self.expect_byte()? {
+            b']' => match min {
+                Some(len) => format!(".{{{}}}", len),
+                None => return Err(self.error(ParseErrorKind::InvalidHexString)),
+            },
+            b'-' => {
+                let max = self.jump_bound()?;
+                if self.expect_byte()? != b']' {
+                    return Err(self.error(ParseErrorKind::InvalidHexString));
+                }
+
+                // Jumps are lazy, so that the reported matches are as short as
+                // possible.
+                match (min.unwrap_or(0), max) {
+                    (min, Some(max)) if min <= max => format!(".{{{},{}}}?", min, max),
+                    (min, None) => format!(".{{{},}}?", min),
+                    _ => return Err(self.error(ParseErrorKind::InvalidHexString)),
+                }
+            }
+            _ => return Err(self.error(ParseErrorKind::InvalidHexString)),
+        };
+
+        Ok(jump)
+    }
+
+    /// Consumes an optional bound of a hex string jump.
+    fn jump_bound(&mut self) -> Result<Option<u32>, ParseError> {
+        self.take_while(|byte| byte == b' ');
+        let digits = self.take_while(|byte| byte.is_ascii_digit());
+        self.take_while(|byte| byte == b' ');
+
+        if digits.is_empty() {
+            return Ok(None);
+        }
+
+        String::from_utf8_lossy(digits).parse::<u32>()
+            .map(Some)
+            .map_err(|_| self.error(ParseErrorKind::InvalidHexString))
+    }
+}
+
+/// Returns the value of the given hexadecimal digit.
+fn hex_digit(byte: u8) -> Option<u8> {
+    char::from(byte).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Returns a regular expression matching a byte of a hex string (given as two
+/// digits or `?` wildcards).
+fn hex_byte_regex(high: u8, low: u8) -> Option<String> {
+    let values = |nibble: u8| match nibble {
+        b'?' => Some(0x0..=0xF),
+        _ => hex_digit(nibble).map(|digit| digit..=digit),
+    };
+
+    let highs = values(high)?;
+    let lows = values(low)?;
+
+    let regex = match (high, low) {
+        (b'?', b'?') => String::from("."),
+        (_, b'?') => format!("[\\x{:X}0-\\x{:X}F]", highs.start(), highs.start()),
+        _ => {
+            let bytes = highs
+                .flat_map(|high| lows.clone().map(move |low| high << 4 | low))
+                .map(|byte| format!("\\x{:02X}", byte))
+                .collect::<String>();
+            format!("[{}]", bytes)
+        }
+    };
+
+    Some(regex)
+}
+
+/// Returns a regular expression matching the given text.
+fn text_regex(text: &[u8], ascii: bool, wide: bool) -> String {
+    let ascii_regex = text.iter()
+        .map(|byte| format!("\\x{:02X}", byte))
+        .collect::<String>();
+    let wide_regex = text.iter()
+        .map(|byte| format!("\\x{:02X}\\x00", byte))
+        .collect::<String>();
+
+    match (ascii, wide) {
+        (true, true) => format!("(?:{}|{})", ascii_regex, wide_regex),
+        (false, true) => wide_regex,
+        _ => ascii_regex,
+    }
+}
+
+/// Parses the token stream into rules.
+struct Parser<'s> {
+    /// Lexer providing the tokens.
+    lexer: Lexer<'s>,
+    /// Token peeked but not consumed yet.
+    peeked: Option<Token>,
+    /// Nesting depth of the expression being parsed.
+    depth: usize,
+    /// Rules parsed so far.
+    rules: Vec<Rule>,
+    /// Strings of the rule being parsed.
+    strings: Vec<StringDef>,
+}
+
+impl<'s> Parser<'s> {
+
+    /// Creates an error at the current line.
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.lexer.error(kind)
+    }
+
+    /// Increases the nesting depth, failing if it gets too deep.
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_EXPR_DEPTH {
+            return Err(self.error(ParseErrorKind::TooDeep));
+        }
+
+        Ok(())
+    }
+
+    /// Parses a nested expression using the given function.
+    fn nested<T, F>(&mut self, f: F) -> Result<T, ParseError>
+    where
+        F: FnOnce(&mut Parser<'s>) -> Result<T, ParseError>,
+    {
+        self.enter()?;
+        let result = f(self)?;
+        self.depth -= 1;
+
+        Ok(result)
+    }
+
+    /// Returns the next token without consuming it.
+    fn peek(&mut self) -> Result<Option<&Token>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+
+        Ok(self.peeked.as_ref())
+    }
+
+    /// Consumes the next token failing if the source has ended.
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = match self.peeked.take() {
+            Some(token) => Some(token),
+            None => self.lexer.next_token()?,
+        };
+
+        token.ok_or_else(|| self.error(ParseErrorKind::UnexpectedEnd))
+    }
+
+    /// Consumes the next token if it is the given operator or punctuation.
+    fn eat_punct(&mut self, punct: &str) -> Result<bool, ParseError> {
+        if !matches!(self.peek()?, Some(Token::Punct(next)) if *next == punct) {
+            return Ok(false);
+        }
+
+        self.next()?;
+        Ok(true)
+    }
+
+    /// Consumes the next token if it is the given keyword.
+    fn eat_keyword(&mut self, keyword: &str) -> Result<bool, ParseError> {
+        if !matches!(self.peek()?, Some(Token::Ident(next)) if next == keyword) {
+            return Ok(false);
+        }
+
+        self.next()?;
+        Ok(true)
+    }
+
+    /// Consumes the given operator or punctuation.
+    fn expect_punct(&mut self, punct: &str) -> Result<(), ParseError> {
+        match self.next()? {
+            Token::Punct(next) if next == punct => Ok(()),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    /// Consumes the given keyword.
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.next()? {
+            Token::Ident(next) if next == keyword => Ok(()),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    /// Consumes an identifier.
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    /// Creates an error about an unexpected token.
+    fn unexpected(&self, token: &Token) -> ParseError {
+        self.error(ParseErrorKind::UnexpectedToken(format!("{:?}", token)))
+    }
+
+    /// Parses a single rule.
+    fn rule(&mut self) -> Result<Rule, ParseError> {
+        let private = self.eat_keyword("private")?;
+        match self.expect_ident()?.as_str() {
+            "rule" => (),
+            "global" => return Err(self.error(ParseErrorKind::Unsupported("global rules"))),
+            "import" | "include" => return Err(self.error(ParseErrorKind::Unsupported("modules and includes"))),
+            ident => return Err(self.unexpected(&Token::Ident(String::from(ident)))),
+        }
+
+        let name = self.expect_ident()?;
+        if self.rules.iter().any(|rule| rule.name == name) {
+            return Err(self.error(ParseErrorKind::DuplicateRule(name)));
+        }
+
+        let mut tags = Vec::new();
+        if self.eat_punct(":")? {
+            while !self.eat_punct("{")? {
+                tags.push(self.expect_ident()?);
+            }
+        } else {
+            self.expect_punct("{")?;
+        }
+
+        if self.eat_keyword("meta")? {
+            self.expect_punct(":")?;
+            self.meta()?;
+        }
+
+        self.strings.clear();
+        if self.eat_keyword("strings")? {
+            self.expect_punct(":")?;
+            while let Some(Token::StringId(_)) = self.peek()? {
+                let string = self.string()?;
+                self.strings.push(string);
+            }
+        }
+
+        self.expect_keyword("condition")?;
+        self.expect_punct(":")?;
+        let condition = self.expr()?;
+        self.expect_punct("}")?;
+
+        Ok(Rule {
+            name,
+            tags,
+            private,
+            strings: std::mem::take(&mut self.strings),
+            condition,
+        })
+    }
+
+    /// Parses the entries of the meta section (which are ignored).
+    fn meta(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.peek()? {
+                Some(Token::Ident(ident)) if ident != "strings" && ident != "condition" => (),
+                _ => return Ok(()),
+            }
+
+            self.next()?;
+            self.expect_punct("=")?;
+            self.eat_punct("-")?;
+            match self.next()? {
+                Token::Text(_) | Token::Int(_) => (),
+                Token::Ident(ident) if ident == "true" || ident == "false" => (),
+                token => return Err(self.unexpected(&token)),
+            }
+        }
+    }
+
+    /// Parses a single string definition.
+    fn string(&mut self) -> Result<StringDef, ParseError> {
+        let id = match self.next()? {
+            Token::StringId(id) => id,
+            token => return Err(self.unexpected(&token)),
+        };
+        if !id.is_empty() && self.strings.iter().any(|string| string.id == id) {
+            return Err(self.error(ParseErrorKind::DuplicateString(id)));
+        }
+
+        self.expect_punct("=")?;
+
+        // Hex strings are delimited with braces, so they need to be handled
+        // by the lexer directly.
+        self.lexer.skip_trivia()?;
+        let value = if self.lexer.peek_byte() == Some(b'{') {
+            StringValue::Hex(self.lexer.hex_string()?)
+        } else {
+            match self.next()? {
+                Token::Text(text) if !text.is_empty() => StringValue::Text(text),
+                Token::Regex { pattern, nocase, dotall } => {
+                    StringValue::Regex { pattern, nocase, dotall }
+                }
+                token => return Err(self.unexpected(&token)),
+            }
+        };
+
+        let mut nocase = false;
+        let mut ascii = false;
+        let mut wide = false;
+        let mut fullword = false;
+        let mut private = false;
+        loop {
+            let modifier = match self.peek()? {
+                Some(Token::Ident(ident)) if ident != "condition" => ident.clone(),
+                _ => break,
+            };
+            self.next()?;
+
+            match (modifier.as_str(), &value) {
+                ("private", _) => private = true,
+                ("nocase", StringValue::Text(_) | StringValue::Regex { .. }) => nocase = true,
+                ("ascii", StringValue::Text(_) | StringValue::Regex { .. }) => ascii = true,
+                ("fullword", StringValue::Text(_) | StringValue::Regex { .. }) => fullword = true,
+                ("wide", StringValue::Text(_)) => wide = true,
+                _ => return Err(self.error(ParseErrorKind::InvalidModifier(modifier))),
+            }
+        }
+
+        let (pattern, nocase, dotall) = match value {
+            StringValue::Text(text) => (text_regex(&text, ascii, wide), nocase, false),
+            StringValue::Hex(pattern) => (pattern, false, true),
+            StringValue::Regex { pattern, nocase: flag_nocase, dotall } => {
+                (pattern, nocase || flag_nocase, dotall)
+            }
+        };
+
+        let regex = regex::bytes::RegexBuilder::new(&pattern)
+            .unicode(false)
+            .case_insensitive(nocase)
+            .dot_matches_new_line(dotall)
+            .build()
+            .map_err(|error| self.error(ParseErrorKind::InvalidRegex(error)))?;
+
+        Ok(StringDef {
+            id,
+            regex,
+            fullword,
+            private,
+        })
+    }
+
+    /// Parses a condition expression.
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+
+        let mut expr = self.and_expr()?;
+        while self.eat_keyword("or")? {
+            self.enter()?;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// Parses a conjunction (or anything with higher precedence).
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+
+        let mut expr = self.not_expr()?;
+        while self.eat_keyword("and")? {
+            self.enter()?;
+            expr = Expr::And(Box::new(expr), Box::new(self.not_expr()?));
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// Parses a negation (or anything with higher precedence).
+    fn not_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.eat_keyword("not")? {
+            return Ok(Expr::Not(Box::new(self.nested(Parser::not_expr)?)));
+        }
+
+        self.cmp_expr()
+    }
+
+    /// Parses a comparison (or anything with higher precedence).
+    fn cmp_expr(&mut self) -> Result<Expr, ParseError> {
+        let left = self.add_expr()?;
+
+        let op = match self.peek()? {
+            Some(Token::Punct("==")) => CmpOp::Eq,
+            Some(Token::Punct("!=")) => CmpOp::Ne,
+            Some(Token::Punct("<")) => CmpOp::Lt,
+            Some(Token::Punct("<=")) => CmpOp::Le,
+            Some(Token::Punct(">")) => CmpOp::Gt,
+            Some(Token::Punct(">=")) => CmpOp::Ge,
+            _ => return Ok(left),
+        };
+        self.next()?;
+
+        let right = self.add_expr()?;
+        Ok(Expr::Cmp(op, Box::new(left), Box::new(right)))
+    }
+
+    /// Parses an addition or subtraction (or anything with higher precedence).
+    fn add_expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+
+        let mut expr = self.mul_expr()?;
+        loop {
+            let op = match self.peek()? {
+                Some(Token::Punct("+")) => ArithOp::Add,
+                Some(Token::Punct("-")) => ArithOp::Sub,
+                _ => break,
+            };
+            self.next()?;
+
+            self.enter()?;
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.mul_expr()?));
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// Parses a multiplication, division or remainder (or anything with higher
+    /// precedence).
+    fn mul_expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+
+        let mut expr = self.unary_expr()?;
+        loop {
+            let op = match self.peek()? {
+                Some(Token::Punct("*")) => ArithOp::Mul,
+                Some(Token::Punct("\\")) => ArithOp::Div,
+                Some(Token::Punct("%")) => ArithOp::Rem,
+                _ => break,
+            };
+            self.next()?;
+
+            self.enter()?;
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.unary_expr()?));
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// Parses an arithmetic negation (or a primary expression).
+    fn unary_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.eat_punct("-")? {
+            return Ok(Expr::Neg(Box::new(self.nested(Parser::unary_expr)?)));
+        }
+
+        self.primary_expr()
+    }
+
+    /// Parses a primary expression.
+    fn primary_expr(&mut self) -> Result<Expr, ParseError> {
+        let expr = match self.next()? {
+            Token::Punct("(") => {
+                let expr = self.nested(Parser::expr)?;
+                self.expect_punct(")")?;
+                expr
+            }
+            Token::Int(value) => {
+                if self.eat_keyword("of")? {
+                    let quantifier = Quantifier::AtLeast(Box::new(Expr::Int(value)));
+                    Expr::Of(quantifier, self.string_set()?)
+                } else {
+                    Expr::Int(value)
+                }
+            }
+            Token::StringId(id) => {
+                let idx = self.string_idx(&id)?;
+                if self.eat_keyword("at")? {
+                    Expr::MatchedAt(idx, Box::new(self.nested(Parser::unary_expr)?))
+                } else if self.eat_keyword("in")? {
+                    self.expect_punct("(")?;
+                    let start = self.nested(Parser::add_expr)?;
+                    self.expect_punct("..")?;
+                    let end = self.nested(Parser::add_expr)?;
+                    self.expect_punct(")")?;
+                    Expr::MatchedIn(idx, Box::new(start), Box::new(end))
+                } else {
+                    Expr::Matched(idx)
+                }
+            }
+            Token::StringCount(id) => Expr::Count(self.string_idx(&id)?),
+            Token::StringOffset(id) => {
+                let idx = self.string_idx(&id)?;
+                Expr::Offset(idx, Box::new(self.match_idx()?))
+            }
+            Token::StringLength(id) => {
+                let idx = self.string_idx(&id)?;
+                Expr::Length(idx, Box::new(self.match_idx()?))
+            }
+            Token::Ident(ident) => match ident.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                "filesize" => Expr::Filesize,
+                "all" | "any" | "none" => {
+                    let quantifier = match ident.as_str() {
+                        "all" => Quantifier::All,
+                        "any" => Quantifier::Any,
+                        _ => Quantifier::None,
+                    };
+                    self.expect_keyword("of")?;
+                    Expr::Of(quantifier, self.string_set()?)
+                }
+                "for" => return Err(self.error(ParseErrorKind::Unsupported("`for` loops"))),
+                _ => match self.rules.iter().position(|rule| rule.name == ident) {
+                    Some(idx) => Expr::Rule(idx),
+                    None => return Err(self.error(ParseErrorKind::UndefinedIdent(ident))),
+                },
+            },
+            token => return Err(self.unexpected(&token)),
+        };
+
+        Ok(expr)
+    }
+
+    /// Parses an optional match index of an offset or a length (the first
+    /// match being the default).
+    fn match_idx(&mut self) -> Result<Expr, ParseError> {
+        if !self.eat_punct("[")? {
+            return Ok(Expr::Int(1));
+        }
+
+        let idx = self.nested(Parser::expr)?;
+        self.expect_punct("]")?;
+
+        Ok(idx)
+    }
+
+    /// Parses a set of strings (either `them` or a parenthesized list).
+    fn string_set(&mut self) -> Result<Vec<usize>, ParseError> {
+        if self.eat_keyword("them")? {
+            return Ok((0..self.strings.len()).collect());
+        }
+
+        self.expect_punct("(")?;
+
+        let mut idxs = Vec::new();
+        loop {
+            match self.next()? {
+                Token::StringId(id) => idxs.push(self.string_idx(&id)?),
+                Token::StringIdWildcard(prefix) => {
+                    let len = idxs.len();
+                    idxs.extend(self.strings.iter()
+                        .enumerate()
+                        .filter(|(_, string)| !string.id.is_empty())
+                        .filter(|(_, string)| string.id.starts_with(&prefix))
+                        .map(|(idx, _)| idx));
+
+                    if idxs.len() == len {
+                        return Err(self.error(ParseErrorKind::UndefinedString(format!("{}*", prefix))));
+                    }
+                }
+                token => return Err(self.unexpected(&token)),
+            }
+
+            if !self.eat_punct(",")? {
+                break;
+            }
+        }
+        self.expect_punct(")")?;
+
+        idxs.sort_unstable();
+        idxs.dedup();
+        Ok(idxs)
+    }
+
+    /// Returns the index of the string with the given identifier.
+    fn string_idx(&self, id: &str) -> Result<usize, ParseError> {
+        if id.is_empty() {
+            return Err(self.error(ParseErrorKind::Unsupported("anonymous string references")));
+        }
+
+        self.strings.iter()
+            .position(|string| string.id == id)
+            .ok_or_else(|| self.error(ParseErrorKind::UndefinedString(String::from(id))))
+    }
+}
+
+/// A value of a string definition before compilation.
+enum StringValue {
+    /// Text string.
+    Text(Vec<u8>),
+    /// Hex string (already translated to a regular expression).
+    Hex(String),
+    /// Regular expression.
+    Regex {
+        pattern: String,
+        nocase: bool,
+        dotall: bool,
+    },
+}
+
+/// The error type for cases when parsing rules fails.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Line of the source at which the error occurred.
+    line: usize,
+    /// Corresponding [`ParseErrorKind`] of the error.
+    kind: ParseErrorKind,
+}
+
+/// Kinds of errors that can happen when parsing rules.
+#[derive(Debug)]
+enum ParseErrorKind {
+    /// The source contains a character that does not start any token.
+    UnexpectedChar(char),
+    /// The source contains a token that is not valid at its position.
+    UnexpectedToken(String),
+    /// The source ended in the middle of a rule, string or comment.
+    UnexpectedEnd,
+    /// A text string contains an invalid escape sequence.
+    InvalidEscape,
+    /// An integer literal is malformed or too big.
+    InvalidInt,
+    /// A hex string is malformed.
+    InvalidHexString,
+    /// A regular expression is not valid UTF-8.
+    InvalidRegexSyntax,
+    /// A string cannot be compiled to a regular expression.
+    InvalidRegex(regex::Error),
+    /// A string has a modifier that is not supported for its kind.
+    InvalidModifier(String),
+    /// A rule with the same name was already defined.
+    DuplicateRule(String),
+    /// A string with the same identifier was already defined in the rule.
+    DuplicateString(String),
+    /// A condition references a string that is not defined in the rule.
+    UndefinedString(String),
+    /// A condition references a rule that is not defined before it.
+    UndefinedIdent(String),
+    /// The source uses a YARA feature that is not supported.
+    Unsupported(&'static str),
+    /// A condition expression is nested too deeply.
+    TooDeep,
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ParseErrorKind::*;
+
+        write!(fmt, "line {}: ", self.line)?;
+
+        match self.kind {
+            UnexpectedChar(char) => write!(fmt, "unexpected character {:?}", char),
+            UnexpectedToken(ref token) => write!(fmt, "unexpected token {}", token),
+            UnexpectedEnd => write!(fmt, "unexpected end of input"),
+            InvalidEscape => write!(fmt, "invalid escape sequence"),
+            InvalidInt => write!(fmt, "invalid integer"),
+            InvalidHexString => write!(fmt, "invalid hex string"),
+            InvalidRegexSyntax => write!(fmt, "regular expression is not valid UTF-8"),
+            InvalidRegex(ref error) => write!(fmt, "invalid string: {}", error),
+            InvalidModifier(ref modifier) => write!(fmt, "invalid string modifier '{}'", modifier),
+            DuplicateRule(ref name) => write!(fmt, "duplicate rule '{}'", name),
+            DuplicateString(ref id) => write!(fmt, "duplicate string '${}'", id),
+            UndefinedString(ref id) => write!(fmt, "undefined string '${}'", id),
+            UndefinedIdent(ref ident) => write!(fmt, "undefined identifier '{}'", ident),
+            Unsupported(feature) => write!(fmt, "unsupported feature: {}", feature),
+            TooDeep => write!(fmt, "expression nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseErrorKind::*;
+
+        match self.kind {
+            InvalidRegex(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn parse_error(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("// comment\n/* another\ncomment */").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_full_rule() {
+        let rules = parse(r#"
+            private rule foo : bar baz {
+                meta:
+                    author = "John Doe"
+                    version = 2
+                    experimental = true
+                strings:
+                    $text = "f\x6Fo\"\n" nocase wide ascii fullword
+                    $hex = { 4D 5A [4] ( ?? | 0A 0B ) [1-] 5? ?A } // comment
+                    $regex = /ba\/r+/is private
+                    $ = "quux"
+                condition:
+                    ($text at 0 or $hex in (0..filesize - 1)) and #regex > 1
+                    and @text[1] + !hex == 2 * -3 and 1 of ($text, $h*) and all of them
+            }
+        "#).unwrap();
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].name, "foo");
+        assert_eq!(rules[0].tags, vec!["bar", "baz"]);
+        assert!(rules[0].private);
+        assert_eq!(rules[0].strings.len(), 4);
+        assert!(rules[0].strings[0].fullword);
+        assert!(rules[0].strings[2].private);
+        assert_eq!(rules[0].strings[3].id, "");
+    }
+
+    #[test]
+    fn parse_rule_reference() {
+        let rules = parse(r#"
+            rule foo { condition: true }
+            rule bar { condition: foo or false }
+        "#).unwrap();
+
+        assert!(matches!(rules[1].condition, Expr::Or(ref left, _) if matches!(**left, Expr::Rule(0))));
+    }
+
+    #[test]
+    fn parse_line_number() {
+        assert!(parse_error("rule foo {\n condition:\n $a\n}").starts_with("line 3:"));
+    }
+
+    #[test]
+    fn parse_duplicates() {
+        assert!(parse_error(r#"
+            rule foo { condition: true }
+            rule foo { condition: true }
+        "#).contains("duplicate rule"));
+        assert!(parse_error(r#"
+            rule foo { strings: $a = "a" $a = "b" condition: $a }
+        "#).contains("duplicate string"));
+    }
+
+    #[test]
+    fn parse_undefined() {
+        assert!(parse_error("rule foo { condition: $a }").contains("undefined string"));
+        assert!(parse_error("rule foo { condition: any of ($a*) }").contains("undefined string"));
+        assert!(parse_error("rule foo { condition: bar }").contains("undefined identifier"));
+        assert!(parse_error("rule foo { condition: foo }").contains("undefined identifier"));
+    }
+
+    #[test]
+    fn parse_invalid_strings() {
+        assert!(parse_error(r#"rule foo { strings: $a = "" condition: $a }"#).contains("unexpected token"));
+        assert!(parse_error(r#"rule foo { strings: $a = "\q" condition: $a }"#).contains("invalid escape"));
+        assert!(parse_error(r#"rule foo { strings: $a = { 4D 5 } condition: $a }"#).contains("invalid hex"));
+        assert!(parse_error(r#"rule foo { strings: $a = { [2] } condition: $a }"#).contains("invalid hex"));
+        assert!(parse_error(r#"rule foo { strings: $a = { 4D [3-2] 5A } condition: $a }"#).contains("invalid hex"));
+        assert!(parse_error(r#"rule foo { strings: $a = /(/ condition: $a }"#).contains("invalid string"));
+        assert!(parse_error(r#"rule foo { strings: $a = { 4D } nocase condition: $a }"#).contains("invalid string modifier"));
+        assert!(parse_error(r#"rule foo { strings: $a = /a/ wide condition: $a }"#).contains("invalid string modifier"));
+        assert!(parse_error(r#"rule foo { strings: $a = "a" xor condition: $a }"#).contains("invalid string modifier"));
+    }
+
+    #[test]
+    fn parse_unsupported() {
+        assert!(parse_error(r#"import "pe""#).contains("unsupported"));
+        assert!(parse_error("global rule foo { condition: true }").contains("unsupported"));
+        assert!(parse_error(r#"
+            rule foo { strings: $a = "a" condition: for any of them : ($ at 0) }
+        "#).contains("unsupported"));
+    }
+
+    #[test]
+    fn parse_unterminated() {
+        assert!(parse_error("rule foo { condition: true").contains("unexpected end"));
+        assert!(parse_error("rule foo { condition: true } /* comment").contains("unexpected end"));
+        assert!(parse_error(r#"rule foo { strings: $a = "a condition: $a }"#).contains("unexpected end"));
+    }
+
+    #[test]
+    fn parse_int() {
+        assert!(parse("rule foo { condition: filesize > 0x10 and filesize < 2MB }").is_ok());
+        assert!(parse_error("rule foo { condition: 99999999999999999999 }").contains("invalid integer"));
+        assert!(parse_error("rule foo { condition: 1GB }").contains("invalid integer"));
+    }
+
+    #[test]
+    fn parse_too_deep() {
+        let nested = |open: &str, inner: &str, close: &str, count: usize| {
+            format! {
+                "rule foo {{ condition: {}{}{} }}",
+                open.repeat(count),
+                inner,
+                close.repeat(count),
+            }
+        };
+
+        assert!(parse(&nested("(", "true", ")", MAX_EXPR_DEPTH)).is_ok());
+        assert!(parse_error(&nested("(", "true", ")", MAX_EXPR_DEPTH + 1)).contains("too deeply"));
+        assert!(parse_error(&nested("(", "true", ")", 1_000_000)).contains("too deeply"));
+        assert!(parse_error(&nested("not ", "true", "", 1_000_000)).contains("too deeply"));
+        assert!(parse_error(&nested("-", "1", "", 1_000_000)).contains("too deeply"));
+        assert!(parse_error(&nested("true and ", "true", "", 1_000_000)).contains("too deeply"));
+        assert!(parse_error(&nested("1 + ", "1", "", 1_000_000)).contains("too deeply"));
+    }
+}
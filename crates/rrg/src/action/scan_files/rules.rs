@@ -0,0 +1,790 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+//! Rules written in a subset of the [YARA] language and their evaluation.
+//!
+//! The supported subset consists of:
+//!
+//!   * text strings (with `nocase`, `ascii`, `wide`, `fullword` and `private`
+//!     modifiers), hex strings (with wildcards, jumps and alternatives) and
+//!     regular expressions (with `i` and `s` flags),
+//!   * conditions with boolean, comparison and arithmetic operators, string
+//!     matches (`$a`), counts (`#a`), offsets (`@a[i]`) and lengths (`!a[i]`),
+//!   * `at` and `in` operators, `filesize` and `all`/`any`/`none`/`N of`
+//!     quantifiers over string sets (e.g. `2 of ($a*, $b)` or `any of them`),
+//!   * `private` rules, tags and references to previously defined rules.
+//!
+//! All strings are compiled to byte regular expressions, so the semantics of
+//! regular expressions is the one of the Rust `regex` crate.
+//!
+//! Scanned data is read in chunks, so only a bounded part of it is kept in
+//! memory at once. Like in YARA, matches are searched for only within a limited
+//! window following their start, so strings matching very long sequences of
+//! bytes may be cut short (or missed).
+//!
+//! [YARA]: https://yara.readthedocs.io
+
+use std::io::Read;
+use std::ops::Range;
+
+pub use super::parser::ParseError;
+
+/// Number of bytes read from the scanned data at once.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Length of the window following the start of a match that it has to fit in.
+///
+/// Every search for a match looks at most twice as many bytes ahead, so the
+/// cost of a scan grows linearly with the size of the scanned data.
+const MAX_MATCH_LEN: usize = 4096;
+
+/// Number of bytes of the previous chunk to retain when reading the next one.
+///
+/// Searches need to look ahead up to two match windows and the `fullword`
+/// check needs up to two bytes preceding the match.
+const RETAINED_LEN: usize = 2 * MAX_MATCH_LEN + 2;
+
+/// Maximum number of candidate matches of a single string to consider.
+///
+/// Candidates rejected because of the `fullword` modifier count as well, as
+/// they are as costly to find as the actual matches.
+const MAX_STRING_MATCHES: usize = 10_000;
+
+/// Maximum number of bytes of matched data to report for a single match.
+pub const MAX_MATCH_DATA_LEN: usize = 256;
+
+/// A compiled set of rules.
+#[derive(Debug)]
+pub struct Rules {
+    /// Rules in the order of their definition.
+    rules: Vec<Rule>,
+}
+
+/// A single rule of a rule set.
+#[derive(Debug)]
+pub(super) struct Rule {
+    /// Name of the rule.
+    pub name: String,
+    /// Tags attached to the rule.
+    pub tags: Vec<String>,
+    /// Whether the rule should not be reported when it matches.
+    pub private: bool,
+    /// Strings defined in the rule.
+    pub strings: Vec<StringDef>,
+    /// Condition that has to be met for the rule to match.
+    pub condition: Expr,
+}
+
+/// A string definition of a rule.
+#[derive(Debug)]
+pub(super) struct StringDef {
+    /// Identifier of the string (without the `$` prefix, empty if anonymous).
+    pub id: String,
+    /// Regular expression the string is compiled to.
+    pub regex: regex::bytes::Regex,
+    /// Whether matches have to be delimited by non-alphanumeric characters.
+    pub fullword: bool,
+    /// Whether matches should not be reported.
+    pub private: bool,
+}
+
+/// An expression of a rule condition.
+#[derive(Debug)]
+pub(super) enum Expr {
+    /// Boolean literal.
+    Bool(bool),
+    /// Integer literal.
+    Int(i64),
+    /// Size of the scanned file.
+    Filesize,
+    /// Whether the string (given by index) has any match.
+    Matched(usize),
+    /// Whether the string has a match at the given offset.
+    MatchedAt(usize, Box<Expr>),
+    /// Whether the string has a match within the given (inclusive) range.
+    MatchedIn(usize, Box<Expr>, Box<Expr>),
+    /// Number of matches of the string.
+    Count(usize),
+    /// Offset of the match of the string with the given (1-based) index.
+    Offset(usize, Box<Expr>),
+    /// Length of the match of the string with the given (1-based) index.
+    Length(usize, Box<Expr>),
+    /// Result of the rule (given by index) defined earlier.
+    Rule(usize),
+    /// Number of strings of the set that have any match compared to the
+    /// quantifier.
+    Of(Quantifier, Vec<usize>),
+    /// Logical negation.
+    Not(Box<Expr>),
+    /// Logical conjunction.
+    And(Box<Expr>, Box<Expr>),
+    /// Logical disjunction.
+    Or(Box<Expr>, Box<Expr>),
+    /// Arithmetic negation.
+    Neg(Box<Expr>),
+    /// Comparison.
+    Cmp(CmpOp, Box<Expr>, Box<Expr>),
+    /// Arithmetic operation.
+    Arith(ArithOp, Box<Expr>, Box<Expr>),
+}
+
+/// A quantifier of strings in a string set.
+#[derive(Debug)]
+pub(super) enum Quantifier {
+    /// All strings of the set.
+    All,
+    /// At least one string of the set.
+    Any,
+    /// No string of the set.
+    None,
+    /// At least the given number of strings of the set.
+    AtLeast(Box<Expr>),
+}
+
+/// A comparison operator.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// An arithmetic operator.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// A rule that matched the scanned data.
+#[derive(Debug, PartialEq)]
+pub struct RuleMatch {
+    /// Name of the rule.
+    pub rule: String,
+    /// Tags attached to the rule.
+    pub tags: Vec<String>,
+    /// Matches of (non-private) strings of the rule.
+    pub strings: Vec<StringMatch>,
+}
+
+/// A match of a rule string in the scanned data.
+#[derive(Debug, PartialEq)]
+pub struct StringMatch {
+    /// Identifier of the string (including the `$` prefix).
+    pub id: String,
+    /// Offset of the match within the scanned data.
+    pub offset: u64,
+    /// Length of the match.
+    pub length: u64,
+    /// Matched data (truncated to [`MAX_MATCH_DATA_LEN`] bytes).
+    pub data: Vec<u8>,
+}
+
+impl Rules {
+
+    /// Parses and compiles rules from the given source.
+    pub fn parse(source: &str) -> Result<Rules, ParseError> {
+        Ok(Rules {
+            rules: super::parser::parse(source)?,
+        })
+    }
+
+    /// Evaluates all rules against the given data and returns the ones that
+    /// matched (in the order of their definition).
+    pub fn scan<R: Read>(&self, reader: R) -> std::io::Result<Vec<RuleMatch>> {
+        self.scan_chunked(reader, CHUNK_SIZE)
+    }
+
+    /// Evaluates all rules reading the data in chunks of the given size.
+    fn scan_chunked<R: Read>(
+        &self,
+        mut reader: R,
+        chunk_size: usize,
+    ) -> std::io::Result<Vec<RuleMatch>> {
+        let mut searches = self.rules.iter()
+            .map(|rule| rule.strings.iter().map(Search::new).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let mut buf = Vec::with_capacity(RETAINED_LEN + chunk_size);
+        // Offset of the first byte of the buffer within the scanned data.
+        let mut buf_offset = 0u64;
+
+        loop {
+            let eof = fill(&mut reader, &mut buf, chunk_size)?;
+
+            for search in searches.iter_mut().flatten() {
+                search.feed(&buf, buf_offset, eof);
+            }
+
+            if eof {
+                break;
+            }
+
+            let consumed_len = buf.len().saturating_sub(RETAINED_LEN);
+            buf.drain(..consumed_len);
+            buf_offset += consumed_len as u64;
+        }
+
+        let filesize = buf_offset + buf.len() as u64;
+
+        let mut results = Vec::with_capacity(self.rules.len());
+        let mut rule_matches = Vec::new();
+
+        for (rule, searches) in self.rules.iter().zip(searches) {
+            let string_matches = searches.into_iter()
+                .map(|search| search.matches)
+                .collect::<Vec<_>>();
+
+            let context = Context {
+                filesize,
+                string_matches: &string_matches,
+                results: &results,
+            };
+
+            // Undefined values (e.g. offsets of non-existent matches) make the
+            // whole condition false.
+            let matched = matches!(context.eval(&rule.condition), Some(value) if value != 0);
+            results.push(matched);
+
+            if !matched || rule.private {
+                continue;
+            }
+
+            let mut strings = Vec::new();
+            for (string, matches) in rule.strings.iter().zip(&string_matches) {
+                if string.private {
+                    continue;
+                }
+
+                for found in matches {
+                    strings.push(StringMatch {
+                        id: format!("${}", string.id),
+                        offset: found.range.start,
+                        length: found.range.end - found.range.start,
+                        data: found.data.clone(),
+                    });
+                }
+            }
+
+            rule_matches.push(RuleMatch {
+                rule: rule.name.clone(),
+                tags: rule.tags.clone(),
+                strings,
+            });
+        }
+
+        Ok(rule_matches)
+    }
+}
+
+/// A match of a rule string found in the scanned data.
+#[derive(Debug)]
+struct Found {
+    /// Range of the match within the scanned data.
+    range: Range<u64>,
+    /// Matched data (truncated to [`MAX_MATCH_DATA_LEN`] bytes).
+    data: Vec<u8>,
+}
+
+/// State of an ongoing search for matches of a single string.
+struct Search<'r> {
+    /// String to search for.
+    string: &'r StringDef,
+    /// Offset (within the scanned data) at which the next match can start.
+    pos: u64,
+    /// Number of candidate matches considered so far.
+    candidates: usize,
+    /// Matches found so far.
+    matches: Vec<Found>,
+}
+
+impl<'r> Search<'r> {
+
+    /// Creates a new search for the given string.
+    fn new(string: &'r StringDef) -> Search<'r> {
+        Search {
+            string,
+            pos: 0,
+            candidates: 0,
+            matches: Vec::new(),
+        }
+    }
+
+    /// Looks for matches in the given buffer of the scanned data.
+    ///
+    /// Matches starting close to the end of the buffer are left for the next
+    /// call (unless this is the last buffer), as there may not be enough data
+    /// to find them yet.
+    fn feed(&mut self, buf: &[u8], buf_offset: u64, eof: bool) {
+        // Once the limit of candidates is reached the search is over and the
+        // buffer is no longer retained for it.
+        if self.candidates >= MAX_STRING_MATCHES {
+            return;
+        }
+
+        let limit = if eof {
+            buf.len()
+        } else {
+            buf.len().saturating_sub(2 * MAX_MATCH_LEN)
+        };
+
+        // Like YARA, we report a match at every offset where the string starts
+        // so we cannot use the iterator (that skips overlapping matches). To
+        // bound the cost of every search, we never look further than two match
+        // windows ahead.
+        let mut pos = usize::try_from(self.pos.saturating_sub(buf_offset))
+            .unwrap_or(usize::MAX);
+        while pos < limit && self.candidates < MAX_STRING_MATCHES {
+            let end = std::cmp::min(pos + 2 * MAX_MATCH_LEN, buf.len());
+
+            let range = match self.string.regex.find_at(&buf[..end], pos) {
+                Some(found) if end == buf.len() || found.start() <= pos + MAX_MATCH_LEN => {
+                    found.range()
+                }
+                None if end == buf.len() => {
+                    pos = buf.len();
+                    continue;
+                }
+                // Matches starting in the first window would have been found
+                // as there is a whole window of data after each of them, so
+                // we can move on to the next one.
+                _ => {
+                    pos += MAX_MATCH_LEN + 1;
+                    continue;
+                }
+            };
+            pos = range.start + 1;
+
+            if range.is_empty() {
+                continue;
+            }
+
+            self.candidates += 1;
+            if self.string.fullword && !is_fullword(buf, &range) {
+                continue;
+            }
+
+            let data_len = std::cmp::min(range.len(), MAX_MATCH_DATA_LEN);
+            self.matches.push(Found {
+                range: buf_offset + range.start as u64..buf_offset + range.end as u64,
+                data: buf[range.start..range.start + data_len].to_vec(),
+            });
+        }
+
+        if self.candidates >= MAX_STRING_MATCHES {
+            self.pos = u64::MAX;
+        } else {
+            self.pos = buf_offset + pos as u64;
+        }
+    }
+}
+
+/// Reads up to `len` more bytes to the buffer.
+///
+/// Returns `true` if the end of the data has been reached.
+fn fill<R: Read>(reader: &mut R, buf: &mut Vec<u8>, len: usize) -> std::io::Result<bool> {
+    let mut chunk = reader.take(len as u64);
+    let read_len = chunk.read_to_end(buf)?;
+
+    Ok(read_len < len)
+}
+
+/// Checks whether the match is not surrounded by alphanumeric characters.
+fn is_fullword(data: &[u8], range: &Range<usize>) -> bool {
+    // For wide strings the preceding character is followed by a zero byte, so
+    // we need to look one byte further.
+    let before = match range.start.checked_sub(1).map(|idx| data[idx]) {
+        Some(0) => range.start.checked_sub(2).map(|idx| data[idx]),
+        before => before,
+    };
+    let after = data.get(range.end).copied();
+
+    !before.iter().chain(after.iter()).any(u8::is_ascii_alphanumeric)
+}
+
+/// State needed for evaluating the condition of a rule.
+struct Context<'a> {
+    /// Size of the scanned data.
+    filesize: u64,
+    /// Matches of all the strings of the rule.
+    string_matches: &'a [Vec<Found>],
+    /// Results of rules evaluated so far.
+    results: &'a [bool],
+}
+
+impl<'a> Context<'a> {
+
+    /// Evaluates the expression.
+    ///
+    /// Booleans are represented as integers (zero being false). `None` means
+    /// that the value is undefined (e.g. because of division by zero).
+    ///
+    /// The recursion is safe as the parser bounds the depth of expressions.
+    fn eval(&self, expr: &Expr) -> Option<i64> {
+        match expr {
+            Expr::Bool(value) => Some(i64::from(*value)),
+            Expr::Int(value) => Some(*value),
+            Expr::Filesize => i64::try_from(self.filesize).ok(),
+            Expr::Matched(idx) => {
+                Some(i64::from(!self.string_matches[*idx].is_empty()))
+            }
+            Expr::MatchedAt(idx, offset) => {
+                let offset = self.eval(offset)?;
+                let matched = self.string_matches[*idx].iter()
+                    .any(|found| found.range.start as i64 == offset);
+                Some(i64::from(matched))
+            }
+            Expr::MatchedIn(idx, start, end) => {
+                let start = self.eval(start)?;
+                let end = self.eval(end)?;
+                let matched = self.string_matches[*idx].iter()
+                    .any(|found| (start..=end).contains(&(found.range.start as i64)));
+                Some(i64::from(matched))
+            }
+            Expr::Count(idx) => Some(self.string_matches[*idx].len() as i64),
+            Expr::Offset(idx, nth) => {
+                let found = self.nth_match(*idx, self.eval(nth)?)?;
+                Some(found.range.start as i64)
+            }
+            Expr::Length(idx, nth) => {
+                let found = self.nth_match(*idx, self.eval(nth)?)?;
+                Some((found.range.end - found.range.start) as i64)
+            }
+            Expr::Rule(idx) => Some(i64::from(self.results[*idx])),
+            Expr::Of(quantifier, idxs) => {
+                let count = idxs.iter()
+                    .filter(|idx| !self.string_matches[**idx].is_empty())
+                    .count() as i64;
+
+                let matched = match quantifier {
+                    Quantifier::All => count == idxs.len() as i64,
+                    Quantifier::Any => count > 0,
+                    Quantifier::None => count == 0,
+                    Quantifier::AtLeast(min) => count >= self.eval(min)?,
+                };
+                Some(i64::from(matched))
+            }
+            Expr::Not(expr) => Some(i64::from(self.eval(expr)? == 0)),
+            Expr::And(left, right) => {
+                let matched = self.is_true(left) && self.is_true(right);
+                Some(i64::from(matched))
+            }
+            Expr::Or(left, right) => {
+                let matched = self.is_true(left) || self.is_true(right);
+                Some(i64::from(matched))
+            }
+            Expr::Neg(expr) => self.eval(expr)?.checked_neg(),
+            Expr::Cmp(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+
+                let matched = match op {
+                    CmpOp::Eq => left == right,
+                    CmpOp::Ne => left != right,
+                    CmpOp::Lt => left < right,
+                    CmpOp::Le => left <= right,
+                    CmpOp::Gt => left > right,
+                    CmpOp::Ge => left >= right,
+                };
+                Some(i64::from(matched))
+            }
+            Expr::Arith(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+
+                match op {
+                    ArithOp::Add => left.checked_add(right),
+                    ArithOp::Sub => left.checked_sub(right),
+                    ArithOp::Mul => left.checked_mul(right),
+                    ArithOp::Div => left.checked_div(right),
+                    ArithOp::Rem => left.checked_rem(right),
+                }
+            }
+        }
+    }
+
+    /// Evaluates the expression in a boolean context.
+    fn is_true(&self, expr: &Expr) -> bool {
+        matches!(self.eval(expr), Some(value) if value != 0)
+    }
+
+    /// Returns the match of the string with the given (1-based) index.
+    fn nth_match(&self, idx: usize, nth: i64) -> Option<&Found> {
+        let nth = usize::try_from(nth).ok()?.checked_sub(1)?;
+        self.string_matches[idx].get(nth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn matched_rules(source: &str, data: &[u8]) -> Vec<String> {
+        Rules::parse(source).unwrap()
+            .scan(data).unwrap().into_iter()
+            .map(|rule_match| rule_match.rule)
+            .collect()
+    }
+
+    #[test]
+    fn scan_text_string() {
+        let rules = Rules::parse(r#"
+            rule foo : bar baz {
+                strings:
+                    $a = "foo"
+                condition:
+                    $a
+            }
+        "#).unwrap();
+
+        assert_eq!(rules.scan(&b"xxfooxxfoo"[..]).unwrap(), vec! {
+            RuleMatch {
+                rule: String::from("foo"),
+                tags: vec![String::from("bar"), String::from("baz")],
+                strings: vec! {
+                    StringMatch {
+                        id: String::from("$a"),
+                        offset: 2,
+                        length: 3,
+                        data: b"foo".to_vec(),
+                    },
+                    StringMatch {
+                        id: String::from("$a"),
+                        offset: 7,
+                        length: 3,
+                        data: b"foo".to_vec(),
+                    },
+                },
+            },
+        });
+        assert_eq!(rules.scan(&b"xxbarxx"[..]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn scan_text_string_modifiers() {
+        let source = r#"
+            rule nocase { strings: $a = "foo" nocase condition: $a }
+            rule wide { strings: $a = "foo" wide condition: $a }
+            rule ascii_wide { strings: $a = "foo" ascii wide condition: #a == 2 }
+            rule fullword { strings: $a = "foo" fullword condition: $a }
+        "#;
+
+        assert_eq!(matched_rules(source, b"xFoOx"), vec!["nocase"]);
+        assert_eq!(matched_rules(source, b"f\0o\0o\0 foo"), vec! {
+            "nocase",
+            "wide",
+            "ascii_wide",
+            "fullword",
+        });
+        assert_eq!(matched_rules(source, b"-f\0o\0o\0-"), vec!["wide"]);
+    }
+
+    #[test]
+    fn scan_overlapping_matches() {
+        let source = r#"
+            rule foo { strings: $a = "aa" condition: #a == 3 }
+        "#;
+
+        assert_eq!(matched_rules(source, b"aaaa"), vec!["foo"]);
+    }
+
+    #[test]
+    fn scan_hex_string() {
+        let source = r#"
+            rule jump { strings: $a = { 4D 5A [2-4] 50 45 } condition: $a at 0 }
+            rule wildcard { strings: $a = { 4D ?A ?? 0? } condition: $a }
+            rule alternative { strings: $a = { 4D ( 5A | 5B 5C ) } condition: $a }
+        "#;
+
+        assert_eq!(matched_rules(source, b"MZ\x00\x00PE"), vec!["jump", "wildcard", "alternative"]);
+        assert_eq!(matched_rules(source, b"MZ\x00\x00\x00\x00\x00PE"), vec!["wildcard", "alternative"]);
+        assert_eq!(matched_rules(source, b"MJ\xFF\x0F"), vec!["wildcard"]);
+        assert_eq!(matched_rules(source, b"xM[\\"), vec!["alternative"]);
+    }
+
+    #[test]
+    fn scan_regex_string() {
+        let source = r#"
+            rule foo { strings: $a = /fo+bar/ condition: $a }
+            rule foo_nocase { strings: $a = /fo+bar/i condition: $a }
+            rule dotall { strings: $a = /foo.bar/s condition: $a }
+        "#;
+
+        assert_eq!(matched_rules(source, b"fooooobar"), vec!["foo", "foo_nocase"]);
+        assert_eq!(matched_rules(source, b"FOOBAR"), vec!["foo_nocase"]);
+        assert_eq!(matched_rules(source, b"foo\nbar"), vec!["dotall"]);
+    }
+
+    #[test]
+    fn scan_condition_count_offset_length() {
+        let source = r#"
+            rule count { strings: $a = "ab" condition: #a >= 2 and #a < 3 }
+            rule offset { strings: $a = "ab" condition: @a[2] == 4 and @a == 1 }
+            rule length { strings: $a = /ab+/ condition: !a[1] == 3 }
+        "#;
+
+        assert_eq!(matched_rules(source, b"xabxab"), vec!["count", "offset"]);
+        assert_eq!(matched_rules(source, b"abbxab"), vec!["count", "length"]);
+        assert_eq!(matched_rules(source, b"xab"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn scan_condition_at_in() {
+        let source = r#"
+            rule at_offset { strings: $a = "foo" condition: $a at 2 }
+            rule in_range { strings: $a = "foo" condition: $a in (0..1) }
+        "#;
+
+        assert_eq!(matched_rules(source, b"xxfoo"), vec!["at_offset"]);
+        assert_eq!(matched_rules(source, b"xfoo"), vec!["in_range"]);
+    }
+
+    #[test]
+    fn scan_condition_filesize() {
+        let source = r#"
+            rule small { condition: filesize < 1KB }
+            rule even { condition: filesize % 2 == 0 and filesize \ 2 >= 1 }
+        "#;
+
+        assert_eq!(matched_rules(source, b"xx"), vec!["small", "even"]);
+        assert_eq!(matched_rules(source, b"x"), vec!["small"]);
+        assert_eq!(matched_rules(source, &[0; 1024]), vec!["even"]);
+    }
+
+    #[test]
+    fn scan_condition_quantifiers() {
+        let source = r#"
+            rule all_of { strings: $a1 = "a" $a2 = "b" $c = "c" condition: all of them }
+            rule any_of { strings: $a1 = "a" $a2 = "b" $c = "c" condition: any of ($a*) }
+            rule none_of { strings: $a1 = "a" $a2 = "b" $c = "c" condition: none of ($a1, $c) }
+            rule two_of { strings: $a1 = "a" $a2 = "b" $c = "c" condition: 2 of them }
+        "#;
+
+        assert_eq!(matched_rules(source, b"abc"), vec!["all_of", "any_of", "two_of"]);
+        assert_eq!(matched_rules(source, b"bc"), vec!["any_of", "two_of"]);
+        assert_eq!(matched_rules(source, b"b"), vec!["any_of", "none_of"]);
+        assert_eq!(matched_rules(source, b"c"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn scan_condition_boolean_operators() {
+        let source = r#"
+            rule foo {
+                strings:
+                    $a = "a"
+                    $b = "b"
+                    $c = "c"
+                condition:
+                    ($a or $b) and not $c
+            }
+        "#;
+
+        assert_eq!(matched_rules(source, b"a"), vec!["foo"]);
+        assert_eq!(matched_rules(source, b"b"), vec!["foo"]);
+        assert_eq!(matched_rules(source, b"ac"), Vec::<&str>::new());
+        assert_eq!(matched_rules(source, b"x"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn scan_condition_undefined() {
+        let source = r#"
+            rule div { condition: 1 \ (filesize - 1) == 1 }
+            rule offset { strings: $a = "a" condition: not (@a[2] == 0) }
+        "#;
+
+        assert_eq!(matched_rules(source, b"a"), Vec::<&str>::new());
+        assert_eq!(matched_rules(source, b"aa"), vec!["div", "offset"]);
+    }
+
+    #[test]
+    fn scan_rule_references() {
+        let source = r#"
+            private rule mz { strings: $a = "MZ" condition: $a at 0 }
+            rule exe { condition: mz and filesize > 2 }
+        "#;
+
+        assert_eq!(matched_rules(source, b"MZxx"), vec!["exe"]);
+        assert_eq!(matched_rules(source, b"MZ"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn scan_private_strings() {
+        let rules = Rules::parse(r#"
+            rule foo { strings: $a = "a" private $b = "b" condition: $a and $b }
+        "#).unwrap();
+
+        let rule_matches = rules.scan(&b"ab"[..]).unwrap();
+        assert_eq!(rule_matches.len(), 1);
+        assert_eq!(rule_matches[0].strings.len(), 1);
+        assert_eq!(rule_matches[0].strings[0].id, "$b");
+    }
+
+    #[test]
+    fn scan_match_data_truncated() {
+        let rules = Rules::parse(r#"
+            rule foo { strings: $a = /a+/ condition: $a at 0 }
+        "#).unwrap();
+
+        let rule_matches = rules.scan(&[b'a'; 1024][..]).unwrap();
+        assert_eq!(rule_matches[0].strings[0].length, 1024);
+        assert_eq!(rule_matches[0].strings[0].data.len(), MAX_MATCH_DATA_LEN);
+    }
+
+    #[test]
+    fn scan_chunk_boundaries() {
+        let rules = Rules::parse(r#"
+            rule foo { strings: $a = "foo" fullword condition: #a == 4 }
+        "#).unwrap();
+
+        let mut data = vec![b'x'; 4 * RETAINED_LEN];
+        for offset in [0, RETAINED_LEN - 1, 2 * RETAINED_LEN + 1, 4 * RETAINED_LEN - 3] {
+            data[offset..offset + 3].copy_from_slice(b"foo");
+            if offset > 0 {
+                data[offset - 1] = b' ';
+            }
+            if let Some(byte) = data.get_mut(offset + 3) {
+                *byte = b' ';
+            }
+        }
+
+        let expected = rules.scan_chunked(&data[..], data.len() + 1).unwrap();
+        assert_eq!(expected.len(), 1);
+
+        for chunk_size in [1, 1000, RETAINED_LEN, RETAINED_LEN + 1] {
+            assert_eq!(rules.scan_chunked(&data[..], chunk_size).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn scan_max_string_matches() {
+        let source = format! {
+            "rule foo {{ strings: $a = \"a\" condition: #a == {} }}",
+            MAX_STRING_MATCHES,
+        };
+
+        let data = vec![b'a'; CHUNK_SIZE + 2 * RETAINED_LEN];
+        assert_eq!(matched_rules(&source, &data), vec!["foo"]);
+    }
+
+    #[test]
+    fn scan_long_matches() {
+        let source = format! {
+            "rule foo {{ strings: $a = /ab+/ condition: #a == 1 and !a[1] <= {} }}",
+            2 * MAX_MATCH_LEN,
+        };
+
+        let mut data = vec![b'b'; 4 * MAX_MATCH_LEN];
+        data[0] = b'a';
+
+        assert_eq!(matched_rules(&source, &data), vec!["foo"]);
+    }
+}
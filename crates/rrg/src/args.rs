@@ -13,8 +13,16 @@
 //! shared through the entire lifetime of a program and explicitly passed to
 //! functions that care about it.
 //!
+//! Arguments can also be specified in a configuration file passed through the
+//! `--config` option. It is a TOML file with entries named after options, e.g.
+//! `max-concurrent-actions = 8` or `log-to-stdout = true`. Arguments given
+//! explicitly on the command line take precedence over the ones from the
+//! configuration file.
+//!
 //! [`from_env_args`]: fn.from_env_args.html
 
+mod config;
+
 use std::time::Duration;
 
 #[derive(argh::FromArgs)]
@@ -49,6 +57,230 @@ pub struct Args {
            arg_name="PATH",
            description="whether to log to a file")]
     pub log_to_file: Option<std::path::PathBuf>,
+
+    /// A format in which logs are written to the standard output and files.
+    #[argh(option,
+           long="log-format",
+           arg_name="FORMAT",
+           default="Format::Text",
+           description="format of log records ('text' or 'json')",
+           from_str_fn(parse_format))]
+    pub log_format: Format,
+
+    /// Determines whether to log to the system logging daemon.
+    #[cfg(target_family = "unix")]
+    #[argh(switch,
+           long="log-to-syslog",
+           description="whether to log to syslog")]
+    pub log_to_syslog: bool,
+
+    /// A size after which the log file is rotated.
+    #[argh(option,
+           long="log-file-max-size",
+           arg_name="SIZE",
+           description="size after which the log file is rotated (e.g. '10MiB')",
+           from_str_fn(parse_size))]
+    pub log_file_max_size: Option<u64>,
+
+    /// An age after which the log file is rotated.
+    #[argh(option,
+           long="log-file-max-age",
+           arg_name="DURATION",
+           description="age after which the log file is rotated (e.g. '7days')",
+           from_str_fn(parse_duration))]
+    pub log_file_max_age: Option<Duration>,
+
+    /// A number of rotated log files to keep.
+    #[argh(option,
+           long="log-file-max-count",
+           arg_name="COUNT",
+           default="5",
+           description="number of rotated log files to keep")]
+    pub log_file_max_count: usize,
+
+    /// Determines whether rotated log files are compressed.
+    #[cfg(feature = "log-compression")]
+    #[argh(switch,
+           long="log-file-compress",
+           description="whether to compress rotated log files")]
+    pub log_file_compress: bool,
+
+    /// A transport to communicate with the server through.
+    #[argh(option,
+           long="transport",
+           arg_name="TRANSPORT",
+           default="Transport::Fleetspeak",
+           description="transport to communicate through ('fleetspeak', \
+                        'stdio' or 'unix:PATH')",
+           from_str_fn(parse_transport))]
+    pub transport: Transport,
+
+    /// A maximum number of actions that can be executed at the same time.
+    #[argh(option,
+           long="max-concurrent-actions",
+           arg_name="COUNT",
+           default="::std::num::NonZeroUsize::new(4).unwrap()",
+           description="maximum number of actions executed concurrently")]
+    pub max_concurrent_actions: std::num::NonZeroUsize,
+
+    /// A directory to persist outgoing messages in before they are delivered.
+    #[cfg(feature = "spool")]
+    #[argh(option,
+           long="spool-dir",
+           arg_name="PATH",
+           description="directory to persist undelivered messages in")]
+    pub spool_dir: Option<std::path::PathBuf>,
+
+    /// Actions that the agent is allowed to execute.
+    #[argh(option,
+           long="enable-action",
+           arg_name="ACTION",
+           description="action allowed to be executed (can be repeated, all \
+                        actions are allowed if not specified)",
+           from_str_fn(parse_action))]
+    pub enabled_actions: Vec<rrg_proto::v2::rrg::Action>,
+
+    /// Actions that the agent is not allowed to execute.
+    #[argh(option,
+           long="disable-action",
+           arg_name="ACTION",
+           description="action not allowed to be executed (can be repeated)",
+           from_str_fn(parse_action))]
+    pub disabled_actions: Vec<rrg_proto::v2::rrg::Action>,
+
+    /// A limit on bytes sent by actions of requests that do not specify one.
+    #[argh(option,
+           long="default-network-bytes-limit",
+           arg_name="SIZE",
+           description="limit on bytes sent by an action if the request does \
+                        not specify one (e.g. '10MiB')",
+           from_str_fn(parse_size))]
+    pub default_network_bytes_limit: Option<u64>,
+
+    /// A limit on CPU time of actions of requests that do not specify one.
+    #[argh(option,
+           long="default-cpu-time-limit",
+           arg_name="DURATION",
+           description="limit on CPU time of an action if the request does \
+                        not specify one (e.g. '1min')",
+           from_str_fn(parse_duration))]
+    pub default_cpu_time_limit: Option<Duration>,
+
+    /// A limit on real time of actions of requests that do not specify one.
+    #[argh(option,
+           long="default-real-time-limit",
+           arg_name="DURATION",
+           description="limit on real time of an action if the request does \
+                        not specify one (e.g. '1h')",
+           from_str_fn(parse_duration))]
+    pub default_real_time_limit: Option<Duration>,
+
+    /// Paths that actions are not allowed to access.
+    #[argh(option,
+           long="deny-path",
+           arg_name="PATH",
+           description="path that actions are not allowed to access (together \
+                        with everything under it, can be repeated)")]
+    pub denied_paths: Vec<std::path::PathBuf>,
+
+    /// A configuration file to read additional arguments from.
+    #[argh(option,
+           long="config",
+           arg_name="PATH",
+           description="configuration file to read arguments from")]
+    pub config: Option<std::path::PathBuf>,
+
+    /// alternative mode to run the agent in (if any)
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+
+    /// Checks whether the agent is allowed to execute the given action.
+    ///
+    /// An action is allowed if it is not explicitly disabled and either it is
+    /// explicitly enabled or no actions are explicitly enabled at all.
+    pub fn is_action_enabled(&self, action: rrg_proto::v2::rrg::Action) -> bool {
+        if self.disabled_actions.contains(&action) {
+            return false;
+        }
+
+        self.enabled_actions.is_empty() || self.enabled_actions.contains(&action)
+    }
+}
+
+/// Alternative modes that the agent can be run in.
+#[derive(argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    /// Run a single action locally instead of listening for requests.
+    RunAction(RunActionArgs),
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name="run-action")]
+/// Run a single action and print its results to standard output.
+pub struct RunActionArgs {
+    /// A name of the action to run (e.g. `get_file_hash`).
+    #[argh(positional,
+           arg_name="ACTION",
+           description="name of the action to run")]
+    pub action: String,
+
+    /// Arguments to run the action with.
+    #[argh(option,
+           long="args",
+           arg_name="ARGS",
+           default="String::new()",
+           description="arguments to run the action with")]
+    pub args: String,
+
+    /// A format in which the action arguments are specified.
+    #[argh(option,
+           long="args-format",
+           arg_name="FORMAT",
+           default="Format::Text",
+           description="format of the action arguments ('text' or 'json')",
+           from_str_fn(parse_format))]
+    pub args_format: Format,
+
+    /// A format in which the action results are printed.
+    #[argh(option,
+           long="output-format",
+           arg_name="FORMAT",
+           default="Format::Text",
+           description="format of the action results ('text' or 'json')",
+           from_str_fn(parse_format))]
+    pub output_format: Format,
+}
+
+/// A transport that the agent uses to communicate with the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Communicate through the Fleetspeak client (the default).
+    Fleetspeak,
+    /// Communicate through the standard input and output streams.
+    ///
+    /// See [`crate::transport::StreamTransport`] for the format of the streams.
+    ///
+    /// Note that this transport should not be combined with logging to the
+    /// standard output as logs would get mixed with the responses.
+    Stdio,
+    /// Communicate through the Unix socket at the specified path.
+    ///
+    /// See [`crate::transport::StreamTransport`] for the format of the messages.
+    #[cfg(target_family = "unix")]
+    UnixSocket(std::path::PathBuf),
+}
+
+/// A human-readable format of Protocol Buffers messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The Protocol Buffers text format.
+    Text,
+    /// The canonical JSON mapping of Protocol Buffers messages.
+    Json,
 }
 
 /// Parses command-line arguments.
@@ -57,9 +289,125 @@ pub struct Args {
 /// creating instances of [`Args`]. Ideally, it should be called only once in
 /// the entire lifetime of the agent.
 ///
+/// If a configuration file is specified, arguments from it are parsed as well.
+/// Like with invalid command-line arguments, the process exits with an error
+/// message if the configuration file cannot be read or is malformed.
+///
 /// [`Args`]: struct.Args.html
 pub fn from_env_args() -> Args {
-    argh::from_env()
+    use argh::FromArgs as _;
+
+    let mut args = std::env::args_os()
+        .map(|arg| arg.into_string())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|arg| {
+            eprintln!("Invalid utf8: {}", arg.to_string_lossy());
+            std::process::exit(1)
+        });
+
+    if args.is_empty() {
+        eprintln!("No program name, argv is empty");
+        std::process::exit(1)
+    }
+    let cmd_args = args.split_off(1);
+    let cmd = std::path::Path::new(&args[0]).file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&args[0]);
+
+    let config_path = cmd_args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|idx| cmd_args.get(idx + 1))
+        .cloned();
+
+    let mut all_args = Vec::new();
+    let mut cmd_args = cmd_args;
+    if let Some(config_path) = config_path {
+        let config = config::Config::load(&config_path).unwrap_or_else(|error| {
+            eprintln!("Invalid config file '{config_path}': {error}");
+            std::process::exit(1)
+        });
+
+        // Arguments from the configuration file have to go first, as they can
+        // only belong to the top-level command and not to subcommands.
+        all_args.extend(config.to_args(&cmd_args));
+
+        // Negated switches (e.g. `--no-log-to-stdout`) are not known to the
+        // command-line parser, they only cancel entries of the configuration
+        // file, so we have to drop them now.
+        cmd_args.retain(|arg| !config.is_negated_switch(arg));
+    }
+    all_args.extend(cmd_args);
+
+    let all_args = all_args.iter().map(String::as_str).collect::<Vec<_>>();
+    Args::from_args(&[cmd], &all_args).unwrap_or_else(|early_exit| {
+        std::process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!("{}\nRun {} --help for more information.", early_exit.output, cmd);
+                1
+            }
+        })
+    })
+}
+
+/// Parses a transport specification to a `Transport` object.
+fn parse_transport(value: &str) -> Result<Transport, String> {
+    match value {
+        "fleetspeak" => return Ok(Transport::Fleetspeak),
+        "stdio" => return Ok(Transport::Stdio),
+        _ => (),
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        if let Some(path) = value.strip_prefix("unix:") {
+            return Ok(Transport::UnixSocket(std::path::PathBuf::from(path)));
+        }
+    }
+
+    Err(format!("unknown transport '{value}'"))
+}
+
+/// Parses an action name (e.g. `get_file_hash`) to an `Action` object.
+fn parse_action(value: &str) -> Result<rrg_proto::v2::rrg::Action, String> {
+    use protobuf::ProtobufEnum as _;
+
+    rrg_proto::v2::rrg::Action::values().iter()
+        .find(|action| action.descriptor().name().eq_ignore_ascii_case(value))
+        .copied()
+        .ok_or_else(|| format!("unknown action '{value}'"))
+}
+
+/// Parses a format name to a `Format` object.
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "text" => Ok(Format::Text),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("unknown format '{value}'")),
+    }
+}
+
+/// Parses a human-friendly size description (e.g. `512KiB`) to a byte count.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, unit) = match value.find(|char: char| !char.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, ""),
+    };
+
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "KiB" => 1024,
+        "MiB" => 1024 * 1024,
+        "GiB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit '{unit}'")),
+    };
+
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{value}'"))
 }
 
 /// Parses a human-friendly duration description to a `Duration` object.
@@ -0,0 +1,505 @@
+//! Support for specifying command-line arguments in a configuration file.
+//!
+//! Configuration files use a subset of the [TOML] format: a flat list of
+//! `key = value` pairs (one per line) where keys are names of command-line
+//! options (with or without the leading dashes, underscores are allowed in
+//! place of dashes). Values can be strings, integers, booleans or single-line
+//! arrays of these. Comments start with `#` and tables are not supported.
+//!
+//! ```toml
+//! # Agent configuration.
+//! heartbeat-rate = "10s"
+//! verbosity = "info"
+//! log-to-stdout = true
+//! max_concurrent_actions = 8
+//! ```
+//!
+//! Entries are turned into command-line arguments: boolean entries correspond
+//! to switches (present only if the value is `true`), array entries correspond
+//! to options that can be repeated and all other entries correspond to regular
+//! options. This way every command-line option is automatically available in
+//! configuration files as well. For example, the actions that the agent is
+//! allowed to execute can be restricted with the `enable-action` and
+//! `disable-action` entries:
+//!
+//! ```toml
+//! disable-action = ["get_filesystem_timeline", "scan_files"]
+//! ```
+//!
+//! Command-line arguments take precedence over configuration entries. Switches
+//! enabled in the configuration file can be turned off on the command line by
+//! prefixing them with `no-` (e.g. `--no-log-to-stdout`).
+//!
+//! The same goes for default limits of requests that do not specify their own
+//! and for paths that actions are not allowed to access:
+//!
+//! ```toml
+//! default-real-time-limit = "1h"
+//! default-network-bytes-limit = "512MiB"
+//! deny-path = ["/etc/shadow", "/root/.ssh"]
+//! ```
+//!
+//! Note that only options that exist on the command line are supported.
+//!
+//! [TOML]: https://toml.io
+
+/// A parsed configuration file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Configuration entries in the order they appear in the file.
+    entries: Vec<(String, Value)>,
+}
+
+/// A value of a single configuration entry.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Config {
+
+    /// Loads and parses the configuration file at the given path.
+    pub fn load<P>(path: P) -> Result<Config, Box<dyn std::error::Error>>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Config::parse(&text)?)
+    }
+
+    /// Parses the configuration from the given text.
+    pub fn parse(text: &str) -> Result<Config, ParseError> {
+        let mut entries = Vec::<(String, Value)>::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let error = |message: String| ParseError {
+                line: idx + 1,
+                message,
+            };
+
+            let mut parser = LineParser::new(line);
+            if parser.is_done() {
+                continue;
+            }
+            if parser.peek() == Some('[') {
+                return Err(error(String::from("tables are not supported")));
+            }
+
+            let key = parser.key().map_err(error)?;
+            if entries.iter().any(|(other, _)| *other == key) {
+                return Err(error(format!("duplicated key '{key}'")));
+            }
+
+            parser.expect('=').map_err(error)?;
+            let value = parser.value().map_err(error)?;
+            if !parser.is_done() {
+                return Err(error(String::from("unexpected trailing characters")));
+            }
+
+            entries.push((key, value));
+        }
+
+        Ok(Config {
+            entries,
+        })
+    }
+
+    /// Converts the configuration into command-line arguments.
+    ///
+    /// Entries corresponding to options that appear in `overrides` (usually
+    /// the actual command-line arguments) are skipped, so that values given
+    /// explicitly take precedence over those in the configuration file. This
+    /// includes boolean entries that are negated (see [`is_negated_switch`]).
+    ///
+    /// [`is_negated_switch`]: Config::is_negated_switch
+    pub fn to_args(&self, overrides: &[String]) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for (key, value) in &self.entries {
+            let flag = format!("--{key}");
+            if overrides.contains(&flag) {
+                continue;
+            }
+            if matches!(value, Value::Boolean(_)) && overrides.contains(&format!("--no-{key}")) {
+                continue;
+            }
+
+            use Value::*;
+            match value {
+                Boolean(true) => args.push(flag),
+                Boolean(false) => (),
+                Array(values) => for value in values {
+                    args.push(flag.clone());
+                    args.push(value.to_arg());
+                },
+                value => {
+                    args.push(flag);
+                    args.push(value.to_arg());
+                }
+            }
+        }
+
+        args
+    }
+
+    /// Checks whether the given argument negates a switch of the configuration.
+    ///
+    /// Negated switches are of the form `--no-<key>` where `<key>` is a boolean
+    /// entry of the configuration file. They do not correspond to any actual
+    /// command-line option and only exist to turn switches off.
+    pub fn is_negated_switch(&self, arg: &str) -> bool {
+        let key = match arg.strip_prefix("--no-") {
+            Some(key) => key,
+            None => return false,
+        };
+
+        self.entries.iter()
+            .any(|(other, value)| other == key && matches!(value, Value::Boolean(_)))
+    }
+}
+
+impl Value {
+
+    /// Returns the representation of the value as a command-line argument.
+    fn to_arg(&self) -> String {
+        use Value::*;
+
+        match self {
+            String(value) => value.clone(),
+            Integer(value) => value.to_string(),
+            Boolean(value) => value.to_string(),
+            // Nested arrays are rejected by the parser.
+            Array(_) => unreachable!(),
+        }
+    }
+}
+
+/// A parser of a single line of the configuration file.
+struct LineParser<'s> {
+    /// Remaining characters of the line.
+    chars: std::iter::Peekable<std::str::Chars<'s>>,
+}
+
+impl<'s> LineParser<'s> {
+
+    /// Creates a new parser for the given line.
+    fn new(line: &'s str) -> LineParser<'s> {
+        LineParser {
+            chars: line.chars().peekable(),
+        }
+    }
+
+    /// Returns the next meaningful character without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        while let Some(char) = self.chars.peek() {
+            if !char.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+
+        match self.chars.peek() {
+            Some('#') => None,
+            char => char.copied(),
+        }
+    }
+
+    /// Checks whether there are no more meaningful characters in the line.
+    fn is_done(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// Consumes the given character or fails if it is not the next one.
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(char) if char == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            Some(char) => Err(format!("expected '{expected}', found '{char}'")),
+            None => Err(format!("expected '{expected}', found end of line")),
+        }
+    }
+
+    /// Parses an entry key and normalizes it to the option name.
+    fn key(&mut self) -> Result<String, String> {
+        let key = match self.peek() {
+            Some('"') => self.string()?,
+            _ => {
+                let mut key = String::new();
+                while let Some(&char) = self.chars.peek() {
+                    if !(char.is_ascii_alphanumeric() || char == '-' || char == '_') {
+                        break;
+                    }
+                    key.push(char);
+                    self.chars.next();
+                }
+                key
+            }
+        };
+
+        let key = key.trim_start_matches('-').replace('_', "-");
+        if key.is_empty() {
+            return Err(String::from("missing key"));
+        }
+
+        Ok(key)
+    }
+
+    /// Parses an entry value.
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some(_) => self.scalar(),
+            None => Err(String::from("missing value")),
+        }
+    }
+
+    /// Parses a single-line array of non-array values.
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+
+        let mut values = Vec::new();
+        loop {
+            if self.peek() == Some(']') {
+                break;
+            }
+
+            match self.value()? {
+                Value::Array(_) => {
+                    return Err(String::from("nested arrays are not supported"));
+                }
+                value => values.push(value),
+            }
+
+            if self.peek() == Some(',') {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+
+        self.expect(']')?;
+        Ok(Value::Array(values))
+    }
+
+    /// Parses an unquoted value (a boolean or an integer).
+    fn scalar(&mut self) -> Result<Value, String> {
+        let mut repr = String::new();
+        while let Some(&char) = self.chars.peek() {
+            if !(char.is_ascii_alphanumeric() || char == '-' || char == '+' || char == '_') {
+                break;
+            }
+            repr.push(char);
+            self.chars.next();
+        }
+
+        match repr.as_str() {
+            "true" => return Ok(Value::Boolean(true)),
+            "false" => return Ok(Value::Boolean(false)),
+            _ => (),
+        }
+
+        match repr.replace('_', "").parse::<i64>() {
+            Ok(value) => Ok(Value::Integer(value)),
+            Err(_) => Err(format!("invalid value '{repr}'")),
+        }
+    }
+
+    /// Parses a basic (double-quoted) or literal (single-quoted) string.
+    fn string(&mut self) -> Result<String, String> {
+        let quote = match self.chars.next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(String::from("expected a string")),
+        };
+
+        let mut value = String::new();
+        loop {
+            let char = match self.chars.next() {
+                Some(char) => char,
+                None => return Err(String::from("unterminated string")),
+            };
+
+            match char {
+                _ if char == quote => return Ok(value),
+                '\\' if quote == '"' => value.push(self.escape()?),
+                _ => value.push(char),
+            }
+        }
+    }
+
+    /// Parses an escape sequence of a basic string (without the backslash).
+    fn escape(&mut self) -> Result<char, String> {
+        let len = match self.chars.next() {
+            Some('"') => return Ok('"'),
+            Some('\\') => return Ok('\\'),
+            Some('n') => return Ok('\n'),
+            Some('r') => return Ok('\r'),
+            Some('t') => return Ok('\t'),
+            Some('u') => 4,
+            Some('U') => 8,
+            Some(char) => return Err(format!("invalid escape sequence '\\{char}'")),
+            None => return Err(String::from("unterminated string")),
+        };
+
+        let code = self.chars.by_ref().take(len).collect::<String>();
+        u32::from_str_radix(&code, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid unicode escape '{code}'"))
+    }
+}
+
+/// The error type for cases when parsing a configuration file fails.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Number of the line (starting from 1) at which the error occurred.
+    line: usize,
+    /// A human-readable description of the error.
+    message: String,
+}
+
+impl std::fmt::Display for ParseError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(text: &str, overrides: &[&str]) -> Vec<String> {
+        let overrides = overrides.iter()
+            .map(|arg| String::from(*arg))
+            .collect::<Vec<_>>();
+
+        Config::parse(text).unwrap().to_args(&overrides)
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(Config::parse("\n  # Comment.\n\n").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn parse_scalars() {
+        let config = Config::parse(r#"
+            heartbeat-rate = "10s" # Inline comment.
+            max_concurrent_actions = 1_024
+            log-to-stdout = true
+            "log-to-file" = 'C:\Logs\rrg.log'
+        "#).unwrap();
+
+        assert_eq!(config.entries, vec! {
+            (String::from("heartbeat-rate"), Value::String(String::from("10s"))),
+            (String::from("max-concurrent-actions"), Value::Integer(1024)),
+            (String::from("log-to-stdout"), Value::Boolean(true)),
+            (String::from("log-to-file"), Value::String(String::from("C:\\Logs\\rrg.log"))),
+        });
+    }
+
+    #[test]
+    fn parse_string_escapes() {
+        let config = Config::parse(r#"foo = "\"quux\"\t\u00e9\\""#).unwrap();
+        assert_eq!(config.entries, vec! {
+            (String::from("foo"), Value::String(String::from("\"quux\"\té\\"))),
+        });
+    }
+
+    #[test]
+    fn parse_array() {
+        let config = Config::parse(r#"foo = ["bar", 42, ]"#).unwrap();
+        assert_eq!(config.entries, vec! {
+            (String::from("foo"), Value::Array(vec! {
+                Value::String(String::from("bar")),
+                Value::Integer(42),
+            })),
+        });
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Config::parse("[section]").is_err());
+        assert!(Config::parse("foo").is_err());
+        assert!(Config::parse("foo =").is_err());
+        assert!(Config::parse("foo = bar").is_err());
+        assert!(Config::parse("foo = \"bar").is_err());
+        assert!(Config::parse("foo = \"bar\" baz").is_err());
+        assert!(Config::parse("foo = [[1]]").is_err());
+        assert!(Config::parse("foo = 1\nfoo = 2").is_err());
+    }
+
+    #[test]
+    fn parse_error_line() {
+        let error = Config::parse("foo = 1\n\nbar = ?").unwrap_err();
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn to_args_all() {
+        let text = r#"
+            verbosity = "debug"
+            max-concurrent-actions = 8
+            log-to-stdout = true
+            log-to-file = false
+            foo = ["bar", "baz"]
+        "#;
+
+        assert_eq!(args(text, &[]), vec! {
+            "--verbosity", "debug",
+            "--max-concurrent-actions", "8",
+            "--log-to-stdout",
+            "--foo", "bar",
+            "--foo", "baz",
+        });
+    }
+
+    #[test]
+    fn to_args_overrides() {
+        let text = r#"
+            verbosity = "debug"
+            max-concurrent-actions = 8
+        "#;
+
+        assert_eq!(args(text, &["--verbosity", "error"]), vec! {
+            "--max-concurrent-actions", "8",
+        });
+    }
+
+    #[test]
+    fn to_args_negated_switch() {
+        let text = r#"
+            verbosity = "debug"
+            log-to-stdout = true
+        "#;
+
+        assert_eq!(args(text, &["--no-log-to-stdout"]), vec! {
+            "--verbosity", "debug",
+        });
+    }
+
+    #[test]
+    fn is_negated_switch() {
+        let config = Config::parse(r#"
+            verbosity = "debug"
+            log-to-stdout = true
+            log-to-syslog = false
+        "#).unwrap();
+
+        assert!(config.is_negated_switch("--no-log-to-stdout"));
+        assert!(config.is_negated_switch("--no-log-to-syslog"));
+        assert!(!config.is_negated_switch("--no-verbosity"));
+        assert!(!config.is_negated_switch("--no-log-to-file"));
+        assert!(!config.is_negated_switch("--log-to-stdout"));
+    }
+}
@@ -25,6 +25,94 @@ pub struct Entry {
     pub metadata: Metadata,
 }
 
+/// Paths (and their canonical forms) that actions are not allowed to access.
+static DENIED_PATHS: std::sync::RwLock<Vec<PathBuf>> = std::sync::RwLock::new(Vec::new());
+
+/// Denies access to the given path and everything under it.
+///
+/// Denied paths are never yielded by the iterators of this module and listing
+/// or walking them fails with a "permission denied" error. Actions that access
+/// paths given to them directly are expected to verify them with [`check_access`].
+///
+/// This function should be called only during the agent initialization.
+pub fn deny<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+
+    let mut denied_paths = DENIED_PATHS.write()
+        .expect("failed to acquire denied paths lock");
+
+    denied_paths.push(normalize(path));
+    // The path is denied both as specified and in its canonical form, so that
+    // it cannot be accessed through symlinks. Paths that do not exist (yet)
+    // cannot be canonicalized, so only the former is possible for them.
+    if let Ok(canonical_path) = path.canonicalize() {
+        denied_paths.push(canonical_path);
+    }
+}
+
+/// Checks whether access to the given path has been denied.
+///
+/// Both the path itself (with `.` and `..` components resolved) and its
+/// canonical form (if it exists) are verified against the denied paths.
+///
+/// See [`deny`] for more details.
+pub fn is_denied<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+
+    let denied_paths = DENIED_PATHS.read()
+        .expect("failed to acquire denied paths lock");
+    if denied_paths.is_empty() {
+        return false;
+    }
+
+    let is_under_denied = |path: &Path| {
+        denied_paths.iter().any(|denied_path| path.starts_with(denied_path))
+    };
+
+    if is_under_denied(&normalize(path)) {
+        return true;
+    }
+
+    match path.canonicalize() {
+        Ok(canonical_path) => is_under_denied(&canonical_path),
+        Err(_) => false,
+    }
+}
+
+/// Fails with a "permission denied" error if access to the path is denied.
+///
+/// See [`deny`] for more details.
+pub fn check_access<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+
+    if is_denied(path) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("access to '{}' is denied", path.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves `.` and `..` components of the given path lexically.
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+
+    result
+}
+
 /// Returns a deep iterator over entries within a directory.
 ///
 /// The iterator will recursively visit all subdirectories under `root` and
@@ -54,22 +142,72 @@ pub struct Entry {
 /// assert!(items.contains(&PathBuf::from("/usr/lib")));
 /// ```
 pub fn walk_dir<P: AsRef<Path>>(root: P) -> std::io::Result<WalkDir> {
+    walk_dir_with_opts(root, WalkDirOpts::default())
+}
+
+/// Returns a deep iterator over entries within a directory.
+///
+/// This is a variant of the [`walk_dir`] function that allows customization of
+/// the traversal. Refer to its documentation for more details.
+///
+/// [`walk_dir`]: fn.walk_dir.html
+pub fn walk_dir_with_opts<P>(root: P, opts: WalkDirOpts) -> std::io::Result<WalkDir>
+where
+    P: AsRef<Path>,
+{
+    check_access(&root)?;
+
     let metadata = std::fs::symlink_metadata(&root)?;
-    let pending = vec!(list_dir(&root)?);
+    let pending = match opts.max_depth {
+        Some(0) => vec!(),
+        _ => vec!(list_dir(&root)?),
+    };
 
     #[cfg(target_family = "unix")]
     let dev = std::os::unix::fs::MetadataExt::dev(&metadata);
 
+    // When following symlinks, we need to keep track of directories that we
+    // have already visited to avoid infinite loops.
+    let mut visited = std::collections::HashSet::new();
+    if opts.follow_symlinks {
+        visited.insert(root.as_ref().canonicalize()?);
+    }
+
     Ok(WalkDir {
         root: Some(Entry {
             path: root.as_ref().to_path_buf(),
-            metadata: metadata,
+            metadata,
         }),
-        pending: pending,
-        #[cfg(target_family = "unix")] dev: dev,
+        pending,
+        opts,
+        visited,
+        #[cfg(target_family = "unix")] dev,
     })
 }
 
+/// Options and flags that configure the recursive directory traversal.
+#[derive(Clone, Copy, Debug)]
+pub struct WalkDirOpts {
+    /// Whether to skip directories on devices other than the one of the root.
+    pub one_device: bool,
+    /// Whether to descend into directories that symlinks point to.
+    pub follow_symlinks: bool,
+    /// Maximum depth of the traversal (relative to the root, unlimited if not
+    /// specified).
+    pub max_depth: Option<u32>,
+}
+
+impl Default for WalkDirOpts {
+
+    fn default() -> WalkDirOpts {
+        WalkDirOpts {
+            one_device: true,
+            follow_symlinks: false,
+            max_depth: None,
+        }
+    }
+}
+
 /// Returns a shallow iterator over entries within a directory.
 ///
 /// This function is very similar to the standard `std::fs::read_dir`, except
@@ -95,13 +233,44 @@ pub fn walk_dir<P: AsRef<Path>>(root: P) -> std::io::Result<WalkDir> {
 /// assert!(items.contains(&PathBuf::from("/tmp")));
 /// ```
 pub fn list_dir<P: AsRef<Path>>(path: P) -> std::io::Result<ListDir> {
-    let iter = std::fs::read_dir(path)?;
+    let iter = try_list_dir(path)?;
 
     Ok(ListDir {
         iter: iter,
     })
 }
 
+/// Returns a shallow iterator over entries within a directory (with errors).
+///
+/// This function is similar to [`list_dir`], except that entry-related errors
+/// are not ignored but reported by the returned iterator instead.
+///
+/// # Errors
+///
+/// Constructing the iterator can fail if e.g. the specified path does not
+/// represent a directory or does not exist. Failures to collect information
+/// about particular entries are yielded as [`ListDirError`] items.
+///
+/// # Examples
+///
+/// ```no_run
+/// for entry in rrg::fs::try_list_dir("/").unwrap() {
+///     match entry {
+///         Ok(entry) => println!("{}", entry.path.display()),
+///         Err(error) => println!("{}", error),
+///     }
+/// }
+/// ```
+pub fn try_list_dir<P: AsRef<Path>>(path: P) -> std::io::Result<TryListDir> {
+    check_access(&path)?;
+
+    let iter = std::fs::read_dir(path)?;
+
+    Ok(TryListDir {
+        iter,
+    })
+}
+
 /// Iterator over entries in all subdirectories.
 ///
 /// This iterator will recursively descent to all subdirectories and yield
@@ -118,6 +287,8 @@ pub fn list_dir<P: AsRef<Path>>(path: P) -> std::io::Result<ListDir> {
 pub struct WalkDir {
     root: Option<Entry>,
     pending: Vec<ListDir>,
+    opts: WalkDirOpts,
+    visited: std::collections::HashSet<PathBuf>,
     #[cfg(target_family = "unix")] dev: u64,
 }
 
@@ -146,13 +317,57 @@ impl WalkDir {
         None
     }
 
+    fn should_descend(&mut self, entry: &Entry) -> bool {
+        if let Some(max_depth) = self.opts.max_depth {
+            if self.pending.len() >= max_depth as usize {
+                return false;
+            }
+        }
+
+        let target_metadata;
+        let metadata = if self.opts.follow_symlinks && entry.metadata.is_symlink() {
+            // Symlinks can be dangling, in which case there is simply nothing
+            // to descend into.
+            target_metadata = match std::fs::metadata(&entry.path) {
+                Ok(metadata) => metadata,
+                Err(_) => return false,
+            };
+            &target_metadata
+        } else {
+            &entry.metadata
+        };
+
+        if !metadata.is_dir() {
+            return false;
+        }
+        if self.opts.one_device && !self.same_dev(metadata) {
+            return false;
+        }
+
+        if self.opts.follow_symlinks {
+            let path = match entry.path.canonicalize() {
+                Ok(path) => path,
+                Err(error) => {
+                    warn!("failed to resolve '{}': {}", entry.path.display(), error);
+                    return false;
+                }
+            };
+
+            if !self.visited.insert(path) {
+                return false;
+            }
+        }
+
+        true
+    }
+
     #[cfg(target_family = "unix")]
-    fn same_dev(&self, entry: &Entry) -> bool {
-        self.dev == std::os::unix::fs::MetadataExt::dev(&entry.metadata)
+    fn same_dev(&self, metadata: &Metadata) -> bool {
+        self.dev == std::os::unix::fs::MetadataExt::dev(metadata)
     }
 
     #[cfg(target_family = "windows")]
-    fn same_dev(&self, _entry: &Entry) -> bool {
+    fn same_dev(&self, _metadata: &Metadata) -> bool {
         true
     }
 }
@@ -168,7 +383,7 @@ impl std::iter::Iterator for WalkDir {
 
         let entry = self.pop()?;
 
-        if entry.metadata.is_dir() && self.same_dev(&entry) {
+        if self.should_descend(&entry) {
             self.push(&entry);
         }
 
@@ -189,7 +404,7 @@ impl std::iter::Iterator for WalkDir {
 ///
 /// [`list_dir`]: fn.list_dir.html
 pub struct ListDir {
-    iter: std::fs::ReadDir,
+    iter: TryListDir,
 }
 
 impl std::iter::Iterator for ListDir {
@@ -198,30 +413,104 @@ impl std::iter::Iterator for ListDir {
 
     fn next(&mut self) -> Option<Entry> {
         for entry in &mut self.iter {
-            let entry = match entry {
+            match entry {
+                Ok(entry) => return Some(entry),
+                Err(error) => warn!("{}", error),
+            }
+        }
+
+        None
+    }
+}
+
+/// Iterator over the entries in a directory, including errors.
+///
+/// This iterator is very similar to the standard `ReadDir` iterator, except
+/// that the entries it yields are guaranteed to have valid metadata objects
+/// attached. Errors that occurred when collecting information about particular
+/// entries are reported as [`ListDirError`] items.
+///
+/// The iterator can be constructed with the [`try_list_dir`] function.
+///
+/// [`try_list_dir`]: fn.try_list_dir.html
+pub struct TryListDir {
+    iter: std::fs::ReadDir,
+}
+
+impl std::iter::Iterator for TryListDir {
+
+    type Item = Result<Entry, ListDirError>;
+
+    fn next(&mut self) -> Option<Result<Entry, ListDirError>> {
+        let entry = loop {
+            let entry = match self.iter.next()? {
                 Ok(entry) => entry,
-                Err(error) => {
-                    warn!("directory iteration error: {}", error);
-                    continue
-                },
+                Err(error) => return Some(Err(ListDirError {
+                    path: None,
+                    error,
+                })),
             };
 
-            let path = entry.path();
-            let metadata = match entry.metadata() {
-                Ok(metadata) => metadata,
-                Err(error) => {
-                    warn!("failed to stat '{}': {}", path.display(), error);
-                    continue
-                },
-            };
+            // Denied entries are skipped as if they did not exist at all.
+            if !is_denied(entry.path()) {
+                break entry;
+            }
+        };
+
+        let path = entry.path();
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(error) => return Some(Err(ListDirError {
+                path: Some(path),
+                error,
+            })),
+        };
+
+        Some(Ok(Entry {
+            path,
+            metadata,
+        }))
+    }
+}
+
+/// An error type for failures to collect information about directory entries.
+#[derive(Debug)]
+pub struct ListDirError {
+    /// A path to the entry that caused the error (if it is known).
+    path: Option<PathBuf>,
+    /// A cause of the error.
+    error: std::io::Error,
+}
+
+impl ListDirError {
 
-            return Some(Entry {
-                path: path,
-                metadata: metadata,
-            });
+    /// Returns the path to the entry that caused the error (if it is known).
+    ///
+    /// The path is not known if the error occurred during directory iteration
+    /// rather than when collecting metadata of a particular entry.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl std::fmt::Display for ListDirError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => {
+                write!(fmt, "failed to stat '{}': {}", path.display(), self.error)
+            }
+            None => {
+                write!(fmt, "directory iteration error: {}", self.error)
+            }
         }
+    }
+}
 
-        None
+impl std::error::Error for ListDirError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
     }
 }
 
@@ -328,6 +617,33 @@ mod tests {
         assert_eq!(results[1].path, tempdir.path().join("што й па мору"));
     }
 
+    #[test]
+    fn test_try_list_dir_non_existing() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let iter = try_list_dir(tempdir.path().join("foo"));
+        assert!(iter.is_err());
+    }
+
+    #[test]
+    fn test_try_list_dir_with_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        File::create(tempdir.path().join("abc")).unwrap();
+        std::fs::create_dir(tempdir.path().join("def")).unwrap();
+
+        let mut results = try_list_dir(&tempdir).unwrap()
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        results.sort_by_key(|entry| entry.path.clone());
+
+        assert_eq!(results.len(), 2);
+
+        assert_eq!(results[0].path, tempdir.path().join("abc"));
+        assert!(results[0].metadata.is_file());
+
+        assert_eq!(results[1].path, tempdir.path().join("def"));
+        assert!(results[1].metadata.is_dir());
+    }
+
     #[test]
     fn test_walk_dir_non_existing() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -526,6 +842,104 @@ mod tests {
         assert!(results[2].metadata.file_type().is_symlink());
     }
 
+    // Symlinking is supported only on Unix-like systems.
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_walk_dir_with_opts_follow_symlinks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("root");
+        let outside = tempdir.path().join("outside");
+        let symlink = root.join("link");
+
+        std::fs::create_dir(&root).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        File::create(outside.join("foo")).unwrap();
+        std::os::unix::fs::symlink(&outside, &symlink).unwrap();
+
+        let opts = WalkDirOpts {
+            follow_symlinks: true,
+            ..WalkDirOpts::default()
+        };
+
+        let mut results = walk_dir_with_opts(&root, opts).unwrap()
+            .collect::<Vec<_>>();
+        results.sort_by_key(|entry| entry.path.clone());
+
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0].path, root);
+        assert_eq!(results[1].path, symlink);
+        assert!(results[1].metadata.file_type().is_symlink());
+        assert_eq!(results[2].path, symlink.join("foo"));
+        assert!(results[2].metadata.file_type().is_file());
+    }
+
+    // Symlinking is supported only on Unix-like systems.
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_walk_dir_with_opts_follow_circular_symlinks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path().join("foo");
+        let symlink = tempdir.path().join("foo").join("bar");
+
+        std::fs::create_dir(&dir).unwrap();
+        std::os::unix::fs::symlink(&dir, &symlink).unwrap();
+
+        let opts = WalkDirOpts {
+            follow_symlinks: true,
+            ..WalkDirOpts::default()
+        };
+
+        let mut results = walk_dir_with_opts(&tempdir, opts).unwrap()
+            .collect::<Vec<_>>();
+        results.sort_by_key(|entry| entry.path.clone());
+
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0].path, tempdir.path());
+        assert_eq!(results[1].path, dir);
+        assert_eq!(results[2].path, symlink);
+    }
+
+    #[test]
+    fn test_walk_dir_with_opts_max_depth() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tempdir.path().join("a").join("b").join("c"))
+            .unwrap();
+
+        let opts = WalkDirOpts {
+            max_depth: Some(2),
+            ..WalkDirOpts::default()
+        };
+
+        let mut results = walk_dir_with_opts(&tempdir, opts).unwrap()
+            .collect::<Vec<_>>();
+        results.sort_by_key(|entry| entry.path.clone());
+
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0].path, tempdir.path());
+        assert_eq!(results[1].path, tempdir.path().join("a"));
+        assert_eq!(results[2].path, tempdir.path().join("a").join("b"));
+    }
+
+    #[test]
+    fn test_walk_dir_with_opts_max_depth_zero() {
+        let tempdir = tempfile::tempdir().unwrap();
+        File::create(tempdir.path().join("foo")).unwrap();
+
+        let opts = WalkDirOpts {
+            max_depth: Some(0),
+            ..WalkDirOpts::default()
+        };
+
+        let results = walk_dir_with_opts(&tempdir, opts).unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, tempdir.path());
+    }
+
     // macOS mangles Unicode-specific characters in filenames.
     #[cfg_attr(target_os = "macos", ignore)]
     #[test]
@@ -552,4 +966,72 @@ mod tests {
         assert_eq!(results.len(), 2);
         assert_eq!(results[1].metadata.len(), 9);
     }
+
+    #[test]
+    fn test_deny_nested() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("denied")).unwrap();
+        File::create(tempdir.path().join("denied").join("foo")).unwrap();
+
+        deny(tempdir.path().join("denied"));
+
+        assert!(is_denied(tempdir.path().join("denied")));
+        assert!(is_denied(tempdir.path().join("denied").join("foo")));
+        assert!(is_denied(tempdir.path().join("denied").join("bar")));
+        assert!(is_denied(tempdir.path().join("foo").join("..").join("denied")));
+        assert!(!is_denied(tempdir.path()));
+        assert!(!is_denied(tempdir.path().join("denied-not")));
+
+        let error = check_access(tempdir.path().join("denied")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(check_access(tempdir.path()).is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_deny_symlink() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("denied")).unwrap();
+        std::os::unix::fs::symlink(tempdir.path().join("denied"), tempdir.path().join("link"))
+            .unwrap();
+
+        deny(tempdir.path().join("denied"));
+
+        assert!(is_denied(tempdir.path().join("link")));
+    }
+
+    #[test]
+    fn test_deny_list_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tempdir.path().join("denied")).unwrap();
+        File::create(tempdir.path().join("allowed")).unwrap();
+
+        deny(tempdir.path().join("denied"));
+
+        let results = list_dir(&tempdir).unwrap().collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, tempdir.path().join("allowed"));
+
+        assert!(list_dir(tempdir.path().join("denied")).is_err());
+    }
+
+    #[test]
+    fn test_deny_walk_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tempdir.path().join("allowed").join("denied")).unwrap();
+        File::create(tempdir.path().join("allowed").join("denied").join("foo")).unwrap();
+        File::create(tempdir.path().join("allowed").join("bar")).unwrap();
+
+        deny(tempdir.path().join("allowed").join("denied"));
+
+        let mut results = walk_dir(&tempdir).unwrap().collect::<Vec<_>>();
+        results.sort_by_key(|entry| entry.path.clone());
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].path, tempdir.path());
+        assert_eq!(results[1].path, tempdir.path().join("allowed"));
+        assert_eq!(results[2].path, tempdir.path().join("allowed").join("bar"));
+
+        assert!(walk_dir(tempdir.path().join("allowed").join("denied")).is_err());
+    }
 }
@@ -18,11 +18,12 @@ mod request;
 pub mod response;
 
 pub mod startup; // TODO(@panhania): Hide this module.
+pub mod transport;
 
 // Consider moving these to a separate submodule.
-#[cfg(feature = "action-timeline")]
+#[cfg(any(feature = "action-timeline", feature = "action-get_filesystem_timeline", feature = "spool"))]
 pub mod chunked;
-#[cfg(feature = "action-timeline")]
+#[cfg(any(feature = "action-timeline", feature = "action-get_filesystem_timeline", feature = "spool"))]
 pub mod gzchunked;
 
 pub use request::{Request, RequestId};
@@ -33,38 +34,197 @@ pub use response::{ResponseBuilder, ResponseId, Sink};
 /// This function should be called only once (at the very beginning of the
 /// process lifetime).
 pub fn init(args: &crate::args::Args) {
-    log::init(args)
+    log::init(args);
+
+    for path in &args.denied_paths {
+        fs::deny(path);
+    }
 }
 
 /// Enters the agent's main loop and waits for messages.
 ///
 /// It will poll for messages from the GRR server and should consume very few
 /// resources when idling. Once it picks a message, it dispatches it to an
-/// appropriate action handler (which should take care of sending heartbeat
-/// signals if expected to be long-running) and goes back to idling when action
-/// execution is finished.
+/// appropriate action handler (heartbeat signals are sent in the background
+/// while the action is executing) and goes back to idling when action execution
+/// is finished.
 ///
-/// This function never terminates and panics only if something went very wrong
-/// (e.g. the Fleetspeak connection has been broken). All non-critical errors
-/// are going to be handled carefully, notifying the server about the failure if
+/// Requests are executed by a pool of worker threads, so that long-running
+/// actions do not block other requests. The size of the pool is determined by
+/// the `--max-concurrent-actions` flag. Each request is executed by a single
+/// worker, so responses to a particular request are always sent in order. If
+/// all the workers are busy, requests are queued until one of them finishes.
+/// Requests can be cancelled as soon as they are received, so that cancelling
+/// a queued request makes it finish without being executed at all (and
+/// cancellation requests themselves are always executed right away). Requests
+/// that do not specify resource limits are executed with the default ones (see
+/// the `--default-*-limit` flags).
+///
+/// This function terminates only once the transport is closed (which never
+/// happens for Fleetspeak) and panics only if something went very wrong (e.g.
+/// the Fleetspeak connection has been broken). All non-critical errors are
+/// going to be handled carefully, notifying the server about the failure if
 /// appropriate.
-pub fn listen(args: &crate::args::Args) {
-    loop {
-        use ::log::{info, error};
-
-        let request = match Request::receive(args.heartbeat_rate) {
-            Ok(request) => request,
-            Err(error) => {
-                error!("failed to receive a request: {}", error);
-                continue
+pub fn listen<T>(args: &crate::args::Args, transport: &T)
+where
+    T: crate::transport::Transport + Sync,
+{
+    use ::log::{info, error};
+
+    // The queue is unbounded, so that we keep receiving requests (and most
+    // importantly cancellation requests) even if all the workers are busy.
+    let (request_sender, request_receiver) = std::sync::mpsc::channel();
+    let request_receiver = std::sync::Mutex::new(request_receiver);
+
+    std::thread::scope(|scope| {
+        for _ in 0..args.max_concurrent_actions.get() {
+            scope.spawn(|| loop {
+                let (request, cancellation): (Request, _) = match request_receiver.lock()
+                    .expect("failed to acquire request receiver lock")
+                    .recv()
+                {
+                    Ok(queued) => queued,
+                    // The sender has been dropped, there will be no more
+                    // requests to execute.
+                    Err(_) => return,
+                };
+                let request_id = request.id();
+
+                session::FleetspeakSession::dispatch(transport, args.heartbeat_rate, request, cancellation);
+                info!("finished handling request '{}'", request_id);
+            });
+        }
+
+        while let Some(request) = Request::receive(transport) {
+            let mut request = match request {
+                Ok(request) => request,
+                Err(error) => {
+                    error!("failed to receive a request: {}", error);
+                    reject(transport, error);
+                    continue
+                }
+            };
+            let request_id = request.id();
+            match request.action() {
+                Ok(action) => info!("received request '{}': {:?}", request_id, action),
+                Err(error) => info!("received request '{}': {}", request_id, error),
             }
-        };
-        let request_id = request.id();
-        info!("received request '{}': {:?}", request_id, request.action());
 
-        session::FleetspeakSession::dispatch(request);
-        info!("finished handling request '{}'", request_id);
+            if let Ok(action) = request.action() {
+                if !args.is_action_enabled(action.into()) {
+                    error!("refusing request '{}': action disabled", request_id);
+                    refuse(transport, request_id, session::Error::disabled_action(action));
+                    continue;
+                }
+            }
+
+            request.apply_default_limits(args);
+
+            // The request becomes cancellable right away (and not only once a
+            // worker picks it up), so that queued requests can be cancelled.
+            let cancellation = session::Cancellation::new(request_id);
+
+            // Cancellation requests are executed immediately instead of waiting
+            // for a free worker: otherwise they would get stuck behind the very
+            // requests they are supposed to cancel.
+            #[cfg(feature = "action-cancel_request")]
+            {
+                if let Ok(request::Action::CancelRequest) = request.action() {
+                    session::FleetspeakSession::dispatch(transport, args.heartbeat_rate, request, cancellation);
+                    info!("finished handling request '{}'", request_id);
+                    continue;
+                }
+            }
+
+            request_sender.send((request, cancellation))
+                // Workers stop only once the sender is dropped, so it can fail
+                // only if all of them panicked.
+                .expect("failed to hand over a request to a worker");
+        }
+
+        // Dropping the sender makes the workers finish once they are done with
+        // the requests they are currently executing.
+        drop(request_sender);
+    });
+}
+
+/// Runs a single action locally and prints its results to standard output.
+///
+/// This is an alternative to [`listen`] that does not communicate with the
+/// server at all. It is intended for running actions by hand, e.g. to reproduce
+/// agent behaviour on a particular host while debugging a flow.
+///
+/// # Errors
+///
+/// This function will return an error if the action or its arguments are not
+/// valid or if the action execution failed (note that in the latter case the
+/// error is also printed as part of the action status).
+pub fn run_action(args: &crate::args::RunActionArgs) -> Result<(), Box<dyn std::error::Error>> {
+    use protobuf::ProtobufEnum as _;
+
+    let action_name = args.action.to_ascii_lowercase();
+    let action = rrg_proto::v2::rrg::Action::values().iter()
+        .find(|action| {
+            action.descriptor().name().eq_ignore_ascii_case(&action_name)
+        })
+        .ok_or_else(|| format!("unknown action '{}'", args.action))?;
+
+    let args_type = format!("rrg.action.{action_name}.Args");
+    let args_bytes = match args.args_format {
+        args::Format::Text => rrg_proto::text::text_to_bytes(&args_type, &args.args)?,
+        args::Format::Json => rrg_proto::text::json_to_bytes(&args_type, &args.args)?,
+    };
+
+    let mut args_any = protobuf::well_known_types::Any::new();
+    args_any.set_type_url(format!("type.googleapis.com/{args_type}"));
+    args_any.set_value(args_bytes);
+
+    let mut request_proto = rrg_proto::v2::rrg::Request::new();
+    request_proto.set_action(*action);
+    request_proto.set_args(args_any);
+
+    let request = Request::try_from(request_proto)?;
+
+    let status = session::LocalSession::dispatch(
+        std::io::stdout(),
+        args.output_format,
+        request,
+    );
+    if status.has_error() {
+        return Err(status.get_error().get_message().into());
     }
+
+    Ok(())
+}
+
+/// Notifies the server about a request that could not be parsed.
+///
+/// If the identifier of the request is not known (e.g. the message was not a
+/// valid request at all), there is nobody to notify and the error is dropped.
+fn reject<T>(transport: &T, error: request::ParseRequestError)
+where
+    T: crate::transport::Transport,
+{
+    let request_id = match error.request_id() {
+        Some(request_id) => request_id,
+        None => return,
+    };
+
+    refuse(transport, request_id, error.into());
+}
+
+/// Notifies the server that the request will not be executed because of the
+/// given error.
+fn refuse<T>(transport: &T, request_id: RequestId, error: session::Error)
+where
+    T: crate::transport::Transport,
+{
+    ResponseBuilder::new(request_id).status(Err(error))
+        .send_unaccounted(transport)
+        // If we fail to send the response to the server, our connection is
+        // most likely broken and we should die. In general, this should not
+        // happen.
+        .expect("failed to send a status response");
 }
 
 /// Sends a system message with startup information to the GRR server.
@@ -76,10 +236,282 @@ pub fn listen(args: &crate::args::Args) {
 /// # Errors
 ///
 /// In case we fail to send startup information, this function will report an
-/// error. Note that by "send" we just mean pushing the message to Fleetspeak,
-/// whether Fleetspeak manages to reach the GRR server with it is a separate
-/// issue. Failure to push the message to Fleetspeak means that the pipe used
-/// for communication is most likely broken and we should quit.
-pub fn startup() -> Result<(), fleetspeak::WriteError> {
-    startup::startup()
+/// error. Note that by "send" we just mean pushing the message to the transport
+/// (e.g. Fleetspeak), whether it manages to reach the GRR server with it is a
+/// separate issue. Failure to push the message to the transport means that the
+/// pipe used for communication is most likely broken and we should quit.
+pub fn startup<T>(transport: &T) -> std::io::Result<()>
+where
+    T: crate::transport::Transport,
+{
+    startup::startup(transport)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn listen_unknown_action() {
+        use protobuf::Message as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xF00);
+        request.set_request_id(42);
+
+        let mut data = request.write_to_bytes().unwrap();
+        // The action field (number 3) with a value that is not a known action,
+        // serialized by hand as the generated code does not allow it.
+        data.extend_from_slice(&[3 << 3, 0xE7, 0x07]);
+
+        let transport = FakeTransport::new(vec![data]);
+        listen(&args(&[]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].0.get_flow_id(), 0xF00);
+        assert_eq!(statuses[0].0.get_request_id(), 42);
+        assert_eq! {
+            statuses[0].1.get_error().get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::UNSUPPORTED_ACTION
+        };
+    }
+
+    #[test]
+    fn listen_invalid_request() {
+        use protobuf::Message as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xF00);
+        request.set_request_id(42);
+        request.mut_cpu_time_limit().set_seconds(-1);
+
+        let data = request.write_to_bytes().unwrap();
+
+        let transport = FakeTransport::new(vec![data]);
+        listen(&args(&[]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].0.get_flow_id(), 0xF00);
+        assert_eq!(statuses[0].0.get_request_id(), 42);
+        assert_eq! {
+            statuses[0].1.get_error().get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::INVALID_ARGS
+        };
+    }
+
+    #[test]
+    fn listen_malformed_request() {
+        let transport = FakeTransport::new(vec![b"\xFF\xFF\xFF".to_vec()]);
+        listen(&args(&[]), &transport);
+
+        // There is nobody to notify about a message that is not a request at
+        // all, so it should be dropped without any response (and no crash).
+        assert!(transport.statuses().is_empty());
+    }
+
+    #[test]
+    fn listen_disabled_action() {
+        use protobuf::Message as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xF00);
+        request.set_request_id(42);
+        request.set_action(rrg_proto::v2::rrg::Action::GET_AGENT_METADATA);
+
+        let data = request.write_to_bytes().unwrap();
+
+        let transport = FakeTransport::new(vec![data]);
+        listen(&args(&["--disable-action", "get_agent_metadata"]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq! {
+            statuses[0].1.get_error().get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::UNSUPPORTED_ACTION
+        };
+    }
+
+    #[cfg(feature = "action-get_agent_metadata")]
+    #[test]
+    fn listen_default_limits() {
+        use protobuf::Message as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xF00);
+        request.set_request_id(42);
+        request.set_action(rrg_proto::v2::rrg::Action::GET_AGENT_METADATA);
+
+        let data = request.write_to_bytes().unwrap();
+
+        let transport = FakeTransport::new(vec![data]);
+        listen(&args(&["--default-network-bytes-limit", "1"]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq! {
+            statuses[0].1.get_error().get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::NETWORK_BYTES_SENT_LIMIT_EXCEEDED
+        };
+    }
+
+    #[cfg(feature = "action-get_agent_metadata")]
+    #[test]
+    fn listen_default_limits_overridden() {
+        use protobuf::Message as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xF00);
+        request.set_request_id(42);
+        request.set_action(rrg_proto::v2::rrg::Action::GET_AGENT_METADATA);
+        request.set_network_bytes_sent_limit(1024 * 1024);
+
+        let data = request.write_to_bytes().unwrap();
+
+        let transport = FakeTransport::new(vec![data]);
+        listen(&args(&["--default-network-bytes-limit", "1"]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert!(!statuses[0].1.has_error());
+    }
+
+    #[cfg(all(feature = "action-get_agent_metadata", feature = "action-cancel_request"))]
+    #[test]
+    fn listen_cancel_queued() {
+        use protobuf::Message as _;
+
+        let mut running = rrg_proto::v2::rrg::Request::new();
+        running.set_flow_id(0xF00);
+        running.set_request_id(1);
+        running.set_action(rrg_proto::v2::rrg::Action::GET_AGENT_METADATA);
+
+        let mut queued = rrg_proto::v2::rrg::Request::new();
+        queued.set_flow_id(0xF00);
+        queued.set_request_id(2);
+        queued.set_action(rrg_proto::v2::rrg::Action::GET_AGENT_METADATA);
+
+        let mut cancel_args = rrg_proto::v2::cancel_request::Args::new();
+        cancel_args.set_flow_id(0xF00);
+        cancel_args.set_request_id(2);
+
+        let mut cancel = rrg_proto::v2::rrg::Request::new();
+        cancel.set_flow_id(0xF00);
+        cancel.set_request_id(3);
+        cancel.set_action(rrg_proto::v2::rrg::Action::CANCEL_REQUEST);
+        cancel.mut_args().set_type_url(String::from("type.googleapis.com/rrg.action.cancel_request.Args"));
+        cancel.mut_args().set_value(cancel_args.write_to_bytes().unwrap());
+
+        // The running request cannot finish before the cancellation request is
+        // received, so with a single worker the queued one is still waiting by
+        // then.
+        let transport = FakeTransport::holding(vec! {
+            running.write_to_bytes().unwrap(),
+            queued.write_to_bytes().unwrap(),
+            cancel.write_to_bytes().unwrap(),
+        });
+        listen(&args(&["--max-concurrent-actions", "1"]), &transport);
+
+        let statuses = transport.statuses();
+        assert_eq!(statuses.len(), 3);
+
+        let status = |request_id| {
+            statuses.iter()
+                .find(|(response, _)| response.get_request_id() == request_id)
+                .map(|(_, status)| status.clone())
+                .unwrap()
+        };
+        assert!(!status(1).has_error());
+        assert!(!status(3).has_error());
+        assert_eq! {
+            status(2).get_error().get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::CANCELLED
+        };
+    }
+
+    /// Returns agent arguments parsed from the given command-line arguments.
+    fn args(args: &[&str]) -> crate::args::Args {
+        <crate::args::Args as argh::FromArgs>::from_args(&["rrg"], args)
+            .unwrap()
+    }
+
+    /// A transport that yields predefined messages and collects responses.
+    struct FakeTransport {
+        /// Messages yet to be received by the agent.
+        messages: std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>,
+        /// Signalled whenever the agent receives a message.
+        received: std::sync::Condvar,
+        /// Whether responses are held back until all messages are received.
+        hold_responses: bool,
+        /// Responses sent by the agent so far.
+        responses: std::sync::Mutex<Vec<rrg_proto::v2::rrg::Response>>,
+    }
+
+    impl FakeTransport {
+
+        /// Creates a new transport yielding the given messages.
+        fn new(messages: Vec<Vec<u8>>) -> FakeTransport {
+            FakeTransport {
+                messages: std::sync::Mutex::new(messages.into()),
+                received: std::sync::Condvar::new(),
+                hold_responses: false,
+                responses: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        /// Creates a new transport yielding the given messages that blocks
+        /// sending responses until all of them are received.
+        ///
+        /// Note that only the last message can be handled by the agent without
+        /// a worker, otherwise the agent deadlocks.
+        fn holding(messages: Vec<Vec<u8>>) -> FakeTransport {
+            FakeTransport {
+                hold_responses: true,
+                ..FakeTransport::new(messages)
+            }
+        }
+
+        /// Returns all the status responses sent so far (with the status).
+        fn statuses(&self) -> Vec<(rrg_proto::v2::rrg::Response, rrg_proto::v2::rrg::Status)> {
+            self.responses.lock().unwrap().iter()
+                .filter(|response| response.has_status())
+                .map(|response| (response.clone(), response.get_status().clone()))
+                .collect()
+        }
+    }
+
+    impl crate::transport::Transport for FakeTransport {
+
+        fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+            let message = self.messages.lock().unwrap().pop_front();
+            self.received.notify_all();
+
+            Ok(message)
+        }
+
+        fn send_response(&self, data: Vec<u8>) -> std::io::Result<()> {
+            use protobuf::Message as _;
+
+            if self.hold_responses {
+                let messages = self.messages.lock().unwrap();
+                drop(self.received.wait_while(messages, |messages| !messages.is_empty()).unwrap());
+            }
+
+            let response = rrg_proto::v2::rrg::Response::parse_from_bytes(&data)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+            self.responses.lock().unwrap().push(response);
+
+            Ok(())
+        }
+
+        fn send_parcel(&self, _: Vec<u8>) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn heartbeat(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
 }
@@ -3,33 +3,264 @@
 // Use of this source code is governed by an MIT-style license that can be found
 // in the LICENSE file or at https://opensource.org/licenses/MIT.
 
+mod rotating;
+#[cfg(target_family = "unix")]
+mod syslog;
+
+use crate::args::Format;
+
+use self::rotating::{RotatingFile, RotationOpts};
+
 /// Initializes the logging submodule.
 ///
 /// This function should be called only once at the beginning of the process
 /// startup.
 pub fn init(args: &crate::args::Args) {
-    let mut logger = MultiLog::new();
+    let mut logger = MultiLog::new(args.verbosity);
     if args.log_to_stdout {
-        logger.push(WriterLog::new(std::io::stdout()));
+        logger.push(WriterLog::new(std::io::stdout(), args.log_format));
     }
     if let Some(ref path) = args.log_to_file {
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)
+        let opts = RotationOpts {
+            max_size: args.log_file_max_size,
+            max_age: args.log_file_max_age,
+            max_count: args.log_file_max_count,
+            #[cfg(feature = "log-compression")]
+            compress: args.log_file_compress,
+        };
+
+        let file = RotatingFile::open(path, opts)
             .expect("failed to open the log file");
 
-        logger.push(WriterLog::new(file));
+        logger.push(WriterLog::new(file, args.log_format));
+    }
+    #[cfg(target_family = "unix")]
+    {
+        if args.log_to_syslog {
+            let syslog = self::syslog::SyslogLog::connect()
+                .expect("failed to connect to syslog");
+
+            logger.push(syslog);
+        }
     }
 
     log::set_boxed_logger(Box::new(logger))
         .expect("failed to initialize logger");
 
-    log::set_max_level(args.verbosity);
+    MaxLevel::with(|max_level| max_level.verbosity = args.verbosity);
+}
+
+/// Global maximum verbosity level of emitted log records.
+///
+/// Logs can be requested by the server at verbosity levels higher than the one
+/// specified for on-host logging (see [`Capture`]). Thus, the global maximum
+/// level is raised to the level of the most verbose capture, but only for as
+/// long as that capture is active. The verbosity of on-host logging is still
+/// respected by `MultiLog`.
+struct MaxLevel {
+    /// The verbosity level of on-host logging.
+    verbosity: log::LevelFilter,
+    /// Levels of all captures that are currently active (on any thread).
+    captures: Vec<log::LevelFilter>,
+}
+
+/// The global maximum verbosity level state.
+static MAX_LEVEL: std::sync::Mutex<MaxLevel> = std::sync::Mutex::new(MaxLevel {
+    verbosity: log::LevelFilter::Off,
+    captures: Vec::new(),
+});
+
+impl MaxLevel {
+
+    /// Updates the global maximum level state with the given function.
+    ///
+    /// The maximum level of the `log` crate is adjusted accordingly afterwards.
+    fn with<F: FnOnce(&mut MaxLevel)>(f: F) {
+        // The state is never left inconsistent, so even if some other thread
+        // panicked while holding the lock, we can proceed.
+        let mut max_level = MAX_LEVEL.lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        f(&mut max_level);
+
+        let captures = max_level.captures.iter().copied().max()
+            .unwrap_or(log::LevelFilter::Off);
+
+        log::set_max_level(std::cmp::max(max_level.verbosity, captures));
+    }
+}
+
+/// A log record captured during execution of an action.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The verbosity level of the record.
+    pub level: log::Level,
+    /// The time at which the record was logged.
+    pub timestamp: std::time::SystemTime,
+    /// The content of the logged message.
+    pub message: String,
+}
+
+thread_local! {
+    /// Capture of log records active on the current thread (if any).
+    static CAPTURE: std::cell::RefCell<Option<CaptureState>> = const {
+        std::cell::RefCell::new(None)
+    };
+
+    /// Identifier of the request handled on the current thread (if any).
+    static REQUEST_ID: std::cell::Cell<Option<crate::RequestId>> = const {
+        std::cell::Cell::new(None)
+    };
+}
+
+/// Scope of a request handled on the current thread.
+///
+/// While an instance of this type is alive, all log records emitted on the
+/// thread that created it are associated with the given request (which is
+/// included in structured log formats). Records emitted on other threads (even
+/// if spawned while handling the request) are not associated with it.
+///
+/// The scope ends once the instance is dropped.
+pub struct RequestScope {
+    // Scopes are bound to the thread they were created on, so we make sure that
+    // the type is neither `Send` nor `Sync`.
+    _marker: std::marker::PhantomData<*const ()>,
+}
+
+impl RequestScope {
+
+    /// Starts the scope of the request with the given identifier.
+    pub fn new(request_id: crate::RequestId) -> RequestScope {
+        REQUEST_ID.with(|scope| scope.set(Some(request_id)));
+
+        RequestScope {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for RequestScope {
+
+    fn drop(&mut self) {
+        REQUEST_ID.with(|scope| scope.set(None));
+    }
+}
+
+/// State of an active log capture.
+struct CaptureState {
+    /// The maximum verbosity level of captured records.
+    level: log::LevelFilter,
+    /// Records captured so far.
+    records: Vec<Record>,
+}
+
+/// Capture of log records emitted on the current thread.
+///
+/// While an instance of this type is alive, all log records emitted on the
+/// thread that created it (at or above the specified level) are buffered and
+/// can be retrieved using the [`Capture::take`] method. This is used to forward
+/// logs emitted during action execution to the server.
+///
+/// Note that only the thread that created the capture is affected: records
+/// emitted on threads spawned by the action (e.g. worker threads or the session
+/// heartbeat thread) are neither captured nor associated with the request (see
+/// [`RequestScope`]). Such records are logged on-host only.
+///
+/// The capture is stopped once the instance is dropped.
+pub struct Capture {
+    /// The maximum verbosity level of captured records.
+    level: log::LevelFilter,
+    // Captures are bound to the thread they were created on, so we make sure
+    // that the type is neither `Send` nor `Sync`.
+    _marker: std::marker::PhantomData<*const ()>,
+}
+
+impl Capture {
+
+    /// Starts capturing log records at or above the given level.
+    ///
+    /// Note that starting a new capture replaces the one that was active on the
+    /// current thread (if any).
+    pub fn new(level: log::LevelFilter) -> Capture {
+        CAPTURE.with(|capture| {
+            capture.replace(Some(CaptureState {
+                level,
+                records: Vec::new(),
+            }));
+        });
+
+        MaxLevel::with(|max_level| max_level.captures.push(level));
+
+        Capture {
+            level,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Takes all the records captured since the last call.
+    pub fn take(&self) -> Vec<Record> {
+        CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+            Some(state) => std::mem::take(&mut state.records),
+            None => Vec::new(),
+        })
+    }
+}
+
+impl Drop for Capture {
+
+    fn drop(&mut self) {
+        CAPTURE.with(|capture| {
+            capture.replace(None);
+        });
+
+        MaxLevel::with(|max_level| {
+            let captures = &mut max_level.captures;
+            if let Some(index) = captures.iter().position(|level| *level == self.level) {
+                captures.swap_remove(index);
+            }
+        });
+    }
+}
+
+/// Captures the given log record if there is an active capture interested in it.
+fn capture(record: &log::Record) {
+    CAPTURE.with(|capture| {
+        // The capture might be already borrowed if something tries to log while
+        // the records are being taken. We do not want to panic in such a case,
+        // so we just skip the record.
+        let mut capture = match capture.try_borrow_mut() {
+            Ok(capture) => capture,
+            Err(_) => return,
+        };
+
+        let state = match capture.as_mut() {
+            Some(state) if record.level() <= state.level => state,
+            _ => return,
+        };
+
+        state.records.push(Record {
+            level: record.level(),
+            timestamp: std::time::SystemTime::now(),
+            message: record.args().to_string(),
+        });
+    });
+}
+
+/// Checks whether there is an active capture interested in the given record.
+fn capture_enabled(metadata: &log::Metadata) -> bool {
+    CAPTURE.with(|capture| match capture.try_borrow() {
+        Ok(capture) => match capture.as_ref() {
+            Some(state) => metadata.level() <= state.level,
+            None => false,
+        },
+        Err(_) => false,
+    })
 }
 
 /// A wrapper for logging to multiple destinations.
 struct MultiLog {
+    /// The maximum verbosity level of records logged to the destinations.
+    verbosity: log::LevelFilter,
     /// A list of all registered loggers.
     loggers: Vec<Box<dyn log::Log>>,
 }
@@ -37,8 +268,9 @@ struct MultiLog {
 impl MultiLog {
 
     /// Creates a new wrapper with no registered loggers.
-    fn new() -> MultiLog {
+    fn new(verbosity: log::LevelFilter) -> MultiLog {
         MultiLog {
+            verbosity,
             loggers: Vec::new(),
         }
     }
@@ -52,10 +284,19 @@ impl MultiLog {
 impl log::Log for MultiLog {
 
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        self.loggers.iter().any(|logger| logger.enabled(metadata))
+        let enabled = metadata.level() <= self.verbosity &&
+            self.loggers.iter().any(|logger| logger.enabled(metadata));
+
+        enabled || capture_enabled(metadata)
     }
 
     fn log(&self, record: &log::Record) {
+        capture(record);
+
+        if record.level() > self.verbosity {
+            return;
+        }
+
         for log in &self.loggers {
             log.log(record);
         }
@@ -71,13 +312,17 @@ impl log::Log for MultiLog {
 /// A simple logger implementation for logging to writable streams (e.g. files).
 struct WriterLog<W: std::io::Write + Send + Sync> {
     writer: std::sync::Mutex<W>,
+    format: Format,
 }
 
 impl<W: std::io::Write + Send + Sync> WriterLog<W> {
 
-    /// Create a new logger for the given writable stream.
-    fn new(writer: W) -> WriterLog<W> {
-        WriterLog { writer: std::sync::Mutex::new(writer) }
+    /// Create a new logger for the given writable stream and format.
+    fn new(writer: W, format: Format) -> WriterLog<W> {
+        WriterLog {
+            writer: std::sync::Mutex::new(writer),
+            format,
+        }
     }
 }
 
@@ -94,32 +339,21 @@ impl<W: std::io::Write + Send + Sync> log::Log for WriterLog<W> {
 
         let now = std::time::SystemTime::now();
 
+        // The record is formatted upfront and written with a single call, so
+        // that it is never split between files if the log file is rotated.
+        let line = match self.format {
+            Format::Text => format_text(record, now),
+            Format::Json => format_json(record, now),
+        };
+
         // We consider failures to write to the log stream critical. Otherwise,
         // if there is some other issue with the system it is not possible to
         // properly communicate it. Thus, we panic on all write errors.
         let mut writer = self.writer.lock()
             .expect("failed to acquire log output stream lock");
 
-        || -> Result<(), std::io::Error> {
-            write! {
-                writer,
-                "[{level} {timestamp} ",
-                level = record.level(),
-                timestamp = humantime::format_rfc3339_nanos(now)
-            }?;
-            match record.file() {
-                Some(file) => write!(writer, "{file}")?,
-                None => write!(writer, "<unknown>")?,
-            }
-            match record.line() {
-                Some(line) => write!(writer, ":{line}]")?,
-                None => write!(writer, ":<unknown>]")?,
-            }
-
-            write!(writer, " {}\n", record.args())?;
-
-            Ok(())
-        }().expect("failed to write to the log output stream")
+        writer.write_all(line.as_bytes())
+            .expect("failed to write to the log output stream")
     }
 
     fn flush(&self) {
@@ -132,3 +366,206 @@ impl<W: std::io::Write + Send + Sync> log::Log for WriterLog<W> {
             .expect("failed to flush the log output stream");
     }
 }
+
+/// Formats the given record as a human-readable line.
+fn format_text(record: &log::Record, time: std::time::SystemTime) -> String {
+    let mut line = format! {
+        "[{level} {timestamp} ",
+        level = record.level(),
+        timestamp = humantime::format_rfc3339_nanos(time)
+    };
+    match record.file() {
+        Some(file) => line.push_str(file),
+        None => line.push_str("<unknown>"),
+    }
+    match record.line() {
+        Some(number) => line.push_str(&format!(":{number}]")),
+        None => line.push_str(":<unknown>]"),
+    }
+    line.push_str(&format!(" {}\n", record.args()));
+
+    line
+}
+
+/// Formats the given record as a line with a JSON object.
+///
+/// Fields that are not known (e.g. the request identifier if the record was
+/// not emitted while handling a request) are omitted.
+fn format_json(record: &log::Record, time: std::time::SystemTime) -> String {
+    let mut line = format! {
+        "{{\"level\":\"{level}\",\"timestamp\":\"{timestamp}\",\"target\":{target}",
+        level = record.level(),
+        timestamp = humantime::format_rfc3339_nanos(time),
+        target = json_string(record.target()),
+    };
+    if let Some(file) = record.file() {
+        line.push_str(&format!(",\"file\":{}", json_string(file)));
+    }
+    if let Some(number) = record.line() {
+        line.push_str(&format!(",\"line\":{number}"));
+    }
+    if let Some(request_id) = REQUEST_ID.with(|scope| scope.get()) {
+        line.push_str(&format!(",\"request_id\":\"{request_id}\""));
+    }
+    line.push_str(&format! {
+        ",\"message\":{}}}\n",
+        json_string(&record.args().to_string())
+    });
+
+    line
+}
+
+/// Returns the given string as a quoted and escaped JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+
+    result.push('"');
+    for char in value.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            char if char.is_control() => {
+                result.push_str(&format!("\\u{:04x}", char as u32));
+            }
+            char => result.push(char),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use log::Log as _;
+
+    #[test]
+    fn capture_records_at_level() {
+        let logger = MultiLog::new(log::LevelFilter::Off);
+        let capture = Capture::new(log::LevelFilter::Info);
+
+        log(&logger, log::Level::Error, "foo");
+        log(&logger, log::Level::Info, "bar");
+        log(&logger, log::Level::Debug, "baz");
+
+        let records = capture.take();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level, log::Level::Error);
+        assert_eq!(records[0].message, "foo");
+        assert_eq!(records[1].level, log::Level::Info);
+        assert_eq!(records[1].message, "bar");
+    }
+
+    #[test]
+    fn capture_raises_max_level() {
+        let capture = Capture::new(log::LevelFilter::Trace);
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+
+        drop(capture);
+    }
+
+    #[test]
+    fn capture_take_clears_records() {
+        let logger = MultiLog::new(log::LevelFilter::Off);
+        let capture = Capture::new(log::LevelFilter::Info);
+
+        log(&logger, log::Level::Info, "foo");
+        assert_eq!(capture.take().len(), 1);
+        assert_eq!(capture.take().len(), 0);
+    }
+
+    #[test]
+    fn capture_dropped() {
+        let logger = MultiLog::new(log::LevelFilter::Off);
+
+        drop(Capture::new(log::LevelFilter::Info));
+        log(&logger, log::Level::Info, "foo");
+
+        let capture = Capture::new(log::LevelFilter::Info);
+        assert_eq!(capture.take().len(), 0);
+    }
+
+    #[test]
+    fn capture_enabled_above_verbosity() {
+        let logger = MultiLog::new(log::LevelFilter::Error);
+        let metadata = log::Metadata::builder()
+            .level(log::Level::Debug)
+            .build();
+
+        assert!(!logger.enabled(&metadata));
+
+        let _capture = Capture::new(log::LevelFilter::Debug);
+        assert!(logger.enabled(&metadata));
+    }
+
+    #[test]
+    fn format_text_record() {
+        let time = std::time::UNIX_EPOCH;
+        let line = format_text(&log::Record::builder()
+            .level(log::Level::Info)
+            .file(Some("src/foo.rs"))
+            .line(Some(42))
+            .args(format_args!("bar"))
+            .build(), time);
+
+        assert_eq!(line, "[INFO 1970-01-01T00:00:00.000000000Z src/foo.rs:42] bar\n");
+    }
+
+    #[test]
+    fn format_json_record() {
+        let time = std::time::UNIX_EPOCH;
+        let line = format_json(&log::Record::builder()
+            .level(log::Level::Warn)
+            .target("rrg::foo")
+            .file(Some("src/foo.rs"))
+            .line(Some(42))
+            .args(format_args!("bar \"baz\"\n"))
+            .build(), time);
+
+        assert_eq!(line, concat! {
+            "{",
+            "\"level\":\"WARN\",",
+            "\"timestamp\":\"1970-01-01T00:00:00.000000000Z\",",
+            "\"target\":\"rrg::foo\",",
+            "\"file\":\"src/foo.rs\",",
+            "\"line\":42,",
+            "\"message\":\"bar \\\"baz\\\"\\n\"",
+            "}\n",
+        });
+    }
+
+    #[test]
+    fn format_json_record_in_request_scope() {
+        let time = std::time::UNIX_EPOCH;
+        let record = log::Record::builder()
+            .level(log::Level::Info)
+            .target("rrg")
+            .args(format_args!("foo"))
+            .build();
+
+        let scope = RequestScope::new(crate::RequestId::new(0xF10, 42));
+        assert!(format_json(&record, time).contains(",\"request_id\":\"F10/42\","));
+
+        drop(scope);
+        assert!(!format_json(&record, time).contains("request_id"));
+    }
+
+    #[test]
+    fn json_string_control() {
+        assert_eq!(json_string("\u{1}\t\\"), "\"\\u0001\\t\\\\\"");
+    }
+
+    /// Logs a record with the given level and message using `logger`.
+    fn log(logger: &MultiLog, level: log::Level, message: &str) {
+        logger.log(&log::Record::builder()
+            .level(level)
+            .args(format_args!("{}", message))
+            .build());
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs::File;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Options that determine when and how log files are rotated.
+#[derive(Clone, Debug)]
+pub struct RotationOpts {
+    /// Size (in bytes) after which the log file is rotated (if any).
+    pub max_size: Option<u64>,
+    /// Age after which the log file is rotated (if any).
+    pub max_age: Option<Duration>,
+    /// Number of rotated log files to keep.
+    pub max_count: usize,
+    /// Whether rotated log files should be compressed with gzip.
+    #[cfg(feature = "log-compression")]
+    pub compress: bool,
+}
+
+/// A log file that is rotated once it gets too big or too old.
+///
+/// Rotated files are kept next to the current log file with a numeric suffix
+/// (e.g. `rrg.log.1` for the most recent one) and, if compression is enabled,
+/// an additional `.gz` extension. Files older than the configured count of kept
+/// files are removed.
+///
+/// Rotation is checked before every write. Thus, to avoid splitting log records
+/// across files, each record should be written using a single call.
+///
+/// Failing to rotate the file is not considered an error: the failure is
+/// reported to the standard error and records are appended to the current file
+/// until the next rotation attempt (once the file grows or ages again).
+pub struct RotatingFile {
+    /// Path to the current log file.
+    path: PathBuf,
+    /// Options that determine when and how the file is rotated.
+    opts: RotationOpts,
+    /// Handle to the current log file.
+    file: File,
+    /// Number of bytes written to the current log file since the last rotation
+    /// (or the size of the file if it was not rotated yet).
+    len: u64,
+    /// Time at which the current log file was created (or last rotated).
+    created: SystemTime,
+    /// Thread compressing the most recently rotated file (if any).
+    #[cfg(feature = "log-compression")]
+    compression: Option<std::thread::JoinHandle<()>>,
+}
+
+impl RotatingFile {
+
+    /// Opens the log file at the given path in the append mode.
+    pub fn open<P>(path: P, opts: RotationOpts) -> std::io::Result<RotatingFile>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let file = open(&path)?;
+
+        let metadata = file.metadata()?;
+        let created = metadata.created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+
+        Ok(RotatingFile {
+            len: metadata.len(),
+            path,
+            opts,
+            file,
+            created,
+            #[cfg(feature = "log-compression")]
+            compression: None,
+        })
+    }
+
+    /// Checks whether the current log file should be rotated.
+    fn needs_rotation(&self) -> bool {
+        // There is no point in rotating empty files, no matter how old they are.
+        if self.len == 0 {
+            return false;
+        }
+
+        if let Some(max_size) = self.opts.max_size {
+            if self.len >= max_size {
+                return true;
+            }
+        }
+
+        if let Some(max_age) = self.opts.max_age {
+            // If the clock went backwards, we just wait until it catches up.
+            if let Ok(age) = self.created.elapsed() {
+                if age >= max_age {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Rotates the log file, falling back to the current one on failure.
+    fn rotate(&mut self) {
+        // We cannot use the logger to report problems with the logger itself,
+        // so we resort to the standard error.
+        if let Err(error) = self.shuffle() {
+            eprintln!("failed to rotate log file '{}': {}", self.path.display(), error);
+        }
+
+        // If the rotation failed before the current file was moved aside, this
+        // simply reopens it. If we cannot even open the file, we keep writing
+        // through the old handle.
+        match open(&self.path) {
+            Ok(file) => self.file = file,
+            Err(error) => {
+                eprintln!("failed to open log file '{}': {}", self.path.display(), error);
+            }
+        }
+
+        // We start counting anew even if the rotation failed, so that it is not
+        // retried on every single write.
+        self.len = 0;
+        self.created = SystemTime::now();
+    }
+
+    /// Moves the current log file aside, shifting older rotated files.
+    ///
+    /// The handle to the current file is kept open (files opened by the standard
+    /// library can be renamed while open on all supported platforms), so that
+    /// we can keep appending to it in case of a failure.
+    fn shuffle(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+
+        // The previous compression might be still running and we are about to
+        // move its files around, so we have to wait for it to finish.
+        #[cfg(feature = "log-compression")]
+        self.wait_for_compression();
+
+        let max_count = self.opts.max_count;
+        for compressed in [false, true] {
+            remove_if_exists(&self.rotated_path(max_count, compressed))?;
+
+            for idx in (1..max_count).rev() {
+                let path = self.rotated_path(idx, compressed);
+                if path.exists() {
+                    std::fs::rename(&path, self.rotated_path(idx + 1, compressed))?;
+                }
+            }
+        }
+
+        if max_count == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+
+        let rotated_path = self.rotated_path(1, false);
+        std::fs::rename(&self.path, &rotated_path)?;
+
+        // Compressing big files can take a while and we do not want to block
+        // all the logging threads waiting for the lock in the meantime, so the
+        // compression happens in the background.
+        #[cfg(feature = "log-compression")]
+        if self.opts.compress {
+            let compressed_path = self.rotated_path(1, true);
+            self.compression = Some(std::thread::spawn(move || {
+                let result = compress(&rotated_path, &compressed_path)
+                    .and_then(|()| std::fs::remove_file(&rotated_path));
+
+                if let Err(error) = result {
+                    eprintln! {
+                        "failed to compress rotated log file '{}': {}",
+                        rotated_path.display(), error,
+                    };
+                }
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the compression of the most recently rotated file (if any).
+    #[cfg(feature = "log-compression")]
+    fn wait_for_compression(&mut self) {
+        if let Some(compression) = self.compression.take() {
+            // The compression thread reports its errors on its own, so it can
+            // fail only if it panicked and there is nothing we can do then.
+            let _ = compression.join();
+        }
+    }
+
+    /// Returns a path to the rotated log file with the given index.
+    fn rotated_path(&self, idx: usize, compressed: bool) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{idx}"));
+        if compressed {
+            path.push(".gz");
+        }
+
+        PathBuf::from(path)
+    }
+}
+
+impl std::io::Write for RotatingFile {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.needs_rotation() {
+            self.rotate();
+        }
+
+        // We write the buffer as a whole, so that a single write call does not
+        // end up split between two files.
+        self.file.write_all(buf)?;
+        self.len += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(feature = "log-compression")]
+impl Drop for RotatingFile {
+
+    fn drop(&mut self) {
+        self.wait_for_compression();
+    }
+}
+
+/// Opens the log file at the given path in the append mode.
+fn open(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// Removes the file at the given path unless it does not exist.
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Compresses the file at `src` with gzip and writes the result to `dest`.
+#[cfg(feature = "log-compression")]
+fn compress(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let mut src = File::open(src)?;
+
+    let dest = File::create(dest)?;
+    let mut encoder = flate2::write::GzEncoder::new(dest, flate2::Compression::default());
+    std::io::copy(&mut src, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Write as _;
+
+    use super::*;
+
+    fn opts() -> RotationOpts {
+        RotationOpts {
+            max_size: None,
+            max_age: None,
+            max_count: 2,
+            #[cfg(feature = "log-compression")]
+            compress: false,
+        }
+    }
+
+    fn read(path: PathBuf) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn no_rotation() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        let mut file = RotatingFile::open(&path, opts()).unwrap();
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+        drop(file);
+
+        let mut file = RotatingFile::open(&path, opts()).unwrap();
+        file.write_all(b"baz\n").unwrap();
+
+        assert_eq!(read(path), "foo\nbar\nbaz\n");
+        assert_eq!(std::fs::read_dir(tempdir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rotation_by_size() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        let mut file = RotatingFile::open(&path, RotationOpts {
+            max_size: Some(8),
+            ..opts()
+        }).unwrap();
+
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+        file.write_all(b"baz\n").unwrap();
+        file.write_all(b"quux\n").unwrap();
+        file.write_all(b"norf\n").unwrap();
+        file.write_all(b"thud\n").unwrap();
+        file.write_all(b"blep\n").unwrap();
+
+        assert_eq!(read(path), "blep\n");
+        assert_eq!(read(tempdir.path().join("rrg.log.1")), "norf\nthud\n");
+        assert_eq!(read(tempdir.path().join("rrg.log.2")), "baz\nquux\n");
+        assert_eq!(std::fs::read_dir(tempdir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn rotation_by_age() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        let mut file = RotatingFile::open(&path, RotationOpts {
+            max_age: Some(Duration::ZERO),
+            ..opts()
+        }).unwrap();
+
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+
+        assert_eq!(read(path), "bar\n");
+        assert_eq!(read(tempdir.path().join("rrg.log.1")), "foo\n");
+    }
+
+    #[test]
+    fn rotation_no_kept_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        let mut file = RotatingFile::open(&path, RotationOpts {
+            max_size: Some(1),
+            max_count: 0,
+            ..opts()
+        }).unwrap();
+
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+
+        assert_eq!(read(path), "bar\n");
+        assert_eq!(std::fs::read_dir(tempdir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rotation_failure() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        // A directory in place of the rotated file makes the rotation fail.
+        std::fs::create_dir(tempdir.path().join("rrg.log.1")).unwrap();
+
+        let mut file = RotatingFile::open(&path, RotationOpts {
+            max_size: Some(1),
+            max_count: 1,
+            ..opts()
+        }).unwrap();
+
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+        file.write_all(b"baz\n").unwrap();
+
+        assert_eq!(read(path), "foo\nbar\nbaz\n");
+        assert!(tempdir.path().join("rrg.log.1").is_dir());
+    }
+
+    #[cfg(feature = "log-compression")]
+    #[test]
+    fn rotation_compressed() {
+        use std::io::Read as _;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("rrg.log");
+
+        let mut file = RotatingFile::open(&path, RotationOpts {
+            max_size: Some(1),
+            compress: true,
+            ..opts()
+        }).unwrap();
+
+        file.write_all(b"foo\n").unwrap();
+        file.write_all(b"bar\n").unwrap();
+        file.write_all(b"baz\n").unwrap();
+
+        // Compression happens in the background, dropping the file waits for
+        // it to finish.
+        drop(file);
+
+        let read_gz = |path| {
+            let mut decoder = flate2::read::GzDecoder::new(File::open(path).unwrap());
+
+            let mut result = String::new();
+            decoder.read_to_string(&mut result).unwrap();
+            result
+        };
+
+        assert_eq!(read(path), "baz\n");
+        assert_eq!(read_gz(tempdir.path().join("rrg.log.1.gz")), "bar\n");
+        assert_eq!(read_gz(tempdir.path().join("rrg.log.2.gz")), "foo\n");
+        assert!(!tempdir.path().join("rrg.log.1").exists());
+    }
+}
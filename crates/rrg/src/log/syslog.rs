@@ -0,0 +1,202 @@
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+
+/// Path to the socket of the system logging daemon.
+const SOCKET_PATH: &str = "/dev/log";
+
+/// Facility code of system daemons (see RFC 5424, section 6.2.1).
+const FACILITY_DAEMON: u8 = 3;
+
+/// Length to which messages too big for the socket are truncated.
+///
+/// This is the minimum message length that all receivers should be able to
+/// handle (see RFC 5424, section 6.1).
+const TRUNCATED_MESSAGE_LEN: usize = 2048;
+
+/// A logger implementation for logging to the system logging daemon.
+///
+/// Records are sent as [RFC 5424] messages through the `/dev/log` socket (which
+/// is served both by traditional syslog daemons and by journald).
+///
+/// [RFC 5424]: https://www.rfc-editor.org/rfc/rfc5424
+pub struct SyslogLog {
+    /// Socket connected to the system logging daemon.
+    socket: Mutex<UnixDatagram>,
+    /// Name of the host to include in messages.
+    hostname: String,
+}
+
+impl SyslogLog {
+
+    /// Creates a new logger connected to the system logging daemon.
+    pub fn connect() -> std::io::Result<SyslogLog> {
+        Ok(SyslogLog {
+            socket: Mutex::new(connect()?),
+            hostname: hostname(),
+        })
+    }
+}
+
+impl log::Log for SyslogLog {
+
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = format! {
+            "<{priority}>1 {timestamp} {hostname} rrg {pid} - - {message}",
+            priority = FACILITY_DAEMON * 8 + severity(record.level()),
+            timestamp = humantime::format_rfc3339_micros(std::time::SystemTime::now()),
+            hostname = self.hostname,
+            pid = std::process::id(),
+            message = record.args(),
+        };
+
+        // The socket is always left in a usable state, so even if some other
+        // thread panicked while holding the lock, we can proceed.
+        let mut socket = self.socket.lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if send(&socket, &message).is_ok() {
+            return;
+        }
+
+        // The logging daemon might have been restarted in the meantime, so on
+        // failure we reconnect once before giving up.
+        let result = connect().and_then(|new_socket| {
+            *socket = new_socket;
+            send(&socket, &message)
+        });
+
+        // Unlike other loggers, we do not consider failures to log to syslog
+        // critical (the daemon is outside of our control), so we fall back to
+        // the standard error instead.
+        if let Err(error) = result {
+            eprintln!("failed to write to the syslog socket ({error}): {message}");
+        }
+    }
+
+    fn flush(&self) {
+        // Datagrams are sent right away, there is nothing to flush.
+    }
+}
+
+/// Connects to the socket of the system logging daemon.
+fn connect() -> std::io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(SOCKET_PATH)?;
+
+    Ok(socket)
+}
+
+/// Sends the given message through the socket.
+///
+/// Messages that are too big to be sent as a single datagram are truncated.
+fn send(socket: &UnixDatagram, message: &str) -> std::io::Result<()> {
+    match socket.send(message.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(error) if error.raw_os_error() == Some(libc::EMSGSIZE) => {
+            socket.send(truncate(message, TRUNCATED_MESSAGE_LEN).as_bytes())?;
+            Ok(())
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Truncates the given string to at most `len` bytes (at a char boundary).
+fn truncate(string: &str, len: usize) -> &str {
+    if string.len() <= len {
+        return string;
+    }
+
+    let mut idx = len;
+    while !string.is_char_boundary(idx) {
+        idx -= 1;
+    }
+
+    &string[..idx]
+}
+
+/// Returns a severity code of the given level (see RFC 5424, section 6.2.1).
+fn severity(level: log::Level) -> u8 {
+    use log::Level::*;
+
+    match level {
+        Error => 3,
+        Warn => 4,
+        Info => 6,
+        Debug | Trace => 7,
+    }
+}
+
+/// Returns the name of the host or the nil value if it cannot be determined.
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+
+    // SAFETY: We pass a valid buffer along with its length. The buffer has one
+    // more byte than declared, so the result is always null-terminated.
+    let code = unsafe {
+        libc::gethostname(buf.as_mut_ptr().cast(), buf.len() - 1)
+    };
+    if code != 0 {
+        return String::from("-");
+    }
+
+    let len = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+    match std::str::from_utf8(&buf[..len]) {
+        // RFC 5424 allows only printable ASCII characters in the hostname.
+        Ok(hostname) if !hostname.is_empty() && hostname.bytes().all(|byte| byte.is_ascii_graphic()) => {
+            String::from(hostname)
+        }
+        _ => String::from("-"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn severity_ordered() {
+        assert!(severity(log::Level::Error) < severity(log::Level::Warn));
+        assert!(severity(log::Level::Warn) < severity(log::Level::Info));
+        assert!(severity(log::Level::Info) < severity(log::Level::Debug));
+    }
+
+    #[test]
+    fn hostname_not_empty() {
+        assert!(!hostname().is_empty());
+    }
+
+    #[test]
+    fn truncate_short() {
+        assert_eq!(truncate("foo", 3), "foo");
+        assert_eq!(truncate("foo", 4), "foo");
+    }
+
+    #[test]
+    fn truncate_long() {
+        assert_eq!(truncate("foobar", 3), "foo");
+        assert_eq!(truncate("zażółć", 3), "za");
+    }
+
+    #[test]
+    fn send_oversized() {
+        let (sender, receiver) = UnixDatagram::pair().unwrap();
+
+        // Datagrams are limited by the socket send buffer, which is way below
+        // this size by default.
+        let message = "x".repeat(16 * 1024 * 1024);
+        send(&sender, &message).unwrap();
+
+        let mut buf = vec![0; message.len()];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(len, TRUNCATED_MESSAGE_LEN);
+    }
+}
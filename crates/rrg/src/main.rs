@@ -9,14 +9,77 @@ fn main() {
     let args = rrg::args::from_env_args();
     rrg::init(&args);
 
-    info!("sending Fleetspeak startup information");
-    fleetspeak::startup(env!("CARGO_PKG_VERSION"))
-        .expect("failed to initialize Fleetspeak connection");
+    if let Some(rrg::args::Command::RunAction(ref args)) = args.command {
+        if let Err(error) = rrg::run_action(args) {
+            eprintln!("error: {error}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
+    use rrg::args::Transport::*;
+    match args.transport {
+        Fleetspeak => {
+            info!("sending Fleetspeak startup information");
+            fleetspeak::startup(env!("CARGO_PKG_VERSION"))
+                .expect("failed to initialize Fleetspeak connection");
+
+            let transport = rrg::transport::FleetspeakTransport::new(
+                args.heartbeat_rate,
+            );
+            run(&args, transport);
+        }
+        Stdio => {
+            let transport = rrg::transport::StreamTransport::new(
+                std::io::stdin(),
+                std::io::stdout(),
+            );
+            run(&args, transport);
+        }
+        #[cfg(target_family = "unix")]
+        UnixSocket(ref path) => {
+            let transport = rrg::transport::StreamTransport::connect(path)
+                .expect("failed to connect to the transport socket");
+            run(&args, transport);
+        }
+    }
+}
+
+/// Runs the agent communicating through the given transport.
+///
+/// If a spool directory is specified, the transport is wrapped in a spool and
+/// messages undelivered by earlier runs are replayed first.
+fn run<T>(args: &rrg::args::Args, transport: T)
+where
+    T: rrg::transport::Transport + Sync,
+{
+    #[cfg(feature = "spool")]
+    {
+        if let Some(ref spool_dir) = args.spool_dir {
+            let transport = rrg::transport::SpoolTransport::open(spool_dir, transport)
+                .expect("failed to open the spool directory");
+
+            info!("replaying spooled messages");
+            transport.replay()
+                .expect("failed to replay spooled messages");
+
+            serve(args, &transport);
+            return;
+        }
+    }
+
+    serve(args, &transport);
+}
+
+/// Serves requests coming through the given transport.
+fn serve<T>(args: &rrg::args::Args, transport: &T)
+where
+    T: rrg::transport::Transport + Sync,
+{
     info!("sending RRG startup information");
-    rrg::startup()
+    rrg::startup(transport)
         .expect("failed to send RRG startup information");
 
     info!("listening for messages");
-    rrg::listen(&args);
+    rrg::listen(args, transport);
 }
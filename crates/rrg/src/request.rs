@@ -3,8 +3,6 @@
 // Use of this source code is governed by an MIT-style license that can be found
 // in the LICENSE file or at https://opensource.org/licenses/MIT.
 
-use rrg_macro::warn;
-
 /// List of all actions supported by the agent.
 ///
 /// An action is a "unit of execution" and is invoked by flows (created on the
@@ -15,12 +13,73 @@ use rrg_macro::warn;
 /// [request]: crate::Request
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Action {
+    /// Get metadata about the agent itself.
+    #[cfg(feature = "action-get_agent_metadata")]
+    GetAgentMetadata,
     /// Get metadata about the operating system and the machine.
     #[cfg(feature = "action-get_system_metadata")]
     GetSystemMetadata,
     /// Get metadata about the specified file.
     #[cfg(feature = "action-get_file_metadata")]
     GetFileMetadata,
+    /// Get hash of the specified file.
+    #[cfg(feature = "action-get_file_hash")]
+    GetFileHash,
+    /// List contents of a directory.
+    #[cfg(feature = "action-list_directory")]
+    ListDirectory,
+    /// List processes available on the system.
+    #[cfg(feature = "action-list_processes")]
+    ListProcesses,
+    /// List connections available on the system.
+    #[cfg(feature = "action-list_connections")]
+    ListConnections,
+    /// List users available on the system.
+    #[cfg(feature = "action-list_users")]
+    ListUsers,
+    /// Get a timeline of the filesystem.
+    #[cfg(feature = "action-get_filesystem_timeline")]
+    GetFilesystemTimeline,
+    /// Cancel a request that is currently being executed.
+    #[cfg(feature = "action-cancel_request")]
+    CancelRequest,
+    /// Find files matching path queries and conditions.
+    #[cfg(feature = "action-find_files")]
+    FindFiles,
+    /// Scan files with rules written in a subset of the YARA language.
+    #[cfg(feature = "action-scan_files")]
+    ScanFiles,
+}
+
+impl Action {
+
+    /// List of all actions that the agent was built with.
+    pub const ALL: &'static [Action] = &[
+        #[cfg(feature = "action-get_agent_metadata")]
+        Action::GetAgentMetadata,
+        #[cfg(feature = "action-get_system_metadata")]
+        Action::GetSystemMetadata,
+        #[cfg(feature = "action-get_file_metadata")]
+        Action::GetFileMetadata,
+        #[cfg(feature = "action-get_file_hash")]
+        Action::GetFileHash,
+        #[cfg(feature = "action-list_directory")]
+        Action::ListDirectory,
+        #[cfg(feature = "action-list_processes")]
+        Action::ListProcesses,
+        #[cfg(feature = "action-list_connections")]
+        Action::ListConnections,
+        #[cfg(feature = "action-list_users")]
+        Action::ListUsers,
+        #[cfg(feature = "action-get_filesystem_timeline")]
+        Action::GetFilesystemTimeline,
+        #[cfg(feature = "action-cancel_request")]
+        Action::CancelRequest,
+        #[cfg(feature = "action-find_files")]
+        Action::FindFiles,
+        #[cfg(feature = "action-scan_files")]
+        Action::ScanFiles,
+    ];
 }
 
 /// The error type for cases when parsing action fails.
@@ -82,10 +141,30 @@ impl TryFrom<rrg_proto::v2::rrg::Action> for Action {
         use rrg_proto::v2::rrg::Action::*;
 
         match proto {
+            #[cfg(feature = "action-get_agent_metadata")]
+            GET_AGENT_METADATA => Ok(Action::GetAgentMetadata),
             #[cfg(feature = "action-get_system_metadata")]
             GET_SYSTEM_METADATA => Ok(Action::GetSystemMetadata),
             #[cfg(feature ="action-get_file_metadata")]
             GET_FILE_METADATA => Ok(Action::GetFileMetadata),
+            #[cfg(feature = "action-get_file_hash")]
+            GET_FILE_HASH => Ok(Action::GetFileHash),
+            #[cfg(feature = "action-list_directory")]
+            LIST_DIRECTORY => Ok(Action::ListDirectory),
+            #[cfg(feature = "action-list_processes")]
+            LIST_PROCESSES => Ok(Action::ListProcesses),
+            #[cfg(feature = "action-list_connections")]
+            LIST_CONNECTIONS => Ok(Action::ListConnections),
+            #[cfg(feature = "action-list_users")]
+            LIST_USERS => Ok(Action::ListUsers),
+            #[cfg(feature = "action-get_filesystem_timeline")]
+            GET_FILESYSTEM_TIMELINE => Ok(Action::GetFilesystemTimeline),
+            #[cfg(feature = "action-cancel_request")]
+            CANCEL_REQUEST => Ok(Action::CancelRequest),
+            #[cfg(feature = "action-find_files")]
+            FIND_FILES => Ok(Action::FindFiles),
+            #[cfg(feature = "action-scan_files")]
+            SCAN_FILES => Ok(Action::ScanFiles),
             _ => {
                 let val = protobuf::ProtobufEnum::value(&proto);
                 Err(ParseActionErrorKind::UnknownAction(val).into())
@@ -94,6 +173,38 @@ impl TryFrom<rrg_proto::v2::rrg::Action> for Action {
     }
 }
 
+impl From<Action> for rrg_proto::v2::rrg::Action {
+
+    fn from(action: Action) -> rrg_proto::v2::rrg::Action {
+        match action {
+            #[cfg(feature = "action-get_agent_metadata")]
+            Action::GetAgentMetadata => Self::GET_AGENT_METADATA,
+            #[cfg(feature = "action-get_system_metadata")]
+            Action::GetSystemMetadata => Self::GET_SYSTEM_METADATA,
+            #[cfg(feature = "action-get_file_metadata")]
+            Action::GetFileMetadata => Self::GET_FILE_METADATA,
+            #[cfg(feature = "action-get_file_hash")]
+            Action::GetFileHash => Self::GET_FILE_HASH,
+            #[cfg(feature = "action-list_directory")]
+            Action::ListDirectory => Self::LIST_DIRECTORY,
+            #[cfg(feature = "action-list_processes")]
+            Action::ListProcesses => Self::LIST_PROCESSES,
+            #[cfg(feature = "action-list_connections")]
+            Action::ListConnections => Self::LIST_CONNECTIONS,
+            #[cfg(feature = "action-list_users")]
+            Action::ListUsers => Self::LIST_USERS,
+            #[cfg(feature = "action-get_filesystem_timeline")]
+            Action::GetFilesystemTimeline => Self::GET_FILESYSTEM_TIMELINE,
+            #[cfg(feature = "action-cancel_request")]
+            Action::CancelRequest => Self::CANCEL_REQUEST,
+            #[cfg(feature = "action-find_files")]
+            Action::FindFiles => Self::FIND_FILES,
+            #[cfg(feature = "action-scan_files")]
+            Action::ScanFiles => Self::SCAN_FILES,
+        }
+    }
+}
+
 /// A unique identifier of a request.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct RequestId {
@@ -104,6 +215,15 @@ pub struct RequestId {
 }
 
 impl RequestId {
+    /// Creates a request identifier from the identifiers of the flow and the
+    /// request within the flow.
+    pub fn new(flow_id: u64, request_id: u64) -> RequestId {
+        RequestId {
+            flow_id,
+            request_id,
+        }
+    }
+
     /// Returns an identifier of the flow issuing the request.
     pub fn flow_id(&self) -> u64 {
         self.flow_id
@@ -131,10 +251,21 @@ impl std::fmt::Display for RequestId {
 pub struct Request {
     /// A unique identifier of the request.
     id: RequestId,
-    /// An action to invoke.
-    action: Action,
+    /// An action to invoke (as specified in the request).
+    ///
+    /// The action is not parsed until it is actually needed, so that requests
+    /// for actions not supported by the agent can still be responded to.
+    action: rrg_proto::v2::rrg::Action,
     /// Serialized protobuf message with arguments to invoke the action with.
     serialized_args: Vec<u8>,
+    /// Limit on the number of bytes the action execution can send.
+    network_bytes_limit: Option<u64>,
+    /// Limit on the CPU time the action execution can take.
+    cpu_time_limit: Option<std::time::Duration>,
+    /// Limit on the real (wall) time the action execution can take.
+    real_time_limit: Option<std::time::Duration>,
+    /// Verbosity level at which logs should be sent to the server.
+    log_level: log::LevelFilter,
 }
 
 impl Request {
@@ -144,8 +275,13 @@ impl Request {
     }
 
     /// Gets the action this request should invoke.
-    pub fn action(&self) -> Action {
-        self.action
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the action specified in the
+    /// request is not known or the agent was built without support for it.
+    pub fn action(&self) -> Result<Action, ParseActionError> {
+        Action::try_from(self.action)
     }
 
     /// Returns the action arguments stored in this request.
@@ -173,44 +309,78 @@ impl Request {
         A::from_proto(args_proto)
     }
 
-    /// Awaits for a new request message from Fleetspeak.
+    /// Gets the limit on the number of bytes the action execution can send.
+    ///
+    /// If there is no limit, `None` is returned.
+    pub fn network_bytes_limit(&self) -> Option<u64> {
+        self.network_bytes_limit
+    }
+
+    /// Gets the limit on the CPU time the action execution can take.
+    ///
+    /// If there is no limit, `None` is returned.
+    pub fn cpu_time_limit(&self) -> Option<std::time::Duration> {
+        self.cpu_time_limit
+    }
+
+    /// Gets the limit on the real (wall) time the action execution can take.
     ///
-    /// This will suspend execution until the request is actually available.
-    /// However, the process will keep heartbeating at the specified rate to
-    /// ensure that Fleetspeak does not kill the agent for unresponsiveness.
+    /// If there is no limit, `None` is returned.
+    pub fn real_time_limit(&self) -> Option<std::time::Duration> {
+        self.real_time_limit
+    }
+
+    /// Applies the default limits of the agent to limits the request lacks.
+    ///
+    /// Limits specified explicitly by the request are left intact.
+    pub fn apply_default_limits(&mut self, args: &crate::args::Args) {
+        self.network_bytes_limit = self.network_bytes_limit
+            .or(args.default_network_bytes_limit);
+        self.cpu_time_limit = self.cpu_time_limit
+            .or(args.default_cpu_time_limit);
+        self.real_time_limit = self.real_time_limit
+            .or(args.default_real_time_limit);
+    }
+
+    /// Gets the verbosity level at which logs should be sent to the server.
+    ///
+    /// If no logs should be sent, [`log::LevelFilter::Off`] is returned.
+    pub fn log_level(&self) -> log::LevelFilter {
+        self.log_level
+    }
+
+    /// Awaits for a new request message from the given transport.
+    ///
+    /// This will suspend execution until the request is actually available. If
+    /// the transport has been closed and there will be no more requests, `None`
+    /// is returned.
     ///
     /// # Errors
     ///
     /// This function will return an error in case the request was invalid (e.g.
     /// it was missing some necessary fields). However, it will panic in case of
-    /// irrecoverable error like Fleetspeak connection issue as it makes little
+    /// irrecoverable error like transport connection issue as it makes little
     /// sense to continue running in such a state.
-    pub fn receive(heartbeat_rate: std::time::Duration) -> Result<Request, ParseRequestError> {
-        let message = fleetspeak::receive_with_heartbeat(heartbeat_rate)
-            // If we fail to receive a message from Fleetspeak, our connection
+    pub fn receive<T>(transport: &T) -> Option<Result<Request, ParseRequestError>>
+    where
+        T: crate::transport::Transport,
+    {
+        let data = transport.receive()
+            // If we fail to receive a message from the transport, our connection
             // is most likely broken and we should die. In general, this should
             // not happen.
-            .expect("failed to receive a message from Fleetspeak");
-
-        if message.service != "GRR" {
-            let service = message.service;
-            warn!("request send by service '{service}' (instead of 'GRR')");
-        }
-        if message.kind.as_deref() != Some("rrg.Request") {
-            match message.kind {
-                Some(kind) => warn!("request with unexpected kind '{kind}'"),
-                None => warn!("request with unspecified kind"),
-            }
-        }
+            .expect("failed to receive a message")?;
 
         use protobuf::Message as _;
-        let proto = rrg_proto::v2::rrg::Request::parse_from_bytes(&message.data[..])
-            .map_err(|error| {
+        let proto = match rrg_proto::v2::rrg::Request::parse_from_bytes(&data[..]) {
+            Ok(proto) => proto,
+            Err(error) => {
                 use ParseRequestErrorKind::*;
-                ParseRequestError::new(MalformedBytes, error)
-            })?;
+                return Some(Err(ParseRequestError::new(MalformedBytes, error)));
+            }
+        };
 
-        Ok(Request::try_from(proto)?)
+        Some(Request::try_from(proto))
     }
 }
 
@@ -219,16 +389,48 @@ impl TryFrom<rrg_proto::v2::rrg::Request> for Request {
     type Error = ParseRequestError;
 
     fn try_from(mut proto: rrg_proto::v2::rrg::Request) -> Result<Request, ParseRequestError> {
+        use ParseRequestErrorKind::*;
+
+        let id = RequestId {
+            flow_id: proto.get_flow_id(),
+            request_id: proto.get_request_id(),
+        };
+
+        // Limits that are zero are treated as not set (in the same way as the
+        // network bytes limit).
+        let mut cpu_time_limit = None;
+        if proto.has_cpu_time_limit() {
+            let limit = rrg_proto::try_from_duration(proto.take_cpu_time_limit())
+                .map_err(|error| ParseRequestError::new(InvalidCpuTimeLimit, error))
+                .map_err(|error| error.with_request_id(id))?;
+            cpu_time_limit = Some(limit).filter(|limit| !limit.is_zero());
+        }
+
+        let mut real_time_limit = None;
+        if proto.has_real_time_limit() {
+            let limit = rrg_proto::try_from_duration(proto.take_real_time_limit())
+                .map_err(|error| ParseRequestError::new(InvalidRealTimeLimit, error))
+                .map_err(|error| error.with_request_id(id))?;
+            real_time_limit = Some(limit).filter(|limit| !limit.is_zero());
+        }
+
         Ok(Request {
-            id: RequestId {
-                flow_id: proto.get_flow_id(),
-                request_id: proto.get_request_id(),
-            },
-            // TODO(@panhania): We should not parse action at this moment as we
-            // cannot return a meaningful error to the server in case the agent
-            // does not recognize the action.
-            action: proto.get_action().try_into()?,
+            id,
+            action: proto.get_action(),
             serialized_args: proto.take_args().take_value(),
+            network_bytes_limit: match proto.get_network_bytes_sent_limit() {
+                0 => None,
+                limit => Some(limit),
+            },
+            cpu_time_limit,
+            real_time_limit,
+            log_level: match proto.get_log_level() {
+                rrg_proto::v2::rrg::Log_Level::UNSET => log::LevelFilter::Off,
+                rrg_proto::v2::rrg::Log_Level::ERROR => log::LevelFilter::Error,
+                rrg_proto::v2::rrg::Log_Level::WARN => log::LevelFilter::Warn,
+                rrg_proto::v2::rrg::Log_Level::INFO => log::LevelFilter::Info,
+                rrg_proto::v2::rrg::Log_Level::DEBUG => log::LevelFilter::Debug,
+            },
         })
     }
 }
@@ -239,7 +441,9 @@ pub struct ParseRequestError {
     /// A corresponding [`ParseRequestErrorKind`] of the error.
     kind: ParseRequestErrorKind,
     /// A more datailed cause of the error.
-    error: Option<Box<dyn std::error::Error>>,
+    error: Option<Box<dyn std::error::Error + Send + Sync>>,
+    /// An identifier of the request that failed to parse (if known).
+    request_id: Option<RequestId>,
 }
 
 impl ParseRequestError {
@@ -247,19 +451,33 @@ impl ParseRequestError {
     /// Creates a new error from a known kind and its cause.
     pub fn new<E>(kind: ParseRequestErrorKind, error: E) -> ParseRequestError
     where
-        E: Into<Box<dyn std::error::Error>>
+        E: Into<Box<dyn std::error::Error + Send + Sync>>
     {
         ParseRequestError {
             kind,
             error: Some(error.into()),
+            request_id: None,
         }
     }
-}
 
-impl From<ParseActionError> for ParseRequestError {
+    /// Returns the corresponding [`ParseRequestErrorKind`] of the error.
+    pub fn kind(&self) -> ParseRequestErrorKind {
+        self.kind
+    }
+
+    /// Returns an identifier of the request that failed to parse.
+    ///
+    /// The identifier is available only if the request was parsed at least to
+    /// the point where its flow and request identifiers are known. In such
+    /// cases the server can (and should) be notified about the failure.
+    pub fn request_id(&self) -> Option<RequestId> {
+        self.request_id
+    }
 
-    fn from(error: ParseActionError) -> ParseRequestError {
-        ParseRequestErrorKind::InvalidAction(error.kind()).into()
+    /// Associates the error with an identifier of the failed request.
+    fn with_request_id(mut self, request_id: RequestId) -> ParseRequestError {
+        self.request_id = Some(request_id);
+        self
     }
 }
 
@@ -279,6 +497,7 @@ impl std::error::Error for ParseRequestError {
 
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         self.error.as_deref()
+            .map(|error| error as &(dyn std::error::Error + 'static))
     }
 }
 
@@ -287,8 +506,10 @@ impl std::error::Error for ParseRequestError {
 pub enum ParseRequestErrorKind {
     /// The serialized message with request was impossible to deserialize.
     MalformedBytes,
-    /// It was not possible to parse the action specified in the request.
-    InvalidAction(ParseActionErrorKind),
+    /// The CPU time limit specified in the request is invalid.
+    InvalidCpuTimeLimit,
+    /// The real (wall) time limit specified in the request is invalid.
+    InvalidRealTimeLimit,
 }
 
 impl std::fmt::Display for ParseRequestErrorKind {
@@ -298,7 +519,8 @@ impl std::fmt::Display for ParseRequestErrorKind {
 
         match self {
             MalformedBytes => write!(fmt, "malformed protobuf message bytes"),
-            InvalidAction(kind) => write!(fmt, "{}", kind),
+            InvalidCpuTimeLimit => write!(fmt, "invalid CPU time limit"),
+            InvalidRealTimeLimit => write!(fmt, "invalid real time limit"),
         }
     }
 }
@@ -309,6 +531,7 @@ impl From<ParseRequestErrorKind> for ParseRequestError {
         ParseRequestError {
             kind,
             error: None,
+            request_id: None,
         }
     }
 }
@@ -45,28 +45,73 @@ pub struct Reply<I: Item> {
 
 impl<I: Item> Reply<I> {
 
-    /// Sends the reply message through Fleetspeak to the GRR server.
+    /// Sends the reply message through the given transport to the GRR server.
     ///
     /// This function consumes the item to ensure that it is not sent twice.
     ///
     /// Note that this function will not do any network traffic accounting and
     /// should not be used in general. One should almost always prefer to use
-    /// [`Session::reply`] instead.
+    /// [`Session::reply`] instead. However, the number of sent bytes is returned
+    /// so that callers can do the accounting on their own.
     ///
     /// [`Session::reply`]: crate::session::Session::reply
-    pub fn send_unaccounted(self) -> Result<(), fleetspeak::WriteError> {
-        use protobuf::Message as _;
+    pub fn send_unaccounted<T>(self, transport: &T) -> std::io::Result<usize>
+    where
+        T: crate::transport::Transport,
+    {
+        let data = self.into_bytes();
+        let data_len = data.len();
 
-        let data = rrg_proto::v2::rrg::Response::from(self).write_to_bytes()
-            // This should only fail in case we are out of memory, which we are
-            // almost certainly not (and if we are, we have bigger issue).
-            .expect("failed to serialize a result response");
+        transport.send_response(data)?;
+
+        Ok(data_len)
+    }
+
+    /// Serializes the reply message into the wire format.
+    pub fn into_bytes(self) -> Vec<u8> {
+        serialize(rrg_proto::v2::rrg::Response::from(self))
+    }
+}
+
+/// An action log message.
+///
+/// Log messages are sent to the server to inform about events that occurred
+/// during the action execution (e.g. non-critical failures) that would be
+/// otherwise only visible in the on-host logs.
+pub struct Log {
+    /// A unique request identifier for which this log message was emitted.
+    request_id: RequestId,
+    /// A unique response identifier of this log message.
+    response_id: ResponseId,
+    /// The log record captured during the action execution.
+    record: crate::log::Record,
+}
+
+impl Log {
+
+    /// Sends the log message through the given transport to the GRR server.
+    ///
+    /// This function consumes the log message to ensure that it is not sent
+    /// twice.
+    ///
+    /// Note that this function will not do any network traffic accounting. The
+    /// number of sent bytes is returned so that callers can do the accounting
+    /// on their own.
+    pub fn send_unaccounted<T>(self, transport: &T) -> std::io::Result<usize>
+    where
+        T: crate::transport::Transport,
+    {
+        let data = self.into_bytes();
+        let data_len = data.len();
+
+        transport.send_response(data)?;
+
+        Ok(data_len)
+    }
 
-        fleetspeak::send(fleetspeak::Message {
-            service: String::from("GRR"),
-            kind: Some(String::from("rrg.Response")),
-            data,
-        })
+    /// Serializes the log message into the wire format.
+    pub fn into_bytes(self) -> Vec<u8> {
+        serialize(rrg_proto::v2::rrg::Response::from(self))
     }
 }
 
@@ -82,11 +127,32 @@ pub struct Status {
     response_id: ResponseId,
     /// The action execution status.
     result: Result<(), crate::session::Error>,
+    /// Total number of bytes sent by the action.
+    network_bytes_sent: u64,
+    /// Total CPU time spent on executing the action (if known).
+    cpu_time: Option<std::time::Duration>,
+    /// Total real (wall) time spent on executing the action.
+    real_time: std::time::Duration,
 }
 
 impl Status {
 
-    /// Sends the status message through Fleetspeak to the GRR server.
+    /// Sets the total number of bytes sent by the action.
+    pub fn set_network_bytes_sent(&mut self, network_bytes_sent: u64) {
+        self.network_bytes_sent = network_bytes_sent;
+    }
+
+    /// Sets the total CPU time spent on executing the action.
+    pub fn set_cpu_time(&mut self, cpu_time: std::time::Duration) {
+        self.cpu_time = Some(cpu_time);
+    }
+
+    /// Sets the total real (wall) time spent on executing the action.
+    pub fn set_real_time(&mut self, real_time: std::time::Duration) {
+        self.real_time = real_time;
+    }
+
+    /// Sends the status message through the given transport to the GRR server.
     ///
     /// This function consumes the status to ensure that it is not sent twice.
     ///
@@ -94,19 +160,11 @@ impl Status {
     /// accounted version of this function see [`Session::send`].
     ///
     /// [`Session::send`]: crate::session::Session::send
-    pub fn send_unaccounted(self) -> Result<(), fleetspeak::WriteError> {
-        use protobuf::Message as _;
-
-        let data = rrg_proto::v2::rrg::Response::from(self).write_to_bytes()
-            // This should only fail in case we are out of memory, which we are
-            // almost certainly not (and if we are, we have bigger issue).
-            .expect("failed to serialize a status response");
-
-        fleetspeak::send(fleetspeak::Message {
-            service: String::from("GRR"),
-            kind: Some(String::from("rrg.Response")),
-            data,
-        })
+    pub fn send_unaccounted<T>(self, transport: &T) -> std::io::Result<()>
+    where
+        T: crate::transport::Transport,
+    {
+        transport.send_response(serialize(rrg_proto::v2::rrg::Response::from(self)))
     }
 }
 
@@ -144,6 +202,9 @@ impl ResponseBuilder {
             // increment the response id.
             response_id: self.next_response_id,
             result,
+            network_bytes_sent: 0,
+            cpu_time: None,
+            real_time: std::time::Duration::ZERO,
         }
     }
 
@@ -161,6 +222,18 @@ impl ResponseBuilder {
             item,
         }
     }
+
+    /// Builds a new log response for the given captured log record.
+    pub fn log(&mut self, record: crate::log::Record) -> Log {
+        let response_id = self.next_response_id;
+        self.next_response_id.0 += 1;
+
+        Log {
+            request_id: self.request_id,
+            response_id,
+            record,
+        }
+    }
 }
 
 /// Handle to a specific sink.
@@ -220,7 +293,7 @@ impl<I: crate::response::Item> Parcel<I> {
 
 impl<I: crate::response::Item> Parcel<I> {
 
-    /// Sends the parcel message through Fleetspeak to the GRR server.
+    /// Sends the parcel message through the given transport to the GRR server.
     ///
     /// This function consumes the parcel to ensure that it is not sent twice.
     ///
@@ -228,22 +301,45 @@ impl<I: crate::response::Item> Parcel<I> {
     /// of some [session], otherwise network usage might not be correctly
     /// accounted for. Prefer to use [`Session::send`] for such cases.
     ///
+    /// The number of sent bytes is returned so that callers can do the network
+    /// traffic accounting on their own.
+    ///
     /// [session]: crate::session::Session
     /// [`Session::send`]: crate::session::Session::send
-    pub fn send_unaccounted(self) -> Result<(), fleetspeak::WriteError> {
-        use protobuf::Message as _;
+    pub fn send_unaccounted<T>(self, transport: &T) -> std::io::Result<usize>
+    where
+        T: crate::transport::Transport,
+    {
+        let data = self.into_bytes();
+        let data_len = data.len();
 
-        let data = rrg_proto::v2::rrg::Parcel::from(self).write_to_bytes()
-            // This should only fail in case we are out of memory, which we are
-            // almost certainly not (and if we are, we have bigger issue).
-            .unwrap();
+        transport.send_parcel(data)?;
 
-        fleetspeak::send(fleetspeak::Message {
-            service: String::from("GRR"),
-            kind: Some(String::from("rrg.Parcel")),
-            data,
-        })
+        Ok(data_len)
     }
+
+    /// Serializes the parcel message into the wire format.
+    pub fn into_bytes(self) -> Vec<u8> {
+        serialize(rrg_proto::v2::rrg::Parcel::from(self))
+    }
+}
+
+/// Serializes the given protobuf message into the wire format.
+///
+/// Serialized messages are useful for callers that need to know their size
+/// before sending them (e.g. to enforce network traffic limits). They can be
+/// sent using [`Transport::send_response`] or [`Transport::send_parcel`].
+///
+/// [`Transport::send_response`]: crate::transport::Transport::send_response
+/// [`Transport::send_parcel`]: crate::transport::Transport::send_parcel
+fn serialize<M>(message: M) -> Vec<u8>
+where
+    M: protobuf::Message,
+{
+    message.write_to_bytes()
+        // This should only fail in case we are out of memory, which we are
+        // almost certainly not (and if we are, we have bigger issue).
+        .expect("failed to serialize a message")
 }
 
 impl<I> From<Reply<I>> for rrg_proto::v2::rrg::Response
@@ -272,6 +368,42 @@ where
     }
 }
 
+impl From<Log> for rrg_proto::v2::rrg::Response {
+
+    fn from(log: Log) -> rrg_proto::v2::rrg::Response {
+        let mut proto = rrg_proto::v2::rrg::Response::new();
+        proto.set_flow_id(log.request_id.flow_id());
+        proto.set_request_id(log.request_id.request_id());
+        proto.set_response_id(log.response_id.0);
+        proto.set_log(log.record.into());
+
+        proto
+    }
+}
+
+impl From<crate::log::Record> for rrg_proto::v2::rrg::Log {
+
+    fn from(record: crate::log::Record) -> rrg_proto::v2::rrg::Log {
+        use rrg_proto::v2::rrg::Log_Level;
+
+        let level = match record.level {
+            log::Level::Error => Log_Level::ERROR,
+            log::Level::Warn => Log_Level::WARN,
+            log::Level::Info => Log_Level::INFO,
+            // The protocol does not distinguish between debug and trace levels
+            // so we report both of them as debug.
+            log::Level::Debug | log::Level::Trace => Log_Level::DEBUG,
+        };
+
+        let mut proto = rrg_proto::v2::rrg::Log::new();
+        proto.set_level(level);
+        proto.set_timestamp(rrg_proto::into_timestamp(record.timestamp));
+        proto.set_message(record.message);
+
+        proto
+    }
+}
+
 impl From<Status> for rrg_proto::v2::rrg::Response {
 
     fn from(status: Status) -> rrg_proto::v2::rrg::Response {
@@ -292,6 +424,11 @@ impl From<Status> for rrg_proto::v2::rrg::Status {
         if let Err(error) = status.result {
             proto.set_error(error.into());
         }
+        proto.set_network_bytes_sent(status.network_bytes_sent);
+        if let Some(cpu_time) = status.cpu_time {
+            proto.set_cpu_time(rrg_proto::into_duration(cpu_time));
+        }
+        proto.set_real_time(rrg_proto::into_duration(status.real_time));
 
         proto
     }
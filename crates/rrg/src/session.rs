@@ -14,16 +14,20 @@
 //! bytes, action runtime, etc.) and stop the execution if they exceed limits
 //! for a particular request.
 
+mod cancellation;
 mod error;
 
 #[cfg(test)]
 mod fake;
 mod fleetspeak;
+mod local;
 
 #[cfg(test)]
 pub use crate::session::fake::FakeSession;
 pub use crate::session::fleetspeak::FleetspeakSession;
+pub use crate::session::local::LocalSession;
 
+pub use self::cancellation::{cancel, Cancellation};
 pub use self::error::{Error};
 
 /// A specialized `Result` type for sessions.
@@ -40,8 +44,20 @@ pub trait Session {
     where I: crate::response::Item + 'static;
 
     /// Sends a heartbeat signal to the Fleetspeak process.
+    ///
+    /// Long-running actions should call this method periodically to signal
+    /// that the agent is still responsive. Implementations are expected to be
+    /// rate-limited, so it is fine to call it often.
     fn heartbeat(&mut self) {
-        // TODO: Create a real implementation.
+    }
+
+    /// Checks whether the request executed by the session has been cancelled.
+    ///
+    /// Sessions that can be cancelled should make sure that `reply` and `send`
+    /// fail with an appropriate error once the request is cancelled, so that
+    /// actions do not need to call this method on their own.
+    fn is_cancelled(&self) -> bool {
+        false
     }
 }
 
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::RequestId;
+
+/// Flags of all requests that can be currently cancelled.
+static FLAGS: Mutex<Vec<(RequestId, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Cancels the request with the given identifier.
+///
+/// Cancellation is cooperative: the session executing the request is only
+/// notified about it and it is up to the session to stop the action (which
+/// usually happens the next time the action tries to send something).
+///
+/// The returned value indicates whether the request was queued or being
+/// executed at the moment of cancellation.
+pub fn cancel(request_id: RequestId) -> bool {
+    let flags = FLAGS.lock()
+        .expect("failed to acquire cancellation lock");
+
+    let mut cancelled = false;
+    for (flag_request_id, flag) in flags.iter() {
+        if *flag_request_id == request_id {
+            flag.store(true, Ordering::SeqCst);
+            cancelled = true;
+        }
+    }
+
+    cancelled
+}
+
+/// Cancellation flag of a request that is queued or being executed.
+///
+/// While an instance of this type is alive, the request it has been created
+/// for can be cancelled using the [`cancel`] function. The request becomes not
+/// cancellable once the instance is dropped.
+pub struct Cancellation {
+    /// Identifier of the request that can be cancelled.
+    request_id: RequestId,
+    /// Flag indicating whether the request has been cancelled.
+    flag: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+
+    /// Makes the request with the given identifier cancellable.
+    pub fn new(request_id: RequestId) -> Cancellation {
+        let flag = Arc::new(AtomicBool::new(false));
+
+        FLAGS.lock()
+            .expect("failed to acquire cancellation lock")
+            .push((request_id, flag.clone()));
+
+        Cancellation {
+            request_id,
+            flag,
+        }
+    }
+
+    /// Checks whether the request has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Cancellation {
+
+    fn drop(&mut self) {
+        let mut flags = FLAGS.lock()
+            .expect("failed to acquire cancellation lock");
+
+        flags.retain(|(request_id, flag)| {
+            !(*request_id == self.request_id && Arc::ptr_eq(flag, &self.flag))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn cancel_registered() {
+        let cancellation = Cancellation::new(RequestId::new(0xC0FFEE, 1));
+        assert!(!cancellation.is_cancelled());
+
+        assert!(cancel(RequestId::new(0xC0FFEE, 1)));
+        assert!(cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn cancel_other() {
+        let cancellation = Cancellation::new(RequestId::new(0xC0FFEE, 2));
+
+        assert!(!cancel(RequestId::new(0xC0FFEE, 3)));
+        assert!(!cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn cancel_dropped() {
+        drop(Cancellation::new(RequestId::new(0xC0FFEE, 4)));
+
+        assert!(!cancel(RequestId::new(0xC0FFEE, 4)));
+    }
+}
@@ -3,6 +3,7 @@
 // Use of this source code is governed by an MIT-style license that can be found
 // in the LICENSE file or at https://opensource.org/licenses/MIT.
 use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
 
 /// An error type for failures that can occur during a session.
 #[derive(Debug)]
@@ -16,11 +17,21 @@ pub struct Error {
 /// Kinds of errors that can happen during a session.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ErrorKind {
+    /// The requested action is not known or not supported by the agent.
+    UnsupportedAction,
     /// The arguments given for the action were malformed.
     InvalidArgs,
     // strictly necessary, we can be consistent here and rename this variant.
     /// The action execution failed.
     ActionFailure,
+    /// The action sent more bytes over the network than it was allowed to.
+    NetworkBytesSentLimitExceeded,
+    /// The action used more CPU time than it was allowed to.
+    CpuTimeLimitExceeded,
+    /// The action took more real (wall) time than it was allowed to.
+    RealTimeLimitExceeded,
+    /// The action execution was cancelled.
+    Cancelled,
 }
 
 impl Error {
@@ -38,6 +49,60 @@ impl Error {
             error: Box::new(error),
         }
     }
+
+    /// Creates an error for requests invoking an action that is disabled in the
+    /// agent configuration.
+    pub(crate) fn disabled_action(action: crate::request::Action) -> Error {
+        Error {
+            kind: ErrorKind::UnsupportedAction,
+            error: Box::new(DisabledActionError {
+                action,
+            }),
+        }
+    }
+
+    /// Creates an error for sessions that would exceed their network bytes
+    /// limit by sending a message of the given size.
+    pub(super) fn network_bytes_limit(sent: u64, bytes: u64, limit: u64) -> Error {
+        Error {
+            kind: ErrorKind::NetworkBytesSentLimitExceeded,
+            error: Box::new(NetworkBytesLimitError {
+                sent,
+                bytes,
+                limit,
+            }),
+        }
+    }
+
+    /// Creates an error for sessions that exceeded their CPU time limit.
+    pub(super) fn cpu_time_limit(time: Duration, limit: Duration) -> Error {
+        Error {
+            kind: ErrorKind::CpuTimeLimitExceeded,
+            error: Box::new(TimeLimitError {
+                time,
+                limit,
+            }),
+        }
+    }
+
+    /// Creates an error for sessions that have been cancelled.
+    pub(super) fn cancelled() -> Error {
+        Error {
+            kind: ErrorKind::Cancelled,
+            error: Box::new(CancelledError),
+        }
+    }
+
+    /// Creates an error for sessions that exceeded their real time limit.
+    pub(super) fn real_time_limit(time: Duration, limit: Duration) -> Error {
+        Error {
+            kind: ErrorKind::RealTimeLimitExceeded,
+            error: Box::new(TimeLimitError {
+                time,
+                limit,
+            }),
+        }
+    }
 }
 
 impl ErrorKind {
@@ -46,8 +111,13 @@ impl ErrorKind {
         use ErrorKind::*;
 
         match *self {
+            UnsupportedAction => "unsupported action",
             InvalidArgs => "invalid action arguments",
             ActionFailure => "action execution failed",
+            NetworkBytesSentLimitExceeded => "network bytes sent limit exceeded",
+            CpuTimeLimitExceeded => "CPU time limit exceeded",
+            RealTimeLimitExceeded => "real time limit exceeded",
+            Cancelled => "action execution cancelled",
         }
     }
 }
@@ -66,6 +136,101 @@ impl std::error::Error for Error {
     }
 }
 
+/// An error type for sessions that would exceed their network bytes limit.
+#[derive(Debug)]
+struct NetworkBytesLimitError {
+    /// Number of bytes sent by the session so far.
+    sent: u64,
+    /// Size of the message that the session attempted to send.
+    bytes: u64,
+    /// Maximum number of bytes the session was allowed to send.
+    limit: u64,
+}
+
+impl Display for NetworkBytesLimitError {
+
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write! {
+            fmt,
+            "sending {} bytes would exceed the limit (sent: {} bytes, limit: {} bytes)",
+            self.bytes, self.sent, self.limit
+        }
+    }
+}
+
+impl std::error::Error for NetworkBytesLimitError {
+}
+
+/// An error type for sessions that exceeded one of their time limits.
+#[derive(Debug)]
+struct TimeLimitError {
+    /// Time taken by the session.
+    time: Duration,
+    /// Maximum time the session was allowed to take.
+    limit: Duration,
+}
+
+impl Display for TimeLimitError {
+
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write!(fmt, "took {:?} (limit: {:?})", self.time, self.limit)
+    }
+}
+
+impl std::error::Error for TimeLimitError {
+}
+
+/// An error type for requests invoking actions disabled in the configuration.
+#[derive(Debug)]
+struct DisabledActionError {
+    /// The action that the request invoked.
+    action: crate::request::Action,
+}
+
+impl Display for DisabledActionError {
+
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write!(fmt, "action {:?} is disabled", self.action)
+    }
+}
+
+impl std::error::Error for DisabledActionError {
+}
+
+/// An error type for sessions that have been cancelled.
+#[derive(Debug)]
+struct CancelledError;
+
+impl Display for CancelledError {
+
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write!(fmt, "cancellation requested by the server")
+    }
+}
+
+impl std::error::Error for CancelledError {
+}
+
+impl From<crate::request::ParseActionError> for Error {
+
+    fn from(error: crate::request::ParseActionError) -> Error {
+        Error {
+            kind: ErrorKind::UnsupportedAction,
+            error: Box::new(error),
+        }
+    }
+}
+
+impl From<crate::request::ParseRequestError> for Error {
+
+    fn from(error: crate::request::ParseRequestError) -> Error {
+        Error {
+            kind: ErrorKind::InvalidArgs,
+            error: Box::new(error),
+        }
+    }
+}
+
 impl From<crate::request::ParseArgsError> for Error {
 
     fn from(error: crate::request::ParseArgsError) -> Error {
@@ -93,8 +258,13 @@ impl From<ErrorKind> for rrg_proto::v2::rrg::Status_Error_Type {
         use ErrorKind::*;
 
         match kind {
+            UnsupportedAction => Self::UNSUPPORTED_ACTION,
             InvalidArgs => Self::INVALID_ARGS,
             ActionFailure => Self::ACTION_FAILURE,
+            NetworkBytesSentLimitExceeded => Self::NETWORK_BYTES_SENT_LIMIT_EXCEEDED,
+            CpuTimeLimitExceeded => Self::CPU_TIME_LIMIT_EXCEEDED,
+            RealTimeLimitExceeded => Self::REAL_TIME_LIMIT_EXCEEDED,
+            Cancelled => Self::CANCELLED,
         }
     }
 }
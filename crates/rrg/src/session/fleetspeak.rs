@@ -3,16 +3,60 @@
 /// This is a normal session type that that is associated with some flow on the
 /// server. It keeps track of the responses it sends and collects statistics
 /// about network and runtime utilization to kill the action if it is needed.
-pub struct FleetspeakSession {
+///
+/// Despite its name, the session can communicate with the server through any
+/// [transport] (Fleetspeak is the one used in production).
+///
+/// [transport]: crate::transport::Transport
+pub struct FleetspeakSession<'t, T: crate::transport::Transport> {
+    /// Transport through which responses are sent to the server.
+    transport: &'t T,
+    /// Heartbeat signal shared with the thread heartbeating in the background.
+    heartbeat: &'t Heartbeat<'t, T>,
     response_builder: crate::ResponseBuilder,
+    /// Number of bytes sent by the session so far.
+    network_bytes_sent: u64,
+    /// Maximum number of bytes the session is allowed to send.
+    network_bytes_limit: Option<u64>,
+    /// Time at which the session was started.
+    start_time: std::time::Instant,
+    /// CPU time consumed by the session thread at the start of the session.
+    start_cpu_time: Option<std::time::Duration>,
+    /// Maximum CPU time the session is allowed to take.
+    cpu_time_limit: Option<std::time::Duration>,
+    /// Maximum real (wall) time the session is allowed to take.
+    real_time_limit: Option<std::time::Duration>,
+    /// Capture of logs emitted during the session that are sent to the server.
+    log_capture: crate::log::Capture,
+    /// Scope associating logs emitted during the session with the request (it
+    /// is never read, it only needs to live as long as the session).
+    _log_scope: crate::log::RequestScope,
+    /// Flag that allows the session to be cancelled.
+    cancellation: crate::session::Cancellation,
 }
 
-impl FleetspeakSession {
+impl<'t, T: crate::transport::Transport> FleetspeakSession<'t, T> {
 
     /// Creates a new Fleetspeak session for the given `request` object.
-    fn new(request_id: crate::RequestId) -> FleetspeakSession {
+    fn new(
+        transport: &'t T,
+        heartbeat: &'t Heartbeat<'t, T>,
+        request: &crate::Request,
+        cancellation: crate::session::Cancellation,
+    ) -> FleetspeakSession<'t, T> {
         FleetspeakSession {
-            response_builder: crate::ResponseBuilder::new(request_id),
+            transport,
+            heartbeat,
+            response_builder: crate::ResponseBuilder::new(request.id()),
+            network_bytes_sent: 0,
+            network_bytes_limit: request.network_bytes_limit(),
+            start_time: std::time::Instant::now(),
+            start_cpu_time: thread_cpu_time(),
+            cpu_time_limit: request.cpu_time_limit(),
+            real_time_limit: request.real_time_limit(),
+            log_capture: crate::log::Capture::new(request.log_level()),
+            _log_scope: crate::log::RequestScope::new(request.id()),
+            cancellation,
         }
     }
 
@@ -20,53 +64,436 @@ impl FleetspeakSession {
     ///
     /// This is the main entry point of the session. It processes the request
     /// and sends the execution status back to the server.
-    pub fn dispatch(request: crate::Request) {
-        let mut session = FleetspeakSession::new(request.id());
+    ///
+    /// While the action is executing, heartbeat signals are sent in the
+    /// background at the specified rate, so that long-running actions do not
+    /// get the agent killed for unresponsiveness.
+    ///
+    /// The given cancellation flag should be registered for the request. If
+    /// the request has been cancelled before its execution started (e.g. while
+    /// it was waiting for a free worker), the action is not executed at all.
+    pub fn dispatch(
+        transport: &'t T,
+        heartbeat_rate: std::time::Duration,
+        request: crate::Request,
+        cancellation: crate::session::Cancellation,
+    )
+    where
+        T: Sync,
+    {
+        let heartbeat = Heartbeat::new(transport, heartbeat_rate);
+        let mut session = FleetspeakSession::new(transport, &heartbeat, &request, cancellation);
+
+        // Requests can be cancelled while they are waiting for a free worker,
+        // in which case there is no point in starting the action at all.
+        let result = if session.cancellation.is_cancelled() {
+            Err(crate::session::Error::cancelled())
+        } else {
+            std::thread::scope(|scope| {
+                let (stop_sender, stop_receiver) = std::sync::mpsc::channel::<()>();
+
+                let heartbeat = &heartbeat;
+                scope.spawn(move || {
+                    use std::sync::mpsc::RecvTimeoutError::*;
+
+                    // The loop ends once the sender is dropped (which happens
+                    // when the action execution finishes).
+                    while let Err(Timeout) = stop_receiver.recv_timeout(heartbeat_rate) {
+                        heartbeat.beat();
+                    }
+                });
 
-        let result = crate::action::dispatch(&mut session, request);
-        let status = session.response_builder.status(result);
+                let result = crate::action::dispatch(&mut session, request);
+                drop(stop_sender);
 
-        status.send_unaccounted()
-            // If we fail to send the response to Fleetspeak, our connection is
+                result
+            })
+        };
+
+        // Logs emitted at the very end of the action execution have not been
+        // sent yet, so we do it now. Note that if the action itself failed, its
+        // error is more important than the one that might occur here.
+        let result = match session.send_logs() {
+            Err(error) if result.is_ok() => Err(error),
+            _ => result,
+        };
+
+        let real_time = session.real_time();
+        let cpu_time = session.cpu_time();
+
+        let mut status = session.response_builder.status(result);
+        status.set_network_bytes_sent(session.network_bytes_sent);
+        status.set_real_time(real_time);
+        if let Some(cpu_time) = cpu_time {
+            status.set_cpu_time(cpu_time);
+        }
+
+        status.send_unaccounted(transport)
+            // If we fail to send the response to the server, our connection is
             // most likely broken and we should die. In general, this should not
             // happen.
-            .expect("failed to send a status response to Fleetspeak");
+            .expect("failed to send a status response");
 
         // TODO(panhania@): Consider returning the status so that the parent can
         // log appropriate message.
     }
+
+    /// Accounts for the given number of bytes about to be sent over the network.
+    ///
+    /// This function should be called before the message is actually sent, so
+    /// that no message that would exceed the limit ever leaves the agent.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if sending the given number of bytes
+    /// would make the session exceed its limit on the number of bytes it is
+    /// allowed to send. In such a case, the bytes are not accounted for.
+    fn account_network_bytes(&mut self, bytes: usize) -> crate::session::Result<()> {
+        let bytes = bytes as u64;
+
+        match self.network_bytes_limit {
+            Some(limit) if self.network_bytes_sent.saturating_add(bytes) > limit => {
+                let sent = self.network_bytes_sent;
+                return Err(crate::session::Error::network_bytes_limit(sent, bytes, limit));
+            }
+            _ => (),
+        }
+
+        self.network_bytes_sent += bytes;
+
+        Ok(())
+    }
+
+    /// Sends all the logs captured so far to the server.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if sending the logs made the session
+    /// exceed its limit on the number of bytes it is allowed to send. In such
+    /// a case the remaining logs are discarded.
+    fn send_logs(&mut self) -> crate::session::Result<()> {
+        for record in self.log_capture.take() {
+            let data = self.response_builder.log(record).into_bytes();
+            self.account_network_bytes(data.len())?;
+
+            self.transport.send_response(data)
+                // If we fail to send the response to the server, our connection
+                // is most likely broken and we should die. In general, this
+                // should not happen.
+                .expect("failed to send a log response");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the real (wall) time elapsed since the start of the session.
+    fn real_time(&self) -> std::time::Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Returns the CPU time consumed since the start of the session.
+    ///
+    /// If it was not possible to determine the CPU time, `None` is returned.
+    fn cpu_time(&self) -> Option<std::time::Duration> {
+        let start_cpu_time = self.start_cpu_time?;
+        let cpu_time = thread_cpu_time()?;
+
+        Some(cpu_time.saturating_sub(start_cpu_time))
+    }
+
+    /// Verifies that the session has not been cancelled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the session has been cancelled.
+    fn check_cancelled(&self) -> crate::session::Result<()> {
+        if self.cancellation.is_cancelled() {
+            return Err(crate::session::Error::cancelled());
+        }
+
+        Ok(())
+    }
+
+    /// Verifies that the session did not exceed its time limits.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the session exceeded either its
+    /// real time or its CPU time limit.
+    fn check_time_limits(&self) -> crate::session::Result<()> {
+        if let Some(limit) = self.real_time_limit {
+            let real_time = self.real_time();
+            if real_time > limit {
+                return Err(crate::session::Error::real_time_limit(real_time, limit));
+            }
+        }
+
+        if let Some(limit) = self.cpu_time_limit {
+            if let Some(cpu_time) = self.cpu_time() {
+                if cpu_time > limit {
+                    return Err(crate::session::Error::cpu_time_limit(cpu_time, limit));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Heartbeat signal sent to the server no more frequently than at a given rate.
+///
+/// Heartbeats can be sent both by the session itself and by a thread running in
+/// the background, so the time of the last heartbeat is synchronized.
+struct Heartbeat<'t, T: crate::transport::Transport> {
+    /// Transport through which heartbeat signals are sent.
+    transport: &'t T,
+    /// Minimum time between two consecutive heartbeat signals.
+    rate: std::time::Duration,
+    /// Time at which the last heartbeat signal was sent (if any).
+    last_time: std::sync::Mutex<Option<std::time::Instant>>,
 }
 
-impl crate::session::Session for FleetspeakSession {
+impl<'t, T: crate::transport::Transport> Heartbeat<'t, T> {
+
+    /// Creates a new heartbeat signal with the given rate.
+    fn new(transport: &'t T, rate: std::time::Duration) -> Heartbeat<'t, T> {
+        Heartbeat {
+            transport,
+            rate,
+            last_time: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Sends a heartbeat signal unless one has been sent recently.
+    fn beat(&self) {
+        let mut last_time = self.last_time.lock()
+            .expect("failed to acquire heartbeat lock");
+
+        match *last_time {
+            Some(last_time) if last_time.elapsed() < self.rate => return,
+            _ => (),
+        }
+
+        self.transport.heartbeat()
+            // If we fail to send the heartbeat, our connection is most likely
+            // broken and we should die. In general, this should not happen.
+            .expect("failed to send a heartbeat");
+
+        *last_time = Some(std::time::Instant::now());
+    }
+}
+
+/// Returns the CPU time consumed by the current thread so far.
+///
+/// Sessions are executed on a single thread each (possibly in parallel to other
+/// sessions), so this is the CPU time that the session can be accounted for.
+///
+/// Failures are logged and reported as `None`, as they should not prevent the
+/// action from running.
+fn thread_cpu_time() -> Option<std::time::Duration> {
+    match ospect::proc::thread_cpu_time() {
+        Ok(cpu_time) => Some(cpu_time),
+        Err(error) => {
+            log::warn!("failed to obtain thread CPU time: {}", error);
+            None
+        }
+    }
+}
+
+impl<'t, T> crate::session::Session for FleetspeakSession<'t, T>
+where
+    T: crate::transport::Transport,
+{
 
     fn reply<I>(&mut self, item: I) -> crate::session::Result<()>
     where
         I: crate::response::Item,
     {
-        // TODO(panhania@): Enforce limits.
-        let reply = self.response_builder.reply(item);
+        self.check_cancelled()?;
+
+        // Logs are sent first so that they preserve the order in which they
+        // were emitted relative to the results.
+        self.send_logs()?;
 
-        reply.send_unaccounted()
-            // If we fail to send the response to Fleetspeak, our connection is
+        let data = self.response_builder.reply(item).into_bytes();
+        self.account_network_bytes(data.len())?;
+
+        self.transport.send_response(data)
+            // If we fail to send the response to the server, our connection is
             // most likely broken and we should die. In general, this should not
             // happen.
-            .expect("failed to send a result response to Fleetspeak");
+            .expect("failed to send a result response");
 
-        Ok(())
+        self.check_time_limits()
     }
 
     fn send<I>(&mut self, sink: crate::Sink, item: I) -> crate::session::Result<()>
     where
         I: crate::response::Item,
     {
-        let parcel = crate::response::Parcel::new(sink, item);
+        self.check_cancelled()?;
+        self.send_logs()?;
 
-        parcel.send_unaccounted()
-            // If we fail to send the parcel to Fleetspeak, our connection is
+        let data = crate::response::Parcel::new(sink, item).into_bytes();
+        self.account_network_bytes(data.len())?;
+
+        self.transport.send_parcel(data)
+            // If we fail to send the parcel to the server, our connection is
             // most likely broken and we should die. In general, this should not
             // happen.
-            .expect("failed to send a parcel to Fleetspeak");
+            .expect("failed to send a parcel");
 
-        Ok(())
+        self.check_time_limits()
+    }
+
+    fn heartbeat(&mut self) {
+        self.heartbeat.beat();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn heartbeat_rate_limited() {
+        let transport = FakeTransport::default();
+        let heartbeat = Heartbeat::new(&transport, std::time::Duration::MAX);
+
+        heartbeat.beat();
+        heartbeat.beat();
+        heartbeat.beat();
+
+        assert_eq!(transport.count.get(), 1);
+    }
+
+    #[test]
+    fn heartbeat_after_rate() {
+        let transport = FakeTransport::default();
+        let heartbeat = Heartbeat::new(&transport, std::time::Duration::ZERO);
+
+        heartbeat.beat();
+        heartbeat.beat();
+
+        assert_eq!(transport.count.get(), 2);
+    }
+
+    #[test]
+    fn reply_cancelled() {
+        use crate::session::Session as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xCA4CE1);
+        request.set_request_id(42);
+
+        let request = crate::Request::try_from(request).unwrap();
+
+        let transport = FakeTransport::default();
+        let heartbeat = Heartbeat::new(&transport, std::time::Duration::MAX);
+        let cancellation = crate::session::Cancellation::new(request.id());
+        let mut session = FleetspeakSession::new(&transport, &heartbeat, &request, cancellation);
+
+        assert!(session.reply(()).is_ok());
+        assert!(crate::session::cancel(request.id()));
+        assert!(session.reply(()).is_err());
+        assert!(session.send(crate::Sink::Blob, ()).is_err());
+    }
+
+    #[test]
+    fn reply_network_bytes_limit() {
+        use crate::session::Session as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xB1E55ED);
+        request.set_request_id(42);
+
+        // Replies with small response identifiers all have the same size, so we
+        // set the limit to allow exactly one of them.
+        let reply_len = crate::ResponseBuilder::new(request_id(&request))
+            .reply(())
+            .into_bytes()
+            .len();
+        request.set_network_bytes_sent_limit(reply_len as u64);
+
+        let request = crate::Request::try_from(request).unwrap();
+
+        let transport = FakeTransport::default();
+        let heartbeat = Heartbeat::new(&transport, std::time::Duration::MAX);
+        let cancellation = crate::session::Cancellation::new(request.id());
+        let mut session = FleetspeakSession::new(&transport, &heartbeat, &request, cancellation);
+
+        assert!(session.reply(()).is_ok());
+        assert!(session.reply(()).is_err());
+
+        assert_eq!(transport.sent.get(), 1);
+        assert_eq!(session.network_bytes_sent, reply_len as u64);
+    }
+
+    #[test]
+    fn send_network_bytes_limit() {
+        use crate::session::Session as _;
+
+        let mut request = rrg_proto::v2::rrg::Request::new();
+        request.set_flow_id(0xB1E55ED);
+        request.set_request_id(42);
+        request.set_network_bytes_sent_limit(1);
+
+        let request = crate::Request::try_from(request).unwrap();
+
+        let transport = FakeTransport::default();
+        let heartbeat = Heartbeat::new(&transport, std::time::Duration::MAX);
+        let cancellation = crate::session::Cancellation::new(request.id());
+        let mut session = FleetspeakSession::new(&transport, &heartbeat, &request, cancellation);
+
+        let error = session.send(crate::Sink::Blob, ()).unwrap_err();
+        assert_eq! {
+            rrg_proto::v2::rrg::Status_Error::from(error).get_field_type(),
+            rrg_proto::v2::rrg::Status_Error_Type::NETWORK_BYTES_SENT_LIMIT_EXCEEDED
+        };
+
+        assert_eq!(transport.sent.get(), 0);
+        assert_eq!(session.network_bytes_sent, 0);
+    }
+
+    /// Returns the identifier of the given request message.
+    fn request_id(request: &rrg_proto::v2::rrg::Request) -> crate::RequestId {
+        crate::Request::try_from(request.clone()).unwrap().id()
+    }
+
+    /// A transport that discards all messages and counts them and heartbeat
+    /// signals.
+    #[derive(Default)]
+    struct FakeTransport {
+        /// Number of heartbeat signals sent through the transport.
+        count: std::cell::Cell<usize>,
+        /// Number of responses and parcels sent through the transport.
+        sent: std::cell::Cell<usize>,
+    }
+
+    impl crate::transport::Transport for FakeTransport {
+
+        fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn send_response(&self, _: Vec<u8>) -> std::io::Result<()> {
+            self.sent.set(self.sent.get() + 1);
+            Ok(())
+        }
+
+        fn send_parcel(&self, _: Vec<u8>) -> std::io::Result<()> {
+            self.sent.set(self.sent.get() + 1);
+            Ok(())
+        }
+
+        fn heartbeat(&self) -> std::io::Result<()> {
+            self.count.set(self.count.get() + 1);
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,151 @@
+use std::io::Write;
+
+use crate::args::Format;
+
+/// A session implementation that prints all responses to a local stream.
+///
+/// This session is not associated with any flow on the server. It is intended
+/// to be used for running actions by hand (e.g. to reproduce agent behaviour on
+/// a particular host while debugging a flow).
+///
+/// Every reply, parcel and the final status is printed as a single line in the
+/// chosen format.
+pub struct LocalSession<W: Write> {
+    /// Stream to print the responses to.
+    output: W,
+    /// Format in which the responses are printed.
+    format: Format,
+    /// Time at which the session was started.
+    start_time: std::time::Instant,
+}
+
+impl<W: Write> LocalSession<W> {
+
+    /// Creates a new local session printing to `output` in the given `format`.
+    pub fn new(output: W, format: Format) -> LocalSession<W> {
+        LocalSession {
+            output,
+            format,
+            start_time: std::time::Instant::now(),
+        }
+    }
+
+    /// Dispatches the given `request` to an appropriate action handler.
+    ///
+    /// Once the action finishes, its execution status is printed as well. The
+    /// printed status is also returned so that the caller can act upon it.
+    pub fn dispatch(output: W, format: Format, request: crate::Request) -> rrg_proto::v2::rrg::Status {
+        let request_id = request.id();
+        let mut session = LocalSession::new(output, format);
+
+        let result = crate::action::dispatch(&mut session, request);
+
+        let mut status = crate::ResponseBuilder::new(request_id).status(result);
+        status.set_real_time(session.start_time.elapsed());
+
+        let status = rrg_proto::v2::rrg::Status::from(status);
+        let status_repr = session.repr(&status);
+        session.print("status", &status_repr);
+
+        status
+    }
+
+    /// Returns a representation of the given message in the session format.
+    fn repr(&self, proto: &dyn protobuf::Message) -> String {
+        match self.format {
+            Format::Text => {
+                match protobuf::text_format::print_to_string(proto).trim() {
+                    "" => String::from("{}"),
+                    text => format!("{{ {text} }}"),
+                }
+            }
+            Format::Json => rrg_proto::text::print_json(proto),
+        }
+    }
+
+    /// Prints a single response with the given label and representation.
+    fn print(&mut self, label: &str, repr: &str) {
+        let line = match self.format {
+            Format::Text => format!("{label} {repr}"),
+            Format::Json => format!("{{\"{label}\":{repr}}}"),
+        };
+
+        // Local sessions are used only interactively, so there is not much we
+        // can do if the output is broken other than to fail loudly.
+        writeln!(self.output, "{line}")
+            .expect("failed to write to the session output");
+    }
+}
+
+impl<W: Write> crate::session::Session for LocalSession<W> {
+
+    fn reply<I>(&mut self, item: I) -> crate::session::Result<()>
+    where
+        I: crate::response::Item,
+    {
+        let repr = self.repr(&item.into_proto());
+        self.print("reply", &repr);
+
+        Ok(())
+    }
+
+    fn send<I>(&mut self, sink: crate::Sink, item: I) -> crate::session::Result<()>
+    where
+        I: crate::response::Item,
+    {
+        // We do not print the parcel message as is, since its payload is packed
+        // into `google.protobuf.Any` and would not be human-readable.
+        let sink = rrg_proto::v2::rrg::Sink::from(sink);
+        let payload_repr = self.repr(&item.into_proto());
+
+        let repr = match self.format {
+            Format::Text => format!("{{ sink: {sink:?} payload {payload_repr} }}"),
+            Format::Json => {
+                format!("{{\"sink\":\"{sink:?}\",\"payload\":{payload_repr}}}")
+            }
+        };
+        self.print("parcel", &repr);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::session::Session as _;
+
+    #[test]
+    fn reply_text() {
+        let mut output = Vec::new();
+
+        let mut session = LocalSession::new(&mut output, Format::Text);
+        session.reply(()).unwrap();
+        session.reply(()).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "reply {}\nreply {}\n");
+    }
+
+    #[test]
+    fn reply_json() {
+        let mut output = Vec::new();
+
+        let mut session = LocalSession::new(&mut output, Format::Json);
+        session.reply(()).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"reply\":{}}\n");
+    }
+
+    #[test]
+    fn send_json() {
+        let mut output = Vec::new();
+
+        let mut session = LocalSession::new(&mut output, Format::Json);
+        session.send(crate::Sink::Blob, ()).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "{\"parcel\":{\"sink\":\"BLOB\",\"payload\":{}}}\n");
+    }
+}
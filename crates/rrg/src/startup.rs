@@ -6,11 +6,16 @@
 // TODO(panhania): Add support for binary paths in the `Metadata` object.
 
 /// Sends a system message with startup information to the GRR server.
-pub fn startup() -> Result<(), fleetspeak::WriteError> {
+pub fn startup<T>(transport: &T) -> std::io::Result<()>
+where
+    T: crate::transport::Transport,
+{
     let startup = Startup::now();
 
     crate::response::Parcel::new(crate::Sink::Startup, startup)
-        .send_unaccounted()
+        .send_unaccounted(transport)?;
+
+    Ok(())
 }
 
 /// Information about the agent startup.
@@ -64,6 +69,8 @@ pub struct Metadata {
     pub name: String,
     /// Version of the RRG agent.
     pub version: Version,
+    /// Time at which the RRG agent executable was built.
+    pub build_time: Option<std::time::SystemTime>,
 }
 
 impl Metadata {
@@ -76,10 +83,22 @@ impl Metadata {
         Metadata {
             name: String::from(env!("CARGO_PKG_NAME")),
             version: Version::from_cargo(),
+            build_time: build_time(),
         }
     }
 }
 
+/// Returns the time at which the agent executable was built.
+///
+/// The build time is determined by the build script and embedded in the
+/// executable as a number of seconds since the Unix epoch. In case it is
+/// malformed for whatever reason, `None` is returned.
+fn build_time() -> Option<std::time::SystemTime> {
+    let secs = env!("RRG_BUILD_TIME").parse().ok()?;
+
+    std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs))
+}
+
 /// A type for representing version metadata.
 pub struct Version {
     /// Major version of the RRG agent (`x` in `x.y.z`).
@@ -130,8 +149,10 @@ impl Into<rrg_proto::v2::startup::Metadata> for Metadata {
     fn into(self) -> rrg_proto::v2::startup::Metadata {
         let mut proto = rrg_proto::v2::startup::Metadata::new();
         proto.set_name(self.name);
-        // TODO(@panhania): Add support for remaining fields.
         proto.set_version(self.version.into());
+        if let Some(build_time) = self.build_time {
+            proto.set_build_time(rrg_proto::into_timestamp(build_time));
+        }
 
         proto
     }
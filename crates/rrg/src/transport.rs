@@ -0,0 +1,51 @@
+// Copyright 2023 Google LLC
+//
+// Use of this source code is governed by an MIT-style license that can be found
+// in the LICENSE file or at https://opensource.org/licenses/MIT.
+
+//! Utilities for communicating with the server.
+//!
+//! Normally, the agent talks to the GRR server through the Fleetspeak client
+//! running on the same machine. However, for testing and debugging purposes it
+//! is useful to be able to drive the agent without Fleetspeak. Transports are
+//! an abstraction over the channel through which the agent receives requests
+//! and sends responses back.
+
+mod fleetspeak;
+#[cfg(feature = "spool")]
+mod spool;
+mod stream;
+
+pub use self::fleetspeak::FleetspeakTransport;
+#[cfg(feature = "spool")]
+pub use self::spool::SpoolTransport;
+pub use self::stream::StreamTransport;
+
+/// Abstraction for channels used to communicate with the server.
+///
+/// Transports operate on serialized Protocol Buffers messages. The agent only
+/// receives `rrg.Request` messages and sends `rrg.Response` and `rrg.Parcel`
+/// messages.
+///
+/// Requests can be executed concurrently, so transports shared between threads
+/// must guarantee that each message is sent as a whole and not interleaved with
+/// messages sent by other threads.
+pub trait Transport {
+    /// Awaits for a new serialized `rrg.Request` message.
+    ///
+    /// This will suspend execution until the request is actually available. If
+    /// the transport has been closed and there will be no more requests, `None`
+    /// is returned.
+    fn receive(&self) -> std::io::Result<Option<Vec<u8>>>;
+
+    /// Sends a serialized `rrg.Response` message.
+    fn send_response(&self, data: Vec<u8>) -> std::io::Result<()>;
+
+    /// Sends a serialized `rrg.Parcel` message.
+    fn send_parcel(&self, data: Vec<u8>) -> std::io::Result<()>;
+
+    /// Sends a heartbeat signal indicating that the agent is still responsive.
+    ///
+    /// Transports that do not watch for unresponsiveness can ignore this.
+    fn heartbeat(&self) -> std::io::Result<()>;
+}
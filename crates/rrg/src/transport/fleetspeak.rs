@@ -0,0 +1,69 @@
+use rrg_macro::warn;
+
+/// A transport that communicates with the server through Fleetspeak.
+///
+/// This is the standard transport that the agent uses in production.
+pub struct FleetspeakTransport {
+    /// Frequency of heartbeat messages sent while waiting for requests.
+    heartbeat_rate: std::time::Duration,
+}
+
+impl FleetspeakTransport {
+
+    /// Creates a new Fleetspeak transport.
+    ///
+    /// While waiting for requests, the transport will keep heartbeating at the
+    /// specified rate to ensure that Fleetspeak does not kill the agent for
+    /// unresponsiveness.
+    pub fn new(heartbeat_rate: std::time::Duration) -> FleetspeakTransport {
+        FleetspeakTransport {
+            heartbeat_rate,
+        }
+    }
+}
+
+impl super::Transport for FleetspeakTransport {
+
+    fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+        let message = ::fleetspeak::receive_with_heartbeat(self.heartbeat_rate)?;
+
+        if message.service != "GRR" {
+            let service = message.service;
+            warn!("request send by service '{service}' (instead of 'GRR')");
+        }
+        if message.kind.as_deref() != Some("rrg.Request") {
+            match message.kind {
+                Some(kind) => warn!("request with unexpected kind '{kind}'"),
+                None => warn!("request with unspecified kind"),
+            }
+        }
+
+        Ok(Some(message.data))
+    }
+
+    fn send_response(&self, data: Vec<u8>) -> std::io::Result<()> {
+        ::fleetspeak::send(::fleetspeak::Message {
+            service: String::from("GRR"),
+            kind: Some(String::from("rrg.Response")),
+            data,
+        })?;
+
+        Ok(())
+    }
+
+    fn send_parcel(&self, data: Vec<u8>) -> std::io::Result<()> {
+        ::fleetspeak::send(::fleetspeak::Message {
+            service: String::from("GRR"),
+            kind: Some(String::from("rrg.Parcel")),
+            data,
+        })?;
+
+        Ok(())
+    }
+
+    fn heartbeat(&self) -> std::io::Result<()> {
+        ::fleetspeak::heartbeat()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,607 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use protobuf::Message as _;
+use protobuf::well_known_types::{Any, UInt64Value};
+
+/// Type URL of spool entries with serialized `rrg.Response` messages.
+const RESPONSE_TYPE_URL: &str = "type.googleapis.com/rrg.Response";
+
+/// Type URL of spool entries with serialized `rrg.Parcel` messages.
+const PARCEL_TYPE_URL: &str = "type.googleapis.com/rrg.Parcel";
+
+/// Type URL of spool entries marking other entries as delivered.
+const DELIVERED_TYPE_URL: &str = "type.googleapis.com/google.protobuf.UInt64Value";
+
+/// Extension of spool segment files.
+const SEGMENT_EXTENSION: &str = "gzc";
+
+/// A rough size limit after which new messages go to a fresh segment.
+const SEGMENT_SIZE_LIMIT: u64 = 8 * 1024 * 1024; // 8 MiB.
+
+/// A transport that persists outgoing messages on disk before sending them.
+///
+/// Every response and parcel is first appended to a segment file in the spool
+/// directory and only then passed to the underlying transport. Once the message
+/// is sent, a delivery marker is appended to the segment as well. Segments use
+/// the [gzchunked] format (each entry is a separate gzip member) and are never
+/// modified in place.
+///
+/// Once a segment grows over a size limit, new messages are appended to a fresh
+/// one. The old segment is removed as soon as all of its messages are marked as
+/// delivered (which can happen later if some of them were still being sent at
+/// the time of the switch).
+///
+/// If the agent is restarted (e.g. because it crashed or the connection to the
+/// server was broken), all the messages that have not been marked as delivered
+/// can be sent again using the [`replay`] method. Responses are deduplicated by
+/// their `(flow_id, request_id, response_id)` triple, so a response is replayed
+/// at most once even if it ended up in multiple segments.
+///
+/// Note that segments are flushed but not synced after each write, so messages
+/// survive agent crashes but not necessarily system crashes.
+///
+/// [gzchunked]: crate::gzchunked
+/// [`replay`]: SpoolTransport::replay
+pub struct SpoolTransport<T: super::Transport> {
+    /// Underlying transport used to actually deliver the messages.
+    inner: T,
+    /// Directory in which the segment files are stored.
+    path: PathBuf,
+    /// Segments of this run that the messages are appended to.
+    segments: Mutex<Segments>,
+    /// Messages from earlier segments that are awaiting replay.
+    pending: Mutex<Pending>,
+}
+
+/// Messages left by earlier runs that are awaiting replay.
+#[derive(Default)]
+struct Pending {
+    /// Undelivered message entries in the order they were spooled.
+    entries: Vec<Any>,
+    /// Paths to the segment files the entries were read from.
+    segment_paths: Vec<PathBuf>,
+}
+
+/// Segments written by the current run.
+struct Segments {
+    /// Segment that new messages are appended to.
+    current: Segment,
+    /// Earlier segments that still have messages not marked as delivered.
+    retired: Vec<Segment>,
+}
+
+/// State of a segment the messages are appended to.
+struct Segment {
+    /// Path to the segment file.
+    path: PathBuf,
+    /// Handle to the segment file opened in the append mode.
+    file: File,
+    /// Number of bytes written to the segment file so far.
+    len: u64,
+    /// Number of message entries (not counting markers) in the segment so far.
+    count: u64,
+    /// Number of message entries that are not marked as delivered yet.
+    undelivered: u64,
+    /// Number of the segment used to derive segment file names.
+    number: u64,
+}
+
+impl<T: super::Transport> SpoolTransport<T> {
+
+    /// Opens a spool in the given directory on top of the `inner` transport.
+    ///
+    /// The directory is created if it does not exist. Undelivered messages from
+    /// segments left by earlier runs are loaded, but they are not sent until
+    /// [`replay`] is called.
+    ///
+    /// [`replay`]: SpoolTransport::replay
+    pub fn open<P>(path: P, inner: T) -> std::io::Result<SpoolTransport<T>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&path)?;
+
+        let mut numbers = Vec::new();
+        for entry in std::fs::read_dir(&path)? {
+            let entry_path = entry?.path();
+            if entry_path.extension() != Some(SEGMENT_EXTENSION.as_ref()) {
+                continue;
+            }
+
+            let number = entry_path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok());
+            match number {
+                Some(number) => numbers.push(number),
+                None => log::warn! {
+                    "unexpected file in the spool directory: {}",
+                    entry_path.display()
+                },
+            }
+        }
+        numbers.sort_unstable();
+
+        let mut pending = Pending::default();
+        let mut response_ids = HashSet::new();
+        for number in &numbers {
+            let segment_path = segment_path(&path, *number);
+            for entry in read_segment(&segment_path)? {
+                if entry.get_type_url() == RESPONSE_TYPE_URL {
+                    // Responses that cannot be parsed are still replayed, it is
+                    // up to the server to decide what to do with them.
+                    if let Some(response_id) = response_id(&entry) {
+                        if !response_ids.insert(response_id) {
+                            continue;
+                        }
+                    }
+                }
+                pending.entries.push(entry);
+            }
+            pending.segment_paths.push(segment_path);
+        }
+
+        let number = numbers.last().map_or(0, |number| number + 1);
+        let segments = Segments {
+            current: Segment::create(segment_path(&path, number), number)?,
+            retired: Vec::new(),
+        };
+
+        Ok(SpoolTransport {
+            inner,
+            path,
+            segments: Mutex::new(segments),
+            pending: Mutex::new(pending),
+        })
+    }
+
+    /// Sends all undelivered messages from segments left by earlier runs.
+    ///
+    /// Replayed messages go through the spool again, so if the agent dies in
+    /// the middle of the replay, they are not lost. Once all the messages are
+    /// sent, the old segments are removed.
+    pub fn replay(&self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock()
+            .expect("failed to acquire spool pending messages lock"));
+
+        if !pending.entries.is_empty() {
+            log::info!("replaying {} spooled messages", pending.entries.len());
+        }
+
+        for mut entry in pending.entries {
+            let data = entry.take_value();
+            match entry.get_type_url() {
+                RESPONSE_TYPE_URL => self.send(RESPONSE_TYPE_URL, data, T::send_response)?,
+                PARCEL_TYPE_URL => self.send(PARCEL_TYPE_URL, data, T::send_parcel)?,
+                type_url => {
+                    log::warn!("unexpected spool entry type: {}", type_url);
+                }
+            }
+        }
+
+        for segment_path in pending.segment_paths {
+            std::fs::remove_file(segment_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a message through the spool with the given sending function.
+    fn send<F>(&self, type_url: &str, data: Vec<u8>, send: F) -> std::io::Result<()>
+    where
+        F: FnOnce(&T, Vec<u8>) -> std::io::Result<()>,
+    {
+        let mut entry = Any::new();
+        entry.set_type_url(String::from(type_url));
+        entry.set_value(data.clone());
+
+        let (number, index) = self.segments.lock()
+            .expect("failed to acquire spool segments lock")
+            .append(&self.path, entry)?;
+
+        send(&self.inner, data)?;
+
+        self.segments.lock()
+            .expect("failed to acquire spool segments lock")
+            .mark_delivered(number, index)
+    }
+}
+
+impl<T: super::Transport> super::Transport for SpoolTransport<T> {
+
+    fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+        self.inner.receive()
+    }
+
+    fn send_response(&self, data: Vec<u8>) -> std::io::Result<()> {
+        self.send(RESPONSE_TYPE_URL, data, T::send_response)
+    }
+
+    fn send_parcel(&self, data: Vec<u8>) -> std::io::Result<()> {
+        self.send(PARCEL_TYPE_URL, data, T::send_parcel)
+    }
+
+    fn heartbeat(&self) -> std::io::Result<()> {
+        self.inner.heartbeat()
+    }
+}
+
+impl Segments {
+
+    /// Appends a message entry to the current segment.
+    ///
+    /// If the current segment is too big, a fresh one is created first. The
+    /// returned pair consists of the number of the segment the entry has been
+    /// appended to and of the index of the entry within that segment.
+    fn append(&mut self, path: &Path, entry: Any) -> std::io::Result<(u64, u64)> {
+        if self.current.len > SEGMENT_SIZE_LIMIT {
+            let number = self.current.number + 1;
+            let segment = Segment::create(segment_path(path, number), number)?;
+
+            let old_segment = std::mem::replace(&mut self.current, segment);
+            if old_segment.undelivered == 0 {
+                std::fs::remove_file(&old_segment.path)?;
+            } else {
+                self.retired.push(old_segment);
+            }
+        }
+
+        let index = self.current.append(entry)?;
+
+        Ok((self.current.number, index))
+    }
+
+    /// Marks the entry with the given index in the given segment as delivered.
+    ///
+    /// If this was the last undelivered entry of a retired segment, there is
+    /// nothing worth keeping in it anymore and the segment file is removed.
+    fn mark_delivered(&mut self, number: u64, index: u64) -> std::io::Result<()> {
+        let mut index_proto = UInt64Value::new();
+        index_proto.set_value(index);
+
+        let mut marker = Any::new();
+        marker.set_type_url(String::from(DELIVERED_TYPE_URL));
+        marker.set_value(index_proto.write_to_bytes()?);
+
+        if self.current.number == number {
+            return self.current.mark_delivered(marker);
+        }
+
+        let pos = self.retired.iter()
+            .position(|segment| segment.number == number)
+            .expect("delivered entry of an unknown segment");
+        self.retired[pos].mark_delivered(marker)?;
+
+        if self.retired[pos].undelivered == 0 {
+            let segment = self.retired.remove(pos);
+            std::fs::remove_file(&segment.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Segment {
+
+    /// Creates a new empty segment file at the given path.
+    fn create(path: PathBuf, number: u64) -> std::io::Result<Segment> {
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Segment {
+            path,
+            file,
+            len: 0,
+            count: 0,
+            undelivered: 0,
+            number,
+        })
+    }
+
+    /// Appends a message entry to the segment and returns its index.
+    fn append(&mut self, entry: Any) -> std::io::Result<u64> {
+        self.write(entry)?;
+
+        let index = self.count;
+        self.count += 1;
+        self.undelivered += 1;
+
+        Ok(index)
+    }
+
+    /// Appends a delivery marker entry to the segment.
+    fn mark_delivered(&mut self, marker: Any) -> std::io::Result<()> {
+        self.write(marker)?;
+        self.undelivered -= 1;
+
+        Ok(())
+    }
+
+    /// Writes a single entry to the segment file as a separate gzchunked part.
+    fn write(&mut self, entry: Any) -> std::io::Result<()> {
+        // Each entry is written as a whole gzip member, so that a partial write
+        // (e.g. caused by a crash) can corrupt only the last entry.
+        for part in crate::gzchunked::encode(std::iter::once(entry)) {
+            let part = part?;
+            self.file.write_all(&part)?;
+            self.len += part.len() as u64;
+        }
+
+        self.file.flush()
+    }
+}
+
+/// Returns a path to the segment file with the given number.
+fn segment_path(path: &Path, number: u64) -> PathBuf {
+    path.join(format!("{number:020}.{SEGMENT_EXTENSION}"))
+}
+
+/// Reads all undelivered message entries from the given segment file.
+///
+/// A segment that is truncated or corrupted is read up to the first invalid
+/// entry (which most likely was being written while the agent died).
+fn read_segment(path: &Path) -> std::io::Result<Vec<Any>> {
+    let file = std::io::BufReader::new(File::open(path)?);
+
+    // Segments are a concatenation of gzchunked parts, so we can decode them as
+    // a multi-member gzip stream instead of splitting them back into parts.
+    let entries = crate::chunked::decode(flate2::read::MultiGzDecoder::new(file));
+
+    let mut messages = Vec::new();
+    let mut delivered = HashSet::new();
+    for entry in entries {
+        let entry: Any = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                log::warn! {
+                    "failed to read spool segment '{}': {}",
+                    path.display(), error
+                };
+                break;
+            }
+        };
+
+        if entry.get_type_url() == DELIVERED_TYPE_URL {
+            match UInt64Value::parse_from_bytes(entry.get_value()) {
+                Ok(index) => {
+                    delivered.insert(index.get_value());
+                }
+                Err(error) => {
+                    log::warn!("invalid spool delivery marker: {}", error);
+                }
+            }
+        } else {
+            messages.push(entry);
+        }
+    }
+
+    let messages = messages.into_iter()
+        .enumerate()
+        .filter(|(index, _)| !delivered.contains(&(*index as u64)))
+        .map(|(_, message)| message)
+        .collect();
+
+    Ok(messages)
+}
+
+/// Extracts the `(flow_id, request_id, response_id)` triple of a response entry.
+fn response_id(entry: &Any) -> Option<(u64, u64, u64)> {
+    let response = rrg_proto::v2::rrg::Response::parse_from_bytes(entry.get_value())
+        .ok()?;
+
+    Some((
+        response.get_flow_id(),
+        response.get_request_id(),
+        response.get_response_id(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+
+    use crate::transport::Transport as _;
+
+    use super::*;
+
+    /// A transport that records sent messages and can be made to fail.
+    #[derive(Default)]
+    struct FakeTransport {
+        responses: RefCell<Vec<Vec<u8>>>,
+        parcels: RefCell<Vec<Vec<u8>>>,
+        broken: bool,
+    }
+
+    impl FakeTransport {
+
+        fn broken() -> FakeTransport {
+            FakeTransport {
+                broken: true,
+                ..FakeTransport::default()
+            }
+        }
+
+        fn check_broken(&self) -> std::io::Result<()> {
+            if self.broken {
+                use std::io::ErrorKind::BrokenPipe;
+                return Err(std::io::Error::new(BrokenPipe, "broken transport"));
+            }
+
+            Ok(())
+        }
+    }
+
+    impl crate::transport::Transport for FakeTransport {
+
+        fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn send_response(&self, data: Vec<u8>) -> std::io::Result<()> {
+            self.check_broken()?;
+            self.responses.borrow_mut().push(data);
+
+            Ok(())
+        }
+
+        fn send_parcel(&self, data: Vec<u8>) -> std::io::Result<()> {
+            self.check_broken()?;
+            self.parcels.borrow_mut().push(data);
+
+            Ok(())
+        }
+
+        fn heartbeat(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn response(flow_id: u64, request_id: u64, response_id: u64) -> Vec<u8> {
+        let mut response = rrg_proto::v2::rrg::Response::new();
+        response.set_flow_id(flow_id);
+        response.set_request_id(request_id);
+        response.set_response_id(response_id);
+
+        response.write_to_bytes().unwrap()
+    }
+
+    #[test]
+    fn replay_delivered() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.send_response(response(0xF10, 1, 1)).unwrap();
+        spool.send_parcel(b"foo".to_vec()).unwrap();
+        assert_eq!(spool.inner.responses.borrow().len(), 1);
+        assert_eq!(spool.inner.parcels.borrow().len(), 1);
+        drop(spool);
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.replay().unwrap();
+        assert!(spool.inner.responses.borrow().is_empty());
+        assert!(spool.inner.parcels.borrow().is_empty());
+    }
+
+    #[test]
+    fn replay_undelivered() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::broken())
+            .unwrap();
+        assert!(spool.send_response(response(0xF10, 1, 1)).is_err());
+        assert!(spool.send_parcel(b"foo".to_vec()).is_err());
+        drop(spool);
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.replay().unwrap();
+        assert_eq!(*spool.inner.responses.borrow(), vec![response(0xF10, 1, 1)]);
+        assert_eq!(*spool.inner.parcels.borrow(), vec![b"foo".to_vec()]);
+        drop(spool);
+
+        // Replayed messages have been delivered now, so they should not be sent
+        // again after yet another restart.
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.replay().unwrap();
+        assert!(spool.inner.responses.borrow().is_empty());
+        assert!(spool.inner.parcels.borrow().is_empty());
+    }
+
+    #[test]
+    fn replay_deduplicated() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        // We simulate a replay that has been interrupted: the same undelivered
+        // responses end up in two different segments.
+        for _ in 0..2 {
+            let spool = SpoolTransport::open(tempdir.path(), FakeTransport::broken())
+                .unwrap();
+            assert!(spool.send_response(response(0xF10, 1, 1)).is_err());
+            assert!(spool.send_response(response(0xF10, 1, 2)).is_err());
+        }
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.replay().unwrap();
+        assert_eq!(*spool.inner.responses.borrow(), vec! {
+            response(0xF10, 1, 1),
+            response(0xF10, 1, 2),
+        });
+    }
+
+    #[test]
+    fn replay_truncated() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::broken())
+            .unwrap();
+        assert!(spool.send_response(response(0xF10, 1, 1)).is_err());
+        let len_before = spool.segments.lock().unwrap().current.len;
+        assert!(spool.send_response(response(0xF10, 1, 2)).is_err());
+        let len_after = spool.segments.lock().unwrap().current.len;
+        let path = spool.segments.lock().unwrap().current.path.clone();
+        drop(spool);
+
+        // We simulate a crash in the middle of writing the last entry.
+        File::options().write(true).open(&path).unwrap()
+            .set_len((len_before + len_after) / 2).unwrap();
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+        spool.replay().unwrap();
+        assert_eq!(*spool.inner.responses.borrow(), vec![response(0xF10, 1, 1)]);
+    }
+
+    #[test]
+    fn rotate_with_undelivered() {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let spool = SpoolTransport::open(tempdir.path(), FakeTransport::default())
+            .unwrap();
+
+        // We simulate a message that is being sent by another thread while the
+        // segment grows over the limit.
+        let mut entry = Any::new();
+        entry.set_type_url(String::from(RESPONSE_TYPE_URL));
+        entry.set_value(response(0xF10, 1, 1));
+        let (number, index) = spool.segments.lock().unwrap()
+            .append(&spool.path, entry)
+            .unwrap();
+
+        // Random data does not compress, so the segment grows quickly.
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut parcel = || {
+            (0..1024 * 1024).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }).collect::<Vec<u8>>()
+        };
+
+        while spool.segments.lock().unwrap().current.number == number {
+            spool.send_parcel(parcel()).unwrap();
+        }
+
+        // The old segment cannot be removed yet because of the message in
+        // flight, but new messages should not go there anymore.
+        let old_path = segment_path(tempdir.path(), number);
+        assert!(old_path.exists());
+        assert!(spool.segments.lock().unwrap().current.len < SEGMENT_SIZE_LIMIT);
+
+        spool.segments.lock().unwrap()
+            .mark_delivered(number, index)
+            .unwrap();
+        assert!(!old_path.exists());
+    }
+}
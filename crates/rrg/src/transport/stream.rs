@@ -0,0 +1,206 @@
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+/// A transport that communicates over a pair of byte streams.
+///
+/// Requests are read from the input stream and responses and parcels are
+/// written to the output stream. Each request is prefixed with its length
+/// encoded as a varint (the standard framing of length-delimited Protocol
+/// Buffers messages).
+///
+/// Messages written to the output stream are additionally prefixed with a tag
+/// byte that identifies their kind: `0x01` for `rrg.Response` and `0x02` for
+/// `rrg.Parcel` messages. Thus, each output message is a tag byte followed by
+/// the varint-encoded length and the serialized message.
+///
+/// This transport is intended to be used to drive the agent from local test
+/// harnesses (e.g. through standard input and output or a Unix socket) without
+/// running a Fleetspeak client.
+pub struct StreamTransport<R: Read, W: Write> {
+    /// Stream to read serialized requests from.
+    input: Mutex<R>,
+    /// Stream to write serialized responses to.
+    output: Mutex<W>,
+}
+
+/// Tag byte preceding `rrg.Response` messages in the output stream.
+const RESPONSE_TAG: u8 = 0x01;
+
+/// Tag byte preceding `rrg.Parcel` messages in the output stream.
+const PARCEL_TAG: u8 = 0x02;
+
+impl<R: Read, W: Write> StreamTransport<R, W> {
+
+    /// Creates a new transport reading from `input` and writing to `output`.
+    pub fn new(input: R, output: W) -> StreamTransport<R, W> {
+        StreamTransport {
+            input: Mutex::new(input),
+            output: Mutex::new(output),
+        }
+    }
+
+    /// Writes the given message with the given tag to the output stream.
+    fn send(&self, tag: u8, data: Vec<u8>) -> std::io::Result<()> {
+        let mut output = self.output.lock()
+            .expect("failed to acquire transport output stream lock");
+
+        output.write_all(&[tag])?;
+        write_varint(&mut *output, data.len() as u64)?;
+        output.write_all(&data)?;
+        output.flush()
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl StreamTransport<std::os::unix::net::UnixStream, std::os::unix::net::UnixStream> {
+
+    /// Creates a new transport connected to the Unix socket at `path`.
+    pub fn connect<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let input = std::os::unix::net::UnixStream::connect(path)?;
+        let output = input.try_clone()?;
+
+        Ok(StreamTransport::new(input, output))
+    }
+}
+
+impl<R: Read, W: Write> super::Transport for StreamTransport<R, W> {
+
+    fn receive(&self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut input = self.input.lock()
+            .expect("failed to acquire transport input stream lock");
+
+        let len = match read_varint(&mut *input)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        // We do not preallocate the buffer upfront as the length might be bogus
+        // and we could end up trying to allocate a lot of memory.
+        let mut data = Vec::new();
+        (&mut *input).take(len).read_to_end(&mut data)?;
+
+        if data.len() as u64 != len {
+            use std::io::ErrorKind::UnexpectedEof;
+            return Err(std::io::Error::new(UnexpectedEof, "truncated message"));
+        }
+
+        Ok(Some(data))
+    }
+
+    fn send_response(&self, data: Vec<u8>) -> std::io::Result<()> {
+        self.send(RESPONSE_TAG, data)
+    }
+
+    fn send_parcel(&self, data: Vec<u8>) -> std::io::Result<()> {
+        self.send(PARCEL_TAG, data)
+    }
+
+    fn heartbeat(&self) -> std::io::Result<()> {
+        // There is nothing watching the stream for unresponsiveness.
+        Ok(())
+    }
+}
+
+/// Reads a varint-encoded integer from the given stream.
+///
+/// If the stream ended before the first byte of the integer, `None` is returned.
+fn read_varint<R: Read>(input: &mut R) -> std::io::Result<Option<u64>> {
+    let mut value = 0u64;
+
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if input.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+
+            use std::io::ErrorKind::UnexpectedEof;
+            return Err(std::io::Error::new(UnexpectedEof, "truncated varint"));
+        }
+
+        value |= u64::from(byte[0] & 0x7F) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+
+    use std::io::ErrorKind::InvalidData;
+    Err(std::io::Error::new(InvalidData, "varint too long"))
+}
+
+/// Writes a varint-encoded integer to the given stream.
+fn write_varint<W: Write>(output: &mut W, mut value: u64) -> std::io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return output.write_all(&[byte]);
+        }
+        output.write_all(&[byte | 0x80])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::transport::Transport as _;
+
+    #[test]
+    fn receive_empty() {
+        let transport = StreamTransport::new(&b""[..], Vec::new());
+        assert_eq!(transport.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn receive_many() {
+        let transport = StreamTransport::new(&b"\x03foo\x00\x06barbaz"[..], Vec::new());
+        assert_eq!(transport.receive().unwrap(), Some(b"foo".to_vec()));
+        assert_eq!(transport.receive().unwrap(), Some(b"".to_vec()));
+        assert_eq!(transport.receive().unwrap(), Some(b"barbaz".to_vec()));
+        assert_eq!(transport.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn receive_truncated() {
+        let transport = StreamTransport::new(&b"\x06foo"[..], Vec::new());
+        assert!(transport.receive().is_err());
+    }
+
+    #[test]
+    fn send_response() {
+        let transport = StreamTransport::new(&b""[..], Vec::new());
+        transport.send_response(b"foo".to_vec()).unwrap();
+        transport.send_response(vec![0xFF; 300]).unwrap();
+
+        let output = transport.output.into_inner().unwrap();
+        assert_eq!(&output[..5], b"\x01\x03foo");
+        assert_eq!(&output[5..8], b"\x01\xAC\x02");
+        assert_eq!(&output[8..], &[0xFF; 300][..]);
+    }
+
+    #[test]
+    fn send_parcel() {
+        let transport = StreamTransport::new(&b""[..], Vec::new());
+        transport.send_response(b"foo".to_vec()).unwrap();
+        transport.send_parcel(b"quux".to_vec()).unwrap();
+
+        let output = transport.output.into_inner().unwrap();
+        assert_eq!(&output[..], b"\x01\x03foo\x02\x04quux");
+    }
+
+    #[test]
+    fn varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value).unwrap();
+
+            assert_eq!(read_varint(&mut &buf[..]).unwrap(), Some(value));
+        }
+    }
+}